@@ -0,0 +1,288 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::cmp;
+use std::io::{self, ErrorKind, Seek, SeekFrom, Write};
+use std::fs::File;
+use url::Url;
+
+use libc;
+
+use gst_plugin::error::*;
+use gst_plugin_simple::sink::*;
+use gst_plugin_simple::{Flushing, Unlock, UriValidator};
+
+use gst;
+use gst::prelude::*;
+
+use fdsrc::{fd_from_uri, open_fd, validate_fd_uri, wait_fd};
+
+// Writes of at most this size don't block on a pipe once it is writable
+const PIPE_BUF: usize = 4096;
+
+#[derive(Debug)]
+enum StreamingState {
+    Stopped,
+    Started {
+        file: File,
+        position: u64,
+        seekable: bool,
+    },
+}
+
+#[derive(Debug)]
+pub struct FdSink {
+    streaming_state: StreamingState,
+    flushing: Flushing,
+    cat: gst::DebugCategory,
+}
+
+impl FdSink {
+    pub fn new(_sink: &RsBaseSink) -> FdSink {
+        FdSink {
+            streaming_state: StreamingState::Stopped,
+            flushing: Flushing::new(),
+            cat: gst::DebugCategory::new(
+                "rsfdsink",
+                gst::DebugColorFlags::empty(),
+                "Rust file descriptor sink",
+            ),
+        }
+    }
+
+    pub fn new_boxed(sink: &RsBaseSink) -> Box<SinkImpl> {
+        Box::new(FdSink::new(sink))
+    }
+}
+
+// Pipes are written in small pieces once they are writable, so that unlocking
+// can interrupt us in between. Returns false if flushing before everything
+// was written
+fn write_fd(
+    file: &mut File,
+    data: &[u8],
+    position: &mut u64,
+    flushing: &Flushing,
+) -> io::Result<bool> {
+    let mut written = 0;
+    while written < data.len() {
+        if !try!(wait_fd(file, libc::POLLOUT, flushing)) {
+            return Ok(false);
+        }
+
+        let end = cmp::min(data.len(), written + PIPE_BUF);
+        match file.write(&data[written..end]) {
+            Ok(size) => {
+                written += size;
+                *position += size as u64;
+            }
+            Err(ref err) if err.kind() == ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(true)
+}
+
+impl SinkImpl for FdSink {
+    fn uri_validator(&self) -> Box<UriValidator> {
+        Box::new(validate_fd_uri)
+    }
+
+    fn unlock_handle(&self) -> Option<Box<Unlock>> {
+        Some(Box::new(self.flushing.clone()))
+    }
+
+    fn start(&mut self, sink: &RsBaseSink, uri: Url) -> Result<(), ErrorMessage> {
+        if let StreamingState::Started { .. } = self.streaming_state {
+            return Err(error_msg!(
+                gst::LibraryError::Failed,
+                ["Sink already started"]
+            ));
+        }
+
+        let fd = try!(fd_from_uri(&uri).ok_or_else(|| {
+            gst_error!(
+                self.cat,
+                obj: sink,
+                "Unsupported file descriptor URI '{}'",
+                uri.as_str()
+            );
+            error_msg!(
+                gst::LibraryError::Failed,
+                ["Unsupported file descriptor URI '{}'", uri.as_str()]
+            )
+        }));
+
+        let mut file = try!(open_fd(fd).or_else(|err| {
+            gst_error!(
+                self.cat,
+                obj: sink,
+                "Could not open file descriptor {}: {}",
+                fd,
+                err.to_string()
+            );
+            Err(error_msg!(
+                gst::ResourceError::OpenWrite,
+                [
+                    "Could not open file descriptor {} for writing: {}",
+                    fd,
+                    err.to_string()
+                ]
+            ))
+        }));
+
+        let position = file.seek(SeekFrom::Current(0)).ok();
+        let seekable = position.is_some();
+
+        gst_debug!(
+            self.cat,
+            obj: sink,
+            "Opened file descriptor {} (seekable {})",
+            fd,
+            seekable
+        );
+
+        self.streaming_state = StreamingState::Started {
+            file: file,
+            position: position.unwrap_or(0),
+            seekable: seekable,
+        };
+
+        Ok(())
+    }
+
+    fn stop(&mut self, _sink: &RsBaseSink) -> Result<(), ErrorMessage> {
+        self.streaming_state = StreamingState::Stopped;
+
+        Ok(())
+    }
+
+    fn render(&mut self, sink: &RsBaseSink, buffer: &gst::BufferRef) -> Result<(), FlowError> {
+        let cat = self.cat;
+        let flushing = &self.flushing;
+
+        gst_trace!(cat, obj: sink, "Rendering {:?}", buffer);
+
+        let (file, position, seekable) = match self.streaming_state {
+            StreamingState::Started {
+                ref mut file,
+                ref mut position,
+                seekable,
+            } => (file, position, seekable),
+            StreamingState::Stopped => {
+                return Err(FlowError::Error(
+                    error_msg!(gst::LibraryError::Failed, ["Not started yet"]),
+                ));
+            }
+        };
+
+        let map = match buffer.map_readable() {
+            None => {
+                return Err(FlowError::Error(error_msg!(
+                    gst::LibraryError::Failed,
+                    ["Failed to map buffer"]
+                )));
+            }
+            Some(map) => map,
+        };
+        let data = map.as_slice();
+
+        if seekable {
+            try!(file.write_all(data).or_else(|err| {
+                gst_error!(cat, obj: sink, "Failed to write: {}", err);
+                Err(FlowError::Error(error_msg!(
+                    gst::ResourceError::Write,
+                    ["Failed to write: {}", err]
+                )))
+            }));
+            *position += data.len() as u64;
+
+            return Ok(());
+        }
+
+        match write_fd(file, data, position, flushing) {
+            Ok(true) => Ok(()),
+            Ok(false) => {
+                gst_debug!(cat, obj: sink, "Flushing");
+                Err(FlowError::Flushing)
+            }
+            Err(err) => {
+                gst_error!(cat, obj: sink, "Failed to write: {}", err);
+                Err(FlowError::Error(error_msg!(
+                    gst::ResourceError::Write,
+                    ["Failed to write: {}", err]
+                )))
+            }
+        }
+    }
+
+    fn get_position(&self, _sink: &RsBaseSink) -> Option<u64> {
+        match self.streaming_state {
+            StreamingState::Started { position, .. } => Some(position),
+            StreamingState::Stopped => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_fd;
+    use gst_plugin_simple::Flushing;
+    use libc;
+    use std::fs::File;
+    use std::io::Read;
+    use std::os::unix::io::FromRawFd;
+    use std::thread;
+    use std::time::Duration;
+
+    fn pipe() -> (File, File) {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) }
+    }
+
+    #[test]
+    fn test_write_pipe() {
+        let (mut reader, mut writer) = pipe();
+        let data = (0..100_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+
+        // More than fits into the pipe, so writing has to wait for the reader
+        let reader = thread::spawn(move || {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).unwrap();
+            data
+        });
+
+        let flushing = Flushing::new();
+        let mut position = 0;
+        assert!(write_fd(&mut writer, &data, &mut position, &flushing).unwrap());
+        assert_eq!(position, data.len() as u64);
+        drop(writer);
+
+        assert_eq!(reader.join().unwrap(), data);
+    }
+
+    #[test]
+    fn test_write_pipe_unlock() {
+        let (_reader, mut writer) = pipe();
+        let data = vec![0; 1024 * 1024];
+
+        let flushing = Flushing::new();
+        let unlock = flushing.clone();
+        let unlocker = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            unlock.set_flushing(true);
+        });
+
+        let mut position = 0;
+        assert!(!write_fd(&mut writer, &data, &mut position, &flushing).unwrap());
+        assert!(position > 0 && position < data.len() as u64);
+        unlocker.join().unwrap();
+    }
+}
@@ -0,0 +1,388 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom};
+use std::fs::File;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use url::Url;
+
+use libc;
+
+use gst_plugin::error::*;
+use gst_plugin_simple::source::*;
+use gst_plugin_simple::{Flushing, Unlock, UriValidator};
+
+use gst;
+use gst::prelude::*;
+
+// Interval in which blocking operations check if they should be interrupted
+const POLL_TIMEOUT_MS: libc::c_int = 100;
+
+#[derive(Debug)]
+enum StreamingState {
+    Stopped,
+    Started {
+        file: File,
+        position: u64,
+        seekable: bool,
+    },
+}
+
+#[derive(Debug)]
+pub struct FdSrc {
+    streaming_state: StreamingState,
+    flushing: Flushing,
+    cat: gst::DebugCategory,
+}
+
+impl FdSrc {
+    pub fn new(_src: &RsBaseSrc) -> FdSrc {
+        FdSrc {
+            streaming_state: StreamingState::Stopped,
+            flushing: Flushing::new(),
+            cat: gst::DebugCategory::new(
+                "rsfdsrc",
+                gst::DebugColorFlags::empty(),
+                "Rust file descriptor source",
+            ),
+        }
+    }
+
+    pub fn new_boxed(src: &RsBaseSrc) -> Box<SourceImpl> {
+        Box::new(FdSrc::new(src))
+    }
+}
+
+// fd://N, e.g. fd://0 for stdin
+pub fn fd_from_uri(uri: &Url) -> Option<RawFd> {
+    if uri.scheme() != "fd" || (uri.path() != "" && uri.path() != "/") {
+        return None;
+    }
+
+    uri.host_str()
+        .and_then(|host| host.parse::<RawFd>().ok())
+        .and_then(|fd| if fd >= 0 { Some(fd) } else { None })
+}
+
+// Duplicates the file descriptor so that the caller keeps ownership of it
+pub fn open_fd(fd: RawFd) -> io::Result<File> {
+    let fd = unsafe { libc::dup(fd) };
+    if fd < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(unsafe { File::from_raw_fd(fd) })
+    }
+}
+
+// Returns false if flushing before the file descriptor became ready
+pub fn wait_fd(file: &File, events: libc::c_short, flushing: &Flushing) -> io::Result<bool> {
+    loop {
+        if flushing.is_flushing() {
+            return Ok(false);
+        }
+
+        let mut pollfd = libc::pollfd {
+            fd: file.as_raw_fd(),
+            events: events,
+            revents: 0,
+        };
+
+        let res = unsafe { libc::poll(&mut pollfd, 1, POLL_TIMEOUT_MS) };
+        if res < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        } else if res > 0 {
+            return Ok(true);
+        }
+    }
+}
+
+// Reads once data is available. Returns None if flushing before that
+fn read_fd(file: &mut File, data: &mut [u8], flushing: &Flushing) -> io::Result<Option<usize>> {
+    if !try!(wait_fd(file, libc::POLLIN, flushing)) {
+        return Ok(None);
+    }
+
+    file.read(data).map(Some)
+}
+
+// Only regular files have a known size
+fn fd_size(file: &File) -> Option<u64> {
+    file.metadata()
+        .ok()
+        .and_then(|m| if m.is_file() { Some(m.len()) } else { None })
+}
+
+pub fn validate_fd_uri(uri: &Url) -> Result<(), UriError> {
+    match fd_from_uri(uri) {
+        Some(_) => Ok(()),
+        None => Err(UriError::new(
+            gst::URIError::UnsupportedProtocol,
+            format!("Unsupported file descriptor URI '{}'", uri.as_str()),
+        )),
+    }
+}
+
+impl SourceImpl for FdSrc {
+    fn uri_validator(&self) -> Box<UriValidator> {
+        Box::new(validate_fd_uri)
+    }
+
+    fn unlock_handle(&self) -> Option<Box<Unlock>> {
+        Some(Box::new(self.flushing.clone()))
+    }
+
+    fn is_seekable(&self, _src: &RsBaseSrc) -> bool {
+        match self.streaming_state {
+            StreamingState::Started { seekable, .. } => seekable,
+            StreamingState::Stopped => false,
+        }
+    }
+
+    fn get_size(&self, _src: &RsBaseSrc) -> Option<u64> {
+        match self.streaming_state {
+            StreamingState::Started {
+                ref file,
+                seekable: true,
+                ..
+            } => fd_size(file),
+            _ => None,
+        }
+    }
+
+    fn start(&mut self, src: &RsBaseSrc, uri: Url) -> Result<(), ErrorMessage> {
+        if let StreamingState::Started { .. } = self.streaming_state {
+            return Err(error_msg!(
+                gst::LibraryError::Failed,
+                ["Source already started"]
+            ));
+        }
+
+        let fd = try!(fd_from_uri(&uri).ok_or_else(|| {
+            gst_error!(
+                self.cat,
+                obj: src,
+                "Unsupported file descriptor URI '{}'",
+                uri.as_str()
+            );
+            error_msg!(
+                gst::LibraryError::Failed,
+                ["Unsupported file descriptor URI '{}'", uri.as_str()]
+            )
+        }));
+
+        let mut file = try!(open_fd(fd).or_else(|err| {
+            gst_error!(
+                self.cat,
+                obj: src,
+                "Could not open file descriptor {}: {}",
+                fd,
+                err.to_string()
+            );
+            Err(error_msg!(
+                gst::ResourceError::OpenRead,
+                [
+                    "Could not open file descriptor {} for reading: {}",
+                    fd,
+                    err.to_string()
+                ]
+            ))
+        }));
+
+        // Pipes, sockets and terminals can't seek
+        let position = file.seek(SeekFrom::Current(0)).ok();
+        let seekable = position.is_some();
+
+        gst_debug!(
+            self.cat,
+            obj: src,
+            "Opened file descriptor {} (seekable {})",
+            fd,
+            seekable
+        );
+
+        self.streaming_state = StreamingState::Started {
+            file: file,
+            position: position.unwrap_or(0),
+            seekable: seekable,
+        };
+
+        Ok(())
+    }
+
+    fn stop(&mut self, _src: &RsBaseSrc) -> Result<(), ErrorMessage> {
+        self.streaming_state = StreamingState::Stopped;
+
+        Ok(())
+    }
+
+    fn fill(
+        &mut self,
+        src: &RsBaseSrc,
+        offset: u64,
+        _: u32,
+        buffer: &mut gst::BufferRef,
+    ) -> Result<(), FlowError> {
+        let cat = self.cat;
+        let flushing = &self.flushing;
+
+        let (file, position, seekable) = match self.streaming_state {
+            StreamingState::Started {
+                ref mut file,
+                ref mut position,
+                seekable,
+            } => (file, position, seekable),
+            StreamingState::Stopped => {
+                return Err(FlowError::Error(
+                    error_msg!(gst::LibraryError::Failed, ["Not started yet"]),
+                ));
+            }
+        };
+
+        if *position != offset {
+            if !seekable {
+                return Err(FlowError::Error(error_msg!(
+                    gst::ResourceError::Seek,
+                    ["Got unexpected offset {}, expected {}", offset, position]
+                )));
+            }
+
+            try!(file.seek(SeekFrom::Start(offset)).or_else(|err| {
+                gst_error!(cat, obj: src, "Failed to seek to {}: {:?}", offset, err);
+                Err(FlowError::Error(error_msg!(
+                    gst::ResourceError::Seek,
+                    ["Failed to seek to {}: {}", offset, err.to_string()]
+                )))
+            }));
+            *position = offset;
+        }
+
+        let size = {
+            let mut map = match buffer.map_writable() {
+                None => {
+                    return Err(FlowError::Error(error_msg!(
+                        gst::LibraryError::Failed,
+                        ["Failed to map buffer"]
+                    )));
+                }
+                Some(map) => map,
+            };
+
+            let data = map.as_mut_slice();
+
+            // Pipes, sockets and terminals are polled so that unlocking can
+            // interrupt waiting for data
+            let res = if seekable {
+                file.read(data).map(Some)
+            } else {
+                read_fd(file, data, flushing)
+            };
+
+            match res {
+                Ok(Some(size)) => size,
+                Ok(None) => {
+                    gst_debug!(cat, obj: src, "Flushing");
+                    return Err(FlowError::Flushing);
+                }
+                Err(err) => {
+                    gst_error!(cat, obj: src, "Failed to read: {:?}", err);
+                    return Err(FlowError::Error(error_msg!(
+                        gst::ResourceError::Read,
+                        ["Failed to read at {}: {}", offset, err.to_string()]
+                    )));
+                }
+            }
+        };
+
+        if size == 0 {
+            return Err(FlowError::Eos);
+        }
+
+        *position += size as u64;
+
+        buffer.set_size(size);
+
+        Ok(())
+    }
+
+    fn seek(&mut self, _src: &RsBaseSrc, _: u64, _: Option<u64>) -> Result<(), ErrorMessage> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fd_from_uri, fd_size, open_fd, read_fd};
+    use gst_plugin_simple::Flushing;
+    use libc;
+    use std::fs::File;
+    use std::io::{Seek, SeekFrom, Write};
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+    use std::thread;
+    use std::time::Duration;
+    use url::Url;
+
+    fn pipe() -> (File, File) {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) }
+    }
+
+    #[test]
+    fn test_fd_from_uri() {
+        assert_eq!(fd_from_uri(&Url::parse("fd://0").unwrap()), Some(0));
+        assert_eq!(fd_from_uri(&Url::parse("fd://42/").unwrap()), Some(42));
+        assert_eq!(fd_from_uri(&Url::parse("fd://stdin").unwrap()), None);
+        assert_eq!(fd_from_uri(&Url::parse("fd://1/path").unwrap()), None);
+        assert_eq!(fd_from_uri(&Url::parse("file:///0").unwrap()), None);
+    }
+
+    #[test]
+    fn test_read_pipe() {
+        let (reader, mut writer) = pipe();
+        let mut file = open_fd(reader.as_raw_fd()).unwrap();
+        drop(reader);
+
+        // Pipes can't seek and have no size
+        assert!(file.seek(SeekFrom::Current(0)).is_err());
+        assert_eq!(fd_size(&file), None);
+
+        let flushing = Flushing::new();
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            writer.write_all(b"hello").unwrap();
+        });
+
+        let mut data = [0; 16];
+        assert_eq!(read_fd(&mut file, &mut data, &flushing).unwrap(), Some(5));
+        assert_eq!(&data[..5], b"hello");
+
+        // End of stream once the writer is closed
+        writer.join().unwrap();
+        assert_eq!(read_fd(&mut file, &mut data, &flushing).unwrap(), Some(0));
+    }
+
+    #[test]
+    fn test_read_pipe_unlock() {
+        let (mut reader, _writer) = pipe();
+
+        let flushing = Flushing::new();
+        let unlock = flushing.clone();
+        let unlocker = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            unlock.set_flushing(true);
+        });
+
+        let mut data = [0; 16];
+        assert_eq!(read_fd(&mut reader, &mut data, &flushing).unwrap(), None);
+        unlocker.join().unwrap();
+    }
+}
@@ -7,28 +7,225 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::fs::File;
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use std::u64;
 use url::Url;
 
-use std::io::Write;
+use std::io::{self, ErrorKind, Seek, SeekFrom, Write};
 use std::convert::From;
 
 use gst_plugin::error::*;
+use gst_plugin::properties::*;
 use gst_plugin_simple::sink::*;
-use gst_plugin_simple::UriValidator;
+use gst_plugin_simple::{Flushing, PropertyHandle, SettingsImpl, SharedSettings, Unlock,
+                        UriValidator};
 
+use glib;
 use gst;
 use gst::prelude::*;
 
+#[cfg(unix)]
+use iothread::WriteBehind;
+
+// The I/O thread needs Unix file APIs. Elsewhere the write-behind-size
+// property does not exist and writes always happen directly
+#[cfg(not(unix))]
+#[derive(Debug)]
+enum WriteBehind {}
+
+#[cfg(not(unix))]
+impl WriteBehind {
+    fn new(_file: File, _max_size: u64) -> io::Result<WriteBehind> {
+        Err(io::Error::new(ErrorKind::Other, "Not supported"))
+    }
+
+    fn write(&self, _data: Vec<u8>, _flushing: &Flushing) -> Result<bool, ErrorMessage> {
+        match *self {}
+    }
+
+    fn seek(&self, _offset: u64) -> Result<(), ErrorMessage> {
+        match *self {}
+    }
+
+    fn sync(&self, _flushing: &Flushing) -> Result<bool, ErrorMessage> {
+        match *self {}
+    }
+
+    fn drain(&self, _flushing: &Flushing) -> Result<bool, ErrorMessage> {
+        match *self {}
+    }
+}
+
+const DEFAULT_APPEND: bool = false;
+const DEFAULT_OVERWRITE: bool = true;
+const DEFAULT_RANDOM_ACCESS: bool = false;
+const DEFAULT_ATOMIC: bool = false;
+const DEFAULT_FSYNC_ON_EOS: bool = false;
+const DEFAULT_FSYNC_INTERVAL: u64 = 0;
+const DEFAULT_FSYNC_BYTES: u64 = 0;
+const DEFAULT_WRITE_BEHIND_SIZE: u64 = 0;
+
+#[derive(Debug, Clone, Copy)]
+struct Settings {
+    append: bool,
+    overwrite: bool,
+    random_access: bool,
+    atomic: bool,
+    fsync_on_eos: bool,
+    fsync_interval: u64,
+    fsync_bytes: u64,
+    write_behind_size: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            append: DEFAULT_APPEND,
+            overwrite: DEFAULT_OVERWRITE,
+            random_access: DEFAULT_RANDOM_ACCESS,
+            atomic: DEFAULT_ATOMIC,
+            fsync_on_eos: DEFAULT_FSYNC_ON_EOS,
+            fsync_interval: DEFAULT_FSYNC_INTERVAL,
+            fsync_bytes: DEFAULT_FSYNC_BYTES,
+            write_behind_size: DEFAULT_WRITE_BEHIND_SIZE,
+        }
+    }
+}
+
+impl SettingsImpl for Settings {
+    fn set_property(&mut self, property: &Property, value: &glib::Value) -> bool {
+        match *property {
+            Property::Boolean("append", ..) => {
+                self.append = value.get().unwrap();
+            }
+            Property::Boolean("overwrite", ..) => {
+                self.overwrite = value.get().unwrap();
+            }
+            Property::Boolean("random-access", ..) => {
+                self.random_access = value.get().unwrap();
+            }
+            Property::Boolean("atomic", ..) => {
+                self.atomic = value.get().unwrap();
+            }
+            Property::Boolean("fsync-on-eos", ..) => {
+                self.fsync_on_eos = value.get().unwrap();
+            }
+            Property::UInt64("fsync-interval", ..) => {
+                self.fsync_interval = value.get().unwrap();
+            }
+            Property::UInt64("fsync-bytes", ..) => {
+                self.fsync_bytes = value.get().unwrap();
+            }
+            Property::UInt64("write-behind-size", ..) => {
+                self.write_behind_size = value.get().unwrap();
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn get_property(&self, property: &Property) -> Option<glib::Value> {
+        match *property {
+            Property::Boolean("append", ..) => Some(self.append.to_value()),
+            Property::Boolean("overwrite", ..) => Some(self.overwrite.to_value()),
+            Property::Boolean("random-access", ..) => Some(self.random_access.to_value()),
+            Property::Boolean("atomic", ..) => Some(self.atomic.to_value()),
+            Property::Boolean("fsync-on-eos", ..) => Some(self.fsync_on_eos.to_value()),
+            Property::UInt64("fsync-interval", ..) => Some(self.fsync_interval.to_value()),
+            Property::UInt64("fsync-bytes", ..) => Some(self.fsync_bytes.to_value()),
+            Property::UInt64("write-behind-size", ..) => Some(self.write_behind_size.to_value()),
+            _ => None,
+        }
+    }
+}
+
+pub static PROPERTIES: &[Property] = &[
+    Property::Boolean(
+        "append",
+        "Append",
+        "Append to an already existing file",
+        DEFAULT_APPEND,
+        PropertyMutability::ReadWrite,
+    ),
+    Property::Boolean(
+        "overwrite",
+        "Overwrite",
+        "Overwrite an already existing file instead of failing",
+        DEFAULT_OVERWRITE,
+        PropertyMutability::ReadWrite,
+    ),
+    Property::Boolean(
+        "random-access",
+        "Random Access",
+        "Seek in the file according to BYTES segments before writing",
+        DEFAULT_RANDOM_ACCESS,
+        PropertyMutability::ReadWrite,
+    ),
+    Property::Boolean(
+        "atomic",
+        "Atomic",
+        "Write to a temporary file and only rename it to the final location once finished",
+        DEFAULT_ATOMIC,
+        PropertyMutability::ReadWrite,
+    ),
+    Property::Boolean(
+        "fsync-on-eos",
+        "Fsync on EOS",
+        "Synchronize the file to disk on EOS",
+        DEFAULT_FSYNC_ON_EOS,
+        PropertyMutability::ReadWrite,
+    ),
+    Property::UInt64(
+        "fsync-interval",
+        "Fsync Interval",
+        "Synchronize the file to disk at most this many nanoseconds after the last time (0=never)",
+        (0, u64::MAX),
+        DEFAULT_FSYNC_INTERVAL,
+        PropertyMutability::ReadWrite,
+    ),
+    Property::UInt64(
+        "fsync-bytes",
+        "Fsync Bytes",
+        "Synchronize the file to disk every time this many bytes were written (0=never)",
+        (0, u64::MAX),
+        DEFAULT_FSYNC_BYTES,
+        PropertyMutability::ReadWrite,
+    ),
+    #[cfg(unix)]
+    Property::UInt64(
+        "write-behind-size",
+        "Write Behind Size",
+        "Maximum number of bytes queued for writing by a background thread (0=write directly)",
+        (0, u64::MAX),
+        DEFAULT_WRITE_BEHIND_SIZE,
+        PropertyMutability::ReadWrite,
+    ),
+];
+
 #[derive(Debug)]
 enum StreamingState {
     Stopped,
-    Started { file: File, position: u64 },
+    Started {
+        file: File,
+        position: u64,
+        location: PathBuf,
+        temp_file: Option<TempFile>,
+        unsynced_bytes: u64,
+        last_sync: Instant,
+        failed: bool,
+        write_behind: Option<WriteBehind>,
+    },
 }
 
 #[derive(Debug)]
 pub struct FileSink {
     streaming_state: StreamingState,
+    settings: Settings,
+    shared_settings: SharedSettings<Settings>,
+    flushing: Flushing,
     cat: gst::DebugCategory,
 }
 
@@ -36,6 +233,9 @@ impl FileSink {
     pub fn new(_sink: &RsBaseSink) -> FileSink {
         FileSink {
             streaming_state: StreamingState::Stopped,
+            settings: Default::default(),
+            shared_settings: SharedSettings::new(Default::default()),
+            flushing: Flushing::new(),
             cat: gst::DebugCategory::new(
                 "rsfilesink",
                 gst::DebugColorFlags::empty(),
@@ -47,6 +247,82 @@ impl FileSink {
     pub fn new_boxed(sink: &RsBaseSink) -> Box<SinkImpl> {
         Box::new(FileSink::new(sink))
     }
+
+    // Does nothing if flushing before the queued writes were finished. The
+    // file is finished when stopping then
+    fn finish(
+        &mut self,
+        sink: &RsBaseSink,
+        fsync: bool,
+        flushing: &Flushing,
+    ) -> Result<(), ErrorMessage> {
+        let cat = self.cat;
+        let overwrite = self.settings.overwrite;
+
+        let (file, location, temp_file, unsynced_bytes, last_sync, write_behind) =
+            match self.streaming_state {
+                StreamingState::Started {
+                    ref file,
+                    ref location,
+                    ref mut temp_file,
+                    ref mut unsynced_bytes,
+                    ref mut last_sync,
+                    ref write_behind,
+                    ..
+                } => (
+                    file,
+                    location,
+                    temp_file,
+                    unsynced_bytes,
+                    last_sync,
+                    write_behind,
+                ),
+                StreamingState::Stopped => {
+                    return Err(error_msg!(gst::LibraryError::Failed, ["Not started yet"]));
+                }
+            };
+
+        if let Some(ref write_behind) = *write_behind {
+            gst_debug!(cat, obj: sink, "Waiting for queued writes to finish");
+            if !try!(write_behind.drain(flushing)) {
+                gst_debug!(cat, obj: sink, "Flushing while waiting for queued writes");
+                return Ok(());
+            }
+        }
+
+        // The temporary file always has to be on disk before it replaces the final file
+        if fsync || temp_file.is_some() {
+            try!(sync_file(cat, sink, file));
+            *unsynced_bytes = 0;
+            *last_sync = Instant::now();
+        }
+
+        if let Some(temp_file) = temp_file.take() {
+            gst_debug!(
+                cat,
+                obj: sink,
+                "Moving {:?} to final location {:?}",
+                temp_file.temp_location,
+                location
+            );
+
+            let temp_location = temp_file.temp_location.clone();
+            try!(temp_file.commit(overwrite).or_else(|err| {
+                gst_error!(cat, obj: sink, "Failed to move file into place: {}", err);
+                Err(error_msg!(
+                    gst::ResourceError::Write,
+                    [
+                        "Failed to move '{}' to '{}': {}",
+                        temp_location.to_str().unwrap_or("Non-UTF8 path"),
+                        location.to_str().unwrap_or("Non-UTF8 path"),
+                        err.to_string()
+                    ]
+                ))
+            }));
+        }
+
+        Ok(())
+    }
 }
 
 fn validate_uri(uri: &Url) -> Result<(), UriError> {
@@ -59,12 +335,131 @@ fn validate_uri(uri: &Url) -> Result<(), UriError> {
     Ok(())
 }
 
+fn sync_file(cat: gst::DebugCategory, sink: &RsBaseSink, file: &File) -> Result<(), ErrorMessage> {
+    gst_trace!(cat, obj: sink, "Synchronizing file to disk");
+
+    sync_data(file).or_else(|msg| {
+        gst_error!(cat, obj: sink, "Failed to synchronize file: {:?}", msg);
+        Err(msg)
+    })
+}
+
+fn sync_data(file: &File) -> Result<(), ErrorMessage> {
+    file.sync_data().or_else(|err| {
+        Err(error_msg!(
+            gst::ResourceError::Sync,
+            ["Failed to synchronize file to disk: {}", err.to_string()]
+        ))
+    })
+}
+
+// Whether fsync-bytes or fsync-interval require synchronizing the file now
+fn sync_due(settings: &Settings, unsynced_bytes: u64, since_last_sync: Duration) -> bool {
+    let bytes_reached = settings.fsync_bytes != 0 && unsynced_bytes >= settings.fsync_bytes;
+    let interval_reached = settings.fsync_interval != 0
+        && since_last_sync.as_secs() * gst::SECOND + u64::from(since_last_sync.subsec_nanos())
+            >= settings.fsync_interval;
+
+    bytes_reached || interval_reached
+}
+
+// Makes a rename in the directory durable. Directories can only be opened and
+// synchronized like this on Unix
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir).and_then(|dir| dir.sync_all())
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+// Temporary file next to the final location. It is removed again when
+// dropped without being committed, e.g. if writing failed
+#[derive(Debug)]
+struct TempFile {
+    location: PathBuf,
+    temp_location: PathBuf,
+    committed: bool,
+}
+
+impl TempFile {
+    fn create(location: &Path) -> io::Result<(File, TempFile)> {
+        let dir = location.parent().unwrap_or_else(|| Path::new("."));
+        let name = location
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let mut idx = 0;
+        loop {
+            let temp_location = dir.join(format!(".{}.{}.tmp", name, idx));
+
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&temp_location)
+            {
+                Ok(file) => {
+                    return Ok((
+                        file,
+                        TempFile {
+                            location: location.to_path_buf(),
+                            temp_location: temp_location,
+                            committed: false,
+                        },
+                    ))
+                }
+                Err(ref err) if err.kind() == ErrorKind::AlreadyExists => idx += 1,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    // Moves the temporary file to the final location and makes sure that
+    // the new directory entry is on disk too
+    fn commit(mut self, overwrite: bool) -> io::Result<()> {
+        if overwrite {
+            try!(fs::rename(&self.temp_location, &self.location));
+        } else {
+            // Linking fails if the target exists, unlike renaming
+            try!(fs::hard_link(&self.temp_location, &self.location));
+            let _ = fs::remove_file(&self.temp_location);
+        }
+        self.committed = true;
+
+        match self.location.parent() {
+            Some(dir) if dir != Path::new("") => sync_dir(dir),
+            _ => sync_dir(Path::new(".")),
+        }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.temp_location);
+        }
+    }
+}
+
 impl SinkImpl for FileSink {
     fn uri_validator(&self) -> Box<UriValidator> {
         Box::new(validate_uri)
     }
 
+    fn unlock_handle(&self) -> Option<Box<Unlock>> {
+        Some(Box::new(self.flushing.clone()))
+    }
+
+    fn property_handle(&self) -> Option<Box<PropertyHandle>> {
+        Some(Box::new(self.shared_settings.clone()))
+    }
+
     fn start(&mut self, sink: &RsBaseSink, uri: Url) -> Result<(), ErrorMessage> {
+        self.shared_settings.update(&mut self.settings);
+
         if let StreamingState::Started { .. } = self.streaming_state {
             return Err(error_msg!(
                 gst::LibraryError::Failed,
@@ -85,57 +480,183 @@ impl SinkImpl for FileSink {
             ))
         }));
 
+        if self.settings.atomic && self.settings.append {
+            return Err(error_msg!(
+                gst::LibraryError::Settings,
+                ["Appending to a file can't be done atomically"]
+            ));
+        }
 
-        let file = try!(File::create(location.as_path()).or_else(|err| {
-            gst_error!(
-                self.cat,
-                obj: sink,
-                "Could not open file for writing: {}",
-                err.to_string()
-            );
-            Err(error_msg!(
-                gst::ResourceError::OpenWrite,
-                [
-                    "Could not open file for writing '{}': {}",
-                    location.to_str().unwrap_or("Non-UTF8 path"),
+        let open_error = |err: io::Error| {
+            if err.kind() == ErrorKind::AlreadyExists {
+                error_msg!(
+                    gst::ResourceError::OpenWrite,
+                    (
+                        "File '{}' already exists",
+                        location.to_str().unwrap_or("Non-UTF8 path")
+                    ),
+                    ["Not overwriting existing file: {}", err.to_string()]
+                )
+            } else {
+                error_msg!(
+                    gst::ResourceError::OpenWrite,
+                    [
+                        "Could not open file for writing '{}': {}",
+                        location.to_str().unwrap_or("Non-UTF8 path"),
+                        err.to_string()
+                    ]
+                )
+            }
+        };
+
+        let (mut file, temp_file) = if self.settings.atomic {
+            if !self.settings.overwrite && location.exists() {
+                return Err(open_error(io::Error::from(ErrorKind::AlreadyExists)));
+            }
+
+            let (file, temp_file) = try!(TempFile::create(&location).or_else(|err| {
+                gst_error!(
+                    self.cat,
+                    obj: sink,
+                    "Could not create temporary file: {}",
                     err.to_string()
-                ]
-            ))
-        }));
+                );
+                Err(open_error(err))
+            }));
+
+            (file, Some(temp_file))
+        } else {
+            let mut options = OpenOptions::new();
+            options.write(true);
+            if self.settings.append {
+                options.create(true);
+            } else if self.settings.overwrite {
+                options.create(true).truncate(true);
+            } else {
+                options.create_new(true);
+            }
 
-        gst_debug!(self.cat, obj: sink, "Opened file {:?}", file);
+            let file = try!(options.open(location.as_path()).or_else(|err| {
+                gst_error!(
+                    self.cat,
+                    obj: sink,
+                    "Could not open file for writing: {}",
+                    err.to_string()
+                );
+                Err(open_error(err))
+            }));
+
+            (file, None)
+        };
+
+        let position = if self.settings.append {
+            try!(file.seek(SeekFrom::End(0)).or_else(|err| {
+                gst_error!(self.cat, obj: sink, "Failed to seek to end: {}", err);
+                Err(error_msg!(
+                    gst::ResourceError::Seek,
+                    ["Failed to seek to end of file: {}", err.to_string()]
+                ))
+            }))
+        } else {
+            0
+        };
+
+        gst_debug!(
+            self.cat,
+            obj: sink,
+            "Opened file {:?} at position {} (temporary location {:?})",
+            file,
+            position,
+            temp_file.as_ref().map(|temp_file| &temp_file.temp_location)
+        );
+
+        let write_behind = if self.settings.write_behind_size != 0 {
+            let write_behind_size = self.settings.write_behind_size;
+            let write_behind = try!(
+                file.try_clone()
+                    .and_then(|file| WriteBehind::new(file, write_behind_size))
+                    .or_else(|err| {
+                        gst_error!(
+                            self.cat,
+                            obj: sink,
+                            "Could not start write behind thread: {}",
+                            err.to_string()
+                        );
+                        Err(error_msg!(
+                            gst::ResourceError::OpenWrite,
+                            ["Could not start write behind thread: {}", err.to_string()]
+                        ))
+                    })
+            );
+
+            Some(write_behind)
+        } else {
+            None
+        };
 
         self.streaming_state = StreamingState::Started {
             file: file,
-            position: 0,
+            position: position,
+            location: location,
+            temp_file: temp_file,
+            unsynced_bytes: 0,
+            last_sync: Instant::now(),
+            failed: false,
+            write_behind: write_behind,
         };
 
         Ok(())
     }
 
-    fn stop(&mut self, _sink: &RsBaseSink) -> Result<(), ErrorMessage> {
+    fn stop(&mut self, sink: &RsBaseSink) -> Result<(), ErrorMessage> {
+        let failed = match self.streaming_state {
+            StreamingState::Started { failed, .. } => failed,
+            StreamingState::Stopped => return Ok(()),
+        };
+
+        // Everything that was written is kept when stopping, also before EOS.
+        // Only if writing failed the temporary file is removed when dropped and
+        // the final location is left untouched. Queued writes are always
+        // waited for here, independent of flushing
+        let res = if failed {
+            gst_debug!(self.cat, obj: sink, "Stopping without finishing the file");
+            Ok(())
+        } else {
+            self.finish(sink, false, &Flushing::new())
+        };
+
         self.streaming_state = StreamingState::Stopped;
 
-        Ok(())
+        res
     }
 
     fn render(&mut self, sink: &RsBaseSink, buffer: &gst::BufferRef) -> Result<(), FlowError> {
+        self.shared_settings.update(&mut self.settings);
+
         let cat = self.cat;
+        let settings = self.settings;
+        let flushing = &self.flushing;
         let streaming_state = &mut self.streaming_state;
 
         gst_trace!(cat, obj: sink, "Rendering {:?}", buffer);
 
-        let (file, position) = match *streaming_state {
-            StreamingState::Started {
-                ref mut file,
-                ref mut position,
-            } => (file, position),
-            StreamingState::Stopped => {
-                return Err(FlowError::Error(
-                    error_msg!(gst::LibraryError::Failed, ["Not started yet"]),
-                ));
-            }
-        };
+        let (file, position, unsynced_bytes, last_sync, failed, write_behind) =
+            match *streaming_state {
+                StreamingState::Started {
+                    ref mut file,
+                    ref mut position,
+                    ref mut unsynced_bytes,
+                    ref mut last_sync,
+                    ref mut failed,
+                    ref write_behind,
+                    ..
+                } => (file, position, unsynced_bytes, last_sync, failed, write_behind),
+                StreamingState::Stopped => {
+                    return Err(FlowError::Error(
+                        error_msg!(gst::LibraryError::Failed, ["Not started yet"]),
+                    ));
+                }
+            };
 
         let map = match buffer.map_readable() {
             None => {
@@ -148,16 +669,214 @@ impl SinkImpl for FileSink {
         };
         let data = map.as_slice();
 
-        try!(file.write_all(data).or_else(|err| {
-            gst_error!(cat, obj: sink, "Failed to write: {}", err);
-            Err(FlowError::Error(error_msg!(
-                gst::ResourceError::Write,
-                ["Failed to write: {}", err]
-            )))
-        }));
+        if let Some(ref write_behind) = *write_behind {
+            match write_behind.write(data.to_vec(), flushing) {
+                Ok(true) => (),
+                Ok(false) => {
+                    gst_debug!(cat, obj: sink, "Flushing while waiting for queued writes");
+                    return Err(FlowError::Flushing);
+                }
+                Err(msg) => {
+                    gst_error!(cat, obj: sink, "Failed to write: {:?}", msg);
+                    *failed = true;
+                    return Err(FlowError::Error(msg));
+                }
+            }
+        } else {
+            try!(file.write_all(data).or_else(|err| {
+                gst_error!(cat, obj: sink, "Failed to write: {}", err);
+                *failed = true;
+                Err(FlowError::Error(error_msg!(
+                    gst::ResourceError::Write,
+                    ["Failed to write: {}", err]
+                )))
+            }));
+        }
 
         *position += data.len() as u64;
+        *unsynced_bytes += data.len() as u64;
+
+        if sync_due(&settings, *unsynced_bytes, last_sync.elapsed()) {
+            let res = match *write_behind {
+                Some(ref write_behind) => write_behind.sync(flushing),
+                None => sync_file(cat, sink, file).map(|_| true),
+            };
+
+            let synced = try!(res.or_else(|msg| {
+                *failed = true;
+                Err(FlowError::Error(msg))
+            }));
+            if !synced {
+                gst_debug!(cat, obj: sink, "Flushing while synchronizing");
+                return Err(FlowError::Flushing);
+            }
+            *unsynced_bytes = 0;
+            *last_sync = Instant::now();
+        }
+
+        Ok(())
+    }
+
+    fn eos(&mut self, sink: &RsBaseSink) -> Result<(), ErrorMessage> {
+        let fsync = self.settings.fsync_on_eos;
+        let flushing = self.flushing.clone();
+        self.finish(sink, fsync, &flushing)
+    }
+
+    fn is_seekable(&self, _sink: &RsBaseSink) -> bool {
+        self.settings.random_access
+    }
+
+    fn get_position(&self, _sink: &RsBaseSink) -> Option<u64> {
+        match self.streaming_state {
+            StreamingState::Started { position, .. } => Some(position),
+            StreamingState::Stopped => None,
+        }
+    }
+
+    fn seek(&mut self, sink: &RsBaseSink, offset: u64) -> Result<(), ErrorMessage> {
+        let cat = self.cat;
+
+        let (file, position, write_behind) = match self.streaming_state {
+            StreamingState::Started {
+                ref mut file,
+                ref mut position,
+                ref write_behind,
+                ..
+            } => (file, position, write_behind),
+            StreamingState::Stopped => {
+                return Err(error_msg!(gst::LibraryError::Failed, ["Not started yet"]));
+            }
+        };
+
+        // Queued in order with the writes so that earlier data still ends up
+        // at the right place
+        if let Some(ref write_behind) = *write_behind {
+            try!(write_behind.seek(offset));
+            *position = offset;
+
+            return Ok(());
+        }
+
+        try!(file.seek(SeekFrom::Start(offset)).or_else(|err| {
+            gst_error!(cat, obj: sink, "Failed to seek to {}: {}", offset, err);
+            Err(error_msg!(
+                gst::ResourceError::Seek,
+                ["Failed to seek to {}: {}", offset, err.to_string()]
+            ))
+        }));
+        *position = offset;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{sync_data, sync_due, Settings, TempFile};
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::{Read, Write};
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use gst;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos();
+        let dir = env::temp_dir().join(format!("rsfilesink-{}-{}", name, nanos));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(path: &Path, data: &str) {
+        File::create(path)
+            .unwrap()
+            .write_all(data.as_bytes())
+            .unwrap();
+    }
+
+    fn read(path: &Path) -> String {
+        let mut data = String::new();
+        File::open(path).unwrap().read_to_string(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_temp_file_commit() {
+        let dir = test_dir("commit");
+        let location = dir.join("out.flv");
+        write(&location, "old");
+
+        let (mut file, temp_file) = TempFile::create(&location).unwrap();
+        file.write_all(b"new").unwrap();
+        temp_file.commit(true).unwrap();
+
+        assert_eq!(read(&location), "new");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // Failed writes drop the temporary file without committing it
+    #[test]
+    fn test_temp_file_discard() {
+        let dir = test_dir("discard");
+        let location = dir.join("out.flv");
+        write(&location, "old");
+
+        let (mut file, temp_file) = TempFile::create(&location).unwrap();
+        file.write_all(b"partial").unwrap();
+        assert!(temp_file.temp_location.exists());
+        drop(temp_file);
+
+        assert_eq!(read(&location), "old");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        let other = dir.join("other.flv");
+        let (_, temp_file) = TempFile::create(&other).unwrap();
+        drop(temp_file);
+        assert!(!other.exists());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sync_due() {
+        let mut settings = Settings::default();
+        assert!(!sync_due(&settings, u64::max_value(), Duration::from_secs(3600)));
+
+        settings.fsync_bytes = 100;
+        assert!(!sync_due(&settings, 99, Duration::from_secs(3600)));
+        assert!(sync_due(&settings, 100, Duration::from_secs(0)));
+
+        settings.fsync_bytes = 0;
+        settings.fsync_interval = gst::SECOND;
+        assert!(!sync_due(&settings, 1000, Duration::from_millis(999)));
+        assert!(sync_due(&settings, 0, Duration::from_secs(1)));
+
+        settings.fsync_bytes = 100;
+        assert!(sync_due(&settings, 100, Duration::from_millis(1)));
+        assert!(sync_due(&settings, 1, Duration::from_secs(2)));
+        assert!(!sync_due(&settings, 1, Duration::from_millis(1)));
+    }
+
+    // fdatasync() is not possible on character devices
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_sync_error() {
+        let file = File::open("/dev/null").unwrap();
+        let msg = sync_data(&file).unwrap_err();
+        assert!(format!("{:?}", msg).contains("Failed to synchronize file to disk"));
+
+        let dir = test_dir("sync");
+        let file = File::create(dir.join("out.flv")).unwrap();
+        assert!(sync_data(&file).is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -6,27 +6,207 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::u64;
+use std::{cmp, io, u64};
 use std::io::{Read, Seek, SeekFrom};
 use std::fs::File;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use url::Url;
 
+use memmap::{Mmap, MmapOptions};
+
 use gst_plugin::error::*;
+use gst_plugin::properties::*;
 use gst_plugin_simple::source::*;
-use gst_plugin_simple::UriValidator;
+use gst_plugin_simple::{Flushing, PropertyHandle, SettingsImpl, SharedSettings, Unlock,
+                        UriValidator};
 
+use glib;
 use gst;
 use gst::prelude::*;
 
+#[cfg(unix)]
+use iothread::ReadAhead;
+
+// The I/O thread needs Unix file APIs. Elsewhere the read-ahead-size property
+// does not exist and reads always happen directly
+#[cfg(not(unix))]
+#[derive(Debug)]
+enum ReadAhead {}
+
+#[cfg(not(unix))]
+impl ReadAhead {
+    fn new(_file: File, _max_size: u64, _block_size: usize) -> io::Result<ReadAhead> {
+        Err(io::Error::new(io::ErrorKind::Other, "Not supported"))
+    }
+
+    fn read(
+        &self,
+        _offset: u64,
+        _data: &mut [u8],
+        _flushing: &Flushing,
+    ) -> io::Result<Option<usize>> {
+        match *self {}
+    }
+}
+
+const DEFAULT_MMAP: bool = false;
+const DEFAULT_MMAP_WINDOW_SIZE: u64 = 32 * 1024 * 1024;
+const DEFAULT_FOLLOW: bool = false;
+const DEFAULT_FOLLOW_INTERVAL: u64 = 100 * gst::MSECOND;
+const DEFAULT_FOLLOW_TIMEOUT: u64 = 0;
+const DEFAULT_READ_AHEAD_SIZE: u64 = 0;
+
+const READ_AHEAD_BLOCK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy)]
+struct Settings {
+    mmap: bool,
+    mmap_window_size: u64,
+    follow: bool,
+    follow_interval: u64,
+    follow_timeout: u64,
+    read_ahead_size: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            mmap: DEFAULT_MMAP,
+            mmap_window_size: DEFAULT_MMAP_WINDOW_SIZE,
+            follow: DEFAULT_FOLLOW,
+            follow_interval: DEFAULT_FOLLOW_INTERVAL,
+            follow_timeout: DEFAULT_FOLLOW_TIMEOUT,
+            read_ahead_size: DEFAULT_READ_AHEAD_SIZE,
+        }
+    }
+}
+
+impl SettingsImpl for Settings {
+    fn set_property(&mut self, property: &Property, value: &glib::Value) -> bool {
+        match *property {
+            Property::Boolean("mmap", ..) => {
+                self.mmap = value.get().unwrap();
+            }
+            Property::UInt64("mmap-window-size", ..) => {
+                self.mmap_window_size = value.get().unwrap();
+            }
+            Property::Boolean("follow", ..) => {
+                self.follow = value.get().unwrap();
+            }
+            Property::UInt64("follow-interval", ..) => {
+                self.follow_interval = value.get().unwrap();
+            }
+            Property::UInt64("follow-timeout", ..) => {
+                self.follow_timeout = value.get().unwrap();
+            }
+            Property::UInt64("read-ahead-size", ..) => {
+                self.read_ahead_size = value.get().unwrap();
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn get_property(&self, property: &Property) -> Option<glib::Value> {
+        match *property {
+            Property::Boolean("mmap", ..) => Some(self.mmap.to_value()),
+            Property::UInt64("mmap-window-size", ..) => Some(self.mmap_window_size.to_value()),
+            Property::Boolean("follow", ..) => Some(self.follow.to_value()),
+            Property::UInt64("follow-interval", ..) => Some(self.follow_interval.to_value()),
+            Property::UInt64("follow-timeout", ..) => Some(self.follow_timeout.to_value()),
+            Property::UInt64("read-ahead-size", ..) => Some(self.read_ahead_size.to_value()),
+            _ => None,
+        }
+    }
+}
+
+pub static PROPERTIES: &[Property] = &[
+    Property::Boolean(
+        "mmap",
+        "Memory Map",
+        "Memory map the file and output buffers without copying \
+         (the file must not be truncated while reading)",
+        DEFAULT_MMAP,
+        PropertyMutability::ReadWrite,
+    ),
+    Property::UInt64(
+        "mmap-window-size",
+        "Memory Map Window Size",
+        "Size of the parts of the file that are memory mapped at once",
+        (4096, u64::MAX),
+        DEFAULT_MMAP_WINDOW_SIZE,
+        PropertyMutability::ReadWrite,
+    ),
+    Property::Boolean(
+        "follow",
+        "Follow",
+        "Wait for more data at the end of a growing file instead of finishing \
+         (send EOS to the element once the writer is finished)",
+        DEFAULT_FOLLOW,
+        PropertyMutability::ReadWrite,
+    ),
+    Property::UInt64(
+        "follow-interval",
+        "Follow Interval",
+        "Interval in nanoseconds in which the file size is checked when following",
+        (1, u64::MAX),
+        DEFAULT_FOLLOW_INTERVAL,
+        PropertyMutability::ReadWrite,
+    ),
+    Property::UInt64(
+        "follow-timeout",
+        "Follow Timeout",
+        "Finish after the file did not grow for this many nanoseconds when following (0=never)",
+        (0, u64::MAX),
+        DEFAULT_FOLLOW_TIMEOUT,
+        PropertyMutability::ReadWrite,
+    ),
+    #[cfg(unix)]
+    Property::UInt64(
+        "read-ahead-size",
+        "Read Ahead Size",
+        "Maximum number of bytes read ahead by a background thread \
+         (0=read directly, not used when memory mapping or following)",
+        (0, u64::MAX),
+        DEFAULT_READ_AHEAD_SIZE,
+        PropertyMutability::ReadWrite,
+    ),
+];
+
+// A part of a memory mapped window of the file. Keeps the mapping alive for as
+// long as the buffer wrapping it exists
+struct MappedSlice {
+    map: Arc<Mmap>,
+    offset: usize,
+    size: usize,
+}
+
+impl AsRef<[u8]> for MappedSlice {
+    fn as_ref(&self) -> &[u8] {
+        &self.map[self.offset..(self.offset + self.size)]
+    }
+}
+
 #[derive(Debug)]
 enum StreamingState {
     Stopped,
-    Started { file: File, position: u64 },
+    Started {
+        file: File,
+        position: u64,
+        mmap: bool,
+        window: Option<(u64, Arc<Mmap>)>,
+        read_ahead: Option<ReadAhead>,
+    },
 }
 
 #[derive(Debug)]
 pub struct FileSrc {
     streaming_state: StreamingState,
+    settings: Settings,
+    shared_settings: SharedSettings<Settings>,
+    flushing: Flushing,
     cat: gst::DebugCategory,
 }
 
@@ -34,6 +214,9 @@ impl FileSrc {
     pub fn new(_src: &RsBaseSrc) -> FileSrc {
         FileSrc {
             streaming_state: StreamingState::Stopped,
+            settings: Default::default(),
+            shared_settings: SharedSettings::new(Default::default()),
+            flushing: Flushing::new(),
             cat: gst::DebugCategory::new(
                 "rsfilesrc",
                 gst::DebugColorFlags::empty(),
@@ -47,6 +230,184 @@ impl FileSrc {
     }
 }
 
+// Returns the window and the position in it for reading up to length bytes at
+// offset, or None at the end of the file. The size is checked before every
+// read so that nothing after the end of a truncated file is accessed, which
+// would cause SIGBUS
+fn map_range(
+    window: &mut Option<(u64, Arc<Mmap>)>,
+    file: &File,
+    window_size: u64,
+    offset: u64,
+    length: u64,
+) -> io::Result<Option<(Arc<Mmap>, usize, usize)>> {
+    let file_size = try!(file.metadata()).len();
+    if offset >= file_size {
+        return Ok(None);
+    }
+    let length = cmp::min(length, file_size - offset);
+
+    let in_window = match *window {
+        Some((window_offset, ref map)) => {
+            let window_end = window_offset + map.len() as u64;
+            window_end <= file_size && offset >= window_offset && offset + length <= window_end
+        }
+        None => false,
+    };
+
+    if !in_window {
+        *window = None;
+
+        let window_length = cmp::min(cmp::max(window_size, length), file_size - offset);
+        let map = try!(unsafe {
+            MmapOptions::new()
+                .offset(offset as usize)
+                .len(window_length as usize)
+                .map(file)
+        });
+        *window = Some((offset, Arc::new(map)));
+    }
+
+    let &(window_offset, ref map) = window.as_ref().unwrap();
+    Ok(Some((
+        map.clone(),
+        (offset - window_offset) as usize,
+        length as usize,
+    )))
+}
+
+// Files on network filesystems can be truncated by other hosts at any time,
+// so they are read normally instead of being memory mapped
+#[cfg(target_os = "linux")]
+fn is_network_filesystem(file: &File) -> bool {
+    use std::mem;
+    use std::os::unix::io::AsRawFd;
+    use libc;
+
+    const MAGICS: [u64; 9] = [
+        0x6969, // NFS
+        0x517b, // SMB
+        0xff53_4d42, // CIFS
+        0xfe53_4d42, // SMB2
+        0x6573_5546, // FUSE
+        0x00c3_6400, // Ceph
+        0x5346_414f, // AFS
+        0x7375_7245, // Coda
+        0x0102_1997, // 9P
+    ];
+
+    let mut stat: libc::statfs = unsafe { mem::zeroed() };
+    if unsafe { libc::fstatfs(file.as_raw_fd(), &mut stat) } != 0 {
+        return false;
+    }
+
+    let magic = stat.f_type as u64 & 0xffff_ffff;
+    MAGICS.contains(&magic)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_network_filesystem(_file: &File) -> bool {
+    false
+}
+
+fn ns_to_duration(ns: u64) -> Duration {
+    Duration::new(ns / gst::SECOND, (ns % gst::SECOND) as u32)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Wait {
+    Ready,
+    TimedOut,
+    Flushing,
+}
+
+// Polls the file size until the file has data at the given offset, the file
+// did not grow for the timeout (0=never) or the source starts flushing
+fn wait_for_size(
+    file: &File,
+    offset: u64,
+    interval: Duration,
+    timeout: Option<Duration>,
+    flushing: &Flushing,
+) -> io::Result<Wait> {
+    let start = Instant::now();
+
+    loop {
+        if try!(file.metadata()).len() > offset {
+            return Ok(Wait::Ready);
+        }
+
+        if timeout.map_or(false, |timeout| start.elapsed() >= timeout) {
+            return Ok(Wait::TimedOut);
+        }
+
+        if flushing.wait_timeout(interval) {
+            return Ok(Wait::Flushing);
+        }
+    }
+}
+
+// Waits until the file has data at the given offset
+fn wait_for_data(
+    cat: gst::DebugCategory,
+    src: &RsBaseSrc,
+    settings: &Settings,
+    flushing: &Flushing,
+    file: &File,
+    offset: u64,
+) -> Result<(), FlowError> {
+    let timeout = if settings.follow_timeout != 0 {
+        Some(ns_to_duration(settings.follow_timeout))
+    } else {
+        None
+    };
+
+    gst_trace!(cat, obj: src, "Waiting for data at offset {}", offset);
+    match wait_for_size(
+        file,
+        offset,
+        ns_to_duration(settings.follow_interval),
+        timeout,
+        flushing,
+    ) {
+        Ok(Wait::Ready) => Ok(()),
+        Ok(Wait::TimedOut) => {
+            gst_debug!(cat, obj: src, "File did not grow anymore, finishing");
+            Err(FlowError::Eos)
+        }
+        Ok(Wait::Flushing) => {
+            gst_debug!(cat, obj: src, "Flushing while waiting for data");
+            Err(FlowError::Flushing)
+        }
+        Err(err) => {
+            gst_error!(cat, obj: src, "Failed to query file size: {:?}", err);
+            Err(FlowError::Error(error_msg!(
+                gst::ResourceError::Read,
+                ["Failed to query file size: {}", err.to_string()]
+            )))
+        }
+    }
+}
+
+// Reads from the file at the given offset, seeking first if the file is at a
+// different position
+pub fn read_at(
+    file: &mut File,
+    position: &mut u64,
+    offset: u64,
+    data: &mut [u8],
+) -> io::Result<usize> {
+    if *position != offset {
+        try!(file.seek(SeekFrom::Start(offset)));
+        *position = offset;
+    }
+
+    let read = try!(file.read(data));
+    *position += read as u64;
+
+    Ok(read)
+}
+
 fn validate_uri(uri: &Url) -> Result<(), UriError> {
     let _ = try!(uri.to_file_path().or_else(|_| {
         Err(UriError::new(
@@ -66,15 +427,36 @@ impl SourceImpl for FileSrc {
         true
     }
 
+    fn unlock_handle(&self) -> Option<Box<Unlock>> {
+        Some(Box::new(self.flushing.clone()))
+    }
+
+    fn property_handle(&self) -> Option<Box<PropertyHandle>> {
+        Some(Box::new(self.shared_settings.clone()))
+    }
+
     fn get_size(&self, _src: &RsBaseSrc) -> Option<u64> {
-        if let StreamingState::Started { ref file, .. } = self.streaming_state {
-            file.metadata().ok().map(|m| m.len())
+        if let StreamingState::Started {
+            ref file, position, ..
+        } = self.streaming_state
+        {
+            let size = file.metadata().ok().map(|m| m.len());
+
+            // While following, the size is unknown once everything was read
+            // as otherwise the base class would consider this EOS
+            if self.settings.follow && size.map_or(true, |size| size <= position) {
+                None
+            } else {
+                size
+            }
         } else {
             None
         }
     }
 
     fn start(&mut self, src: &RsBaseSrc, uri: Url) -> Result<(), ErrorMessage> {
+        self.shared_settings.update(&mut self.settings);
+
         if let StreamingState::Started { .. } = self.streaming_state {
             return Err(error_msg!(
                 gst::LibraryError::Failed,
@@ -114,9 +496,47 @@ impl SourceImpl for FileSrc {
 
         gst_debug!(self.cat, obj: src, "Opened file {:?}", file);
 
+        let settings = self.settings;
+        let mmap = settings.mmap && !is_network_filesystem(&file);
+        if settings.mmap && !mmap {
+            gst_warning!(
+                self.cat,
+                obj: src,
+                "Not memory mapping file on a network filesystem"
+            );
+        }
+
+        let read_ahead = if settings.read_ahead_size != 0 && !settings.mmap && !settings.follow {
+            let read_ahead = try!(
+                file.try_clone()
+                    .and_then(|file| {
+                        ReadAhead::new(file, settings.read_ahead_size, READ_AHEAD_BLOCK_SIZE)
+                    })
+                    .or_else(|err| {
+                        gst_error!(
+                            self.cat,
+                            obj: src,
+                            "Could not start read ahead thread: {}",
+                            err.to_string()
+                        );
+                        Err(error_msg!(
+                            gst::ResourceError::OpenRead,
+                            ["Could not start read ahead thread: {}", err.to_string()]
+                        ))
+                    })
+            );
+
+            Some(read_ahead)
+        } else {
+            None
+        };
+
         self.streaming_state = StreamingState::Started {
             file: file,
             position: 0,
+            mmap: mmap,
+            window: None,
+            read_ahead: read_ahead,
         };
 
         Ok(())
@@ -135,14 +555,20 @@ impl SourceImpl for FileSrc {
         _: u32,
         buffer: &mut gst::BufferRef,
     ) -> Result<(), FlowError> {
+        self.shared_settings.update(&mut self.settings);
+
         let cat = self.cat;
+        let settings = self.settings;
+        let flushing = &self.flushing;
         let streaming_state = &mut self.streaming_state;
 
-        let (file, position) = match *streaming_state {
+        let (file, position, read_ahead) = match *streaming_state {
             StreamingState::Started {
                 ref mut file,
                 ref mut position,
-            } => (file, position),
+                ref read_ahead,
+                ..
+            } => (file, position, read_ahead),
             StreamingState::Stopped => {
                 return Err(FlowError::Error(
                     error_msg!(gst::LibraryError::Failed, ["Not started yet"]),
@@ -150,15 +576,8 @@ impl SourceImpl for FileSrc {
             }
         };
 
-        if *position != offset {
-            try!(file.seek(SeekFrom::Start(offset)).or_else(|err| {
-                gst_error!(cat, obj: src, "Failed to seek to {}: {:?}", offset, err);
-                Err(FlowError::Error(error_msg!(
-                    gst::ResourceError::Seek,
-                    ["Failed to seek to {}: {}", offset, err.to_string()]
-                )))
-            }));
-            *position = offset;
+        if settings.follow {
+            try!(wait_for_data(cat, src, &settings, flushing, file, offset));
         }
 
         let size = {
@@ -174,23 +593,255 @@ impl SourceImpl for FileSrc {
 
             let data = map.as_mut_slice();
 
-            try!(file.read(data).or_else(|err| {
-                gst_error!(cat, obj: src, "Failed to read: {:?}", err);
-                Err(FlowError::Error(error_msg!(
-                    gst::ResourceError::Read,
-                    ["Failed to read at {}: {}", offset, err.to_string()]
-                )))
-            }))
+            // The read ahead thread handles seeks itself
+            let res = match *read_ahead {
+                Some(ref read_ahead) => read_ahead.read(offset, data, flushing),
+                None => read_at(file, position, offset, data).map(Some),
+            };
+
+            match res {
+                Ok(Some(size)) => size,
+                Ok(None) => {
+                    gst_debug!(cat, obj: src, "Flushing while waiting for data");
+                    return Err(FlowError::Flushing);
+                }
+                Err(err) => {
+                    gst_error!(cat, obj: src, "Failed to read: {:?}", err);
+                    return Err(FlowError::Error(error_msg!(
+                        gst::ResourceError::Read,
+                        ["Failed to read at {}: {}", offset, err.to_string()]
+                    )));
+                }
+            }
         };
 
-        *position += size as u64;
+        *position = offset + size as u64;
 
         buffer.set_size(size);
 
         Ok(())
     }
 
+    fn create(
+        &mut self,
+        src: &RsBaseSrc,
+        offset: u64,
+        length: u32,
+    ) -> Option<Result<gst::Buffer, FlowError>> {
+        self.shared_settings.update(&mut self.settings);
+
+        let cat = self.cat;
+        let settings = self.settings;
+        let window_size = settings.mmap_window_size;
+
+        let (file, position, window) = match self.streaming_state {
+            StreamingState::Started { mmap: false, .. } => return None,
+            StreamingState::Started {
+                ref file,
+                ref mut position,
+                ref mut window,
+                ..
+            } => (file, position, window),
+            StreamingState::Stopped => {
+                return Some(Err(FlowError::Error(
+                    error_msg!(gst::LibraryError::Failed, ["Not started yet"]),
+                )));
+            }
+        };
+
+        if settings.follow {
+            if let Err(err) = wait_for_data(cat, src, &settings, &self.flushing, file, offset) {
+                return Some(Err(err));
+            }
+        }
+
+        let previous = window.as_ref().map(|&(window_offset, _)| window_offset);
+        let res = map_range(window, file, window_size, offset, u64::from(length));
+
+        let (map, map_offset, length) = match res {
+            Ok(Some(range)) => range,
+            Ok(None) => return Some(Err(FlowError::Eos)),
+            Err(err) => {
+                gst_error!(cat, obj: src, "Failed to map file: {:?}", err);
+                return Some(Err(FlowError::Error(error_msg!(
+                    gst::ResourceError::Read,
+                    ["Failed to map file at {}: {}", offset, err.to_string()]
+                ))));
+            }
+        };
+
+        if previous != window.as_ref().map(|&(window_offset, _)| window_offset) {
+            gst_debug!(
+                cat,
+                obj: src,
+                "Mapped window at {} with length {}",
+                offset,
+                map.len()
+            );
+        }
+
+        let buffer = gst::Buffer::from_slice(MappedSlice {
+            map: map,
+            offset: map_offset,
+            size: length,
+        }).unwrap();
+
+        *position = offset + length as u64;
+
+        Some(Ok(buffer))
+    }
+
     fn seek(&mut self, _src: &RsBaseSrc, _: u64, _: Option<u64>) -> Result<(), ErrorMessage> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{map_range, wait_for_size, Wait};
+    use gst_plugin_simple::Flushing;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::PathBuf;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn test_file(name: &str, size: usize) -> (PathBuf, Vec<u8>) {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos();
+        let path = env::temp_dir().join(format!("rsfilesrc-{}-{}", name, nanos));
+
+        let data = (0..size).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        File::create(&path).unwrap().write_all(&data).unwrap();
+
+        (path, data)
+    }
+
+    #[test]
+    fn test_map_range() {
+        let (path, data) = test_file("map", 10000);
+        let file = File::open(&path).unwrap();
+        let mut window = None;
+
+        let read = |window: &mut _, offset: u64, length: u64| {
+            map_range(window, &file, 4096, offset, length)
+                .unwrap()
+                .map(|(map, map_offset, size)| map[map_offset..(map_offset + size)].to_vec())
+        };
+
+        assert_eq!(read(&mut window, 0, 3000), Some(data[0..3000].to_vec()));
+        assert_eq!(window.as_ref().map(|w: &(u64, _)| w.0), Some(0));
+
+        // Still inside the first window
+        assert_eq!(read(&mut window, 3000, 1000), Some(data[3000..4000].to_vec()));
+        assert_eq!(window.as_ref().map(|w| w.0), Some(0));
+
+        // Crossing the end of the window maps a new one at the offset
+        assert_eq!(read(&mut window, 4000, 1000), Some(data[4000..5000].to_vec()));
+        assert_eq!(window.as_ref().map(|w| (w.0, w.1.len())), Some((4000, 4096)));
+
+        // Bigger than the window size
+        assert_eq!(read(&mut window, 1000, 8000), Some(data[1000..9000].to_vec()));
+        assert_eq!(window.as_ref().map(|w| (w.0, w.1.len())), Some((1000, 8000)));
+
+        // Limited by the end of the file
+        assert_eq!(read(&mut window, 9000, 4096), Some(data[9000..].to_vec()));
+        assert_eq!(read(&mut window, 10000, 4096), None);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    // Windows reaching behind the end of a truncated file are not used anymore
+    #[test]
+    fn test_map_range_truncated() {
+        let (path, data) = test_file("truncated", 10000);
+        let file = File::open(&path).unwrap();
+        let mut window = None;
+
+        let read = |window: &mut _, offset: u64, length: u64| {
+            map_range(window, &file, 8192, offset, length)
+                .unwrap()
+                .map(|(map, map_offset, size)| map[map_offset..(map_offset + size)].to_vec())
+        };
+
+        assert_eq!(read(&mut window, 0, 1000), Some(data[0..1000].to_vec()));
+        assert_eq!(window.as_ref().map(|w: &(u64, _)| w.1.len()), Some(8192));
+
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(5000)
+            .unwrap();
+
+        assert_eq!(read(&mut window, 1000, 1000), Some(data[1000..2000].to_vec()));
+        assert_eq!(window.as_ref().map(|w| (w.0, w.1.len())), Some((1000, 4000)));
+        assert_eq!(read(&mut window, 4500, 1000), Some(data[4500..5000].to_vec()));
+        assert_eq!(read(&mut window, 5000, 1000), None);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_wait_for_size() {
+        let (path, _) = test_file("follow", 100);
+        let file = File::open(&path).unwrap();
+        let flushing = Flushing::new();
+        let interval = Duration::from_millis(10);
+
+        assert_eq!(
+            wait_for_size(&file, 50, interval, None, &flushing).unwrap(),
+            Wait::Ready
+        );
+
+        // Data appended by another writer is picked up
+        let writer_path = path.clone();
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            fs::OpenOptions::new()
+                .append(true)
+                .open(&writer_path)
+                .unwrap()
+                .write_all(&[0; 10])
+                .unwrap();
+        });
+        assert_eq!(
+            wait_for_size(&file, 100, interval, None, &flushing).unwrap(),
+            Wait::Ready
+        );
+        writer.join().unwrap();
+        assert_eq!(file.metadata().unwrap().len(), 110);
+
+        // Finishes once the file did not grow for the timeout
+        let start = Instant::now();
+        assert_eq!(
+            wait_for_size(
+                &file,
+                110,
+                interval,
+                Some(Duration::from_millis(50)),
+                &flushing
+            ).unwrap(),
+            Wait::TimedOut
+        );
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        // Flushing interrupts the wait
+        let unlock = flushing.clone();
+        let unlocker = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            unlock.set_flushing(true);
+        });
+        assert_eq!(
+            wait_for_size(&file, 110, Duration::from_secs(60), None, &flushing).unwrap(),
+            Wait::Flushing
+        );
+        unlocker.join().unwrap();
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,650 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::cmp;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, ErrorKind, Seek, SeekFrom, Write};
+use std::os::unix::fs::FileExt;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use libc;
+
+use gst_plugin::error::*;
+use gst_plugin_simple::Flushing;
+
+use gst;
+
+// Interval in which waiting for the I/O thread checks if it should be interrupted
+const WAIT_TIMEOUT_MS: u64 = 100;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Advice {
+    Sequential,
+    WillNeed,
+    DontNeed,
+}
+
+// Only a hint to the kernel, so failures are ignored. A length of 0 means
+// until the end of the file
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+pub fn fadvise(file: &File, offset: u64, len: u64, advice: Advice) {
+    use std::os::unix::io::AsRawFd;
+
+    let advice = match advice {
+        Advice::Sequential => libc::POSIX_FADV_SEQUENTIAL,
+        Advice::WillNeed => libc::POSIX_FADV_WILLNEED,
+        Advice::DontNeed => libc::POSIX_FADV_DONTNEED,
+    };
+
+    unsafe {
+        libc::posix_fadvise(
+            file.as_raw_fd(),
+            offset as libc::off_t,
+            len as libc::off_t,
+            advice,
+        );
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+pub fn fadvise(_file: &File, _offset: u64, _len: u64, _advice: Advice) {}
+
+struct Block {
+    offset: u64,
+    data: Vec<u8>,
+}
+
+#[derive(Default)]
+struct ReadState {
+    blocks: VecDeque<Block>,
+    queued: u64,
+    next_offset: u64,
+    // Increased on every seek so that reads in flight are discarded
+    generation: u64,
+    eos: bool,
+    error: Option<io::Error>,
+    shutdown: bool,
+}
+
+// Reads sequential blocks of a file in a background thread, up to a maximum
+// number of bytes ahead of the consumer
+pub struct ReadAhead {
+    state: Arc<(Mutex<ReadState>, Condvar)>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl ReadAhead {
+    pub fn new(file: File, max_size: u64, block_size: usize) -> io::Result<ReadAhead> {
+        let state = Arc::new((Mutex::new(ReadState::default()), Condvar::new()));
+
+        let thread_state = state.clone();
+        let thread = try!(
+            thread::Builder::new()
+                .name("rsfilesrc-read-ahead".into())
+                .spawn(move || read_loop(file, thread_state, max_size, block_size))
+        );
+
+        Ok(ReadAhead {
+            state: state,
+            thread: Some(thread),
+        })
+    }
+
+    // Returns 0 at the end of the file and None if flushing before any data
+    // was available
+    pub fn read(
+        &self,
+        offset: u64,
+        data: &mut [u8],
+        flushing: &Flushing,
+    ) -> io::Result<Option<usize>> {
+        let &(ref lock, ref cond) = &*self.state;
+        let mut state = lock.lock().unwrap();
+
+        while state
+            .blocks
+            .front()
+            .map_or(false, |block| block.offset + block.data.len() as u64 <= offset)
+        {
+            let block = state.blocks.pop_front().unwrap();
+            state.queued -= block.data.len() as u64;
+        }
+
+        // Anything but continuing where we stopped is a seek, and everything
+        // read so far is useless then
+        let in_queue = match state.blocks.front() {
+            Some(block) => block.offset <= offset,
+            None => state.next_offset == offset,
+        };
+
+        if !in_queue {
+            state.blocks.clear();
+            state.queued = 0;
+            state.next_offset = offset;
+            state.generation += 1;
+            state.eos = false;
+            state.error = None;
+            cond.notify_all();
+        }
+
+        while state.blocks.is_empty() {
+            // Errors are reported by every read until the next seek
+            if let Some(ref err) = state.error {
+                return Err(io::Error::new(err.kind(), err.to_string()));
+            }
+
+            if state.eos {
+                return Ok(Some(0));
+            }
+
+            if flushing.is_flushing() {
+                return Ok(None);
+            }
+
+            state = cond.wait_timeout(state, Duration::from_millis(WAIT_TIMEOUT_MS))
+                .unwrap()
+                .0;
+        }
+
+        let mut filled = 0;
+        while filled < data.len() {
+            let position = offset + filled as u64;
+
+            let (size, consumed) = match state.blocks.front() {
+                None => break,
+                Some(block) => {
+                    let start = (position - block.offset) as usize;
+                    let size = cmp::min(block.data.len() - start, data.len() - filled);
+                    data[filled..(filled + size)]
+                        .copy_from_slice(&block.data[start..(start + size)]);
+                    (size, start + size == block.data.len())
+                }
+            };
+
+            filled += size;
+
+            if consumed {
+                let block = state.blocks.pop_front().unwrap();
+                state.queued -= block.data.len() as u64;
+            }
+        }
+
+        cond.notify_all();
+
+        Ok(Some(filled))
+    }
+}
+
+impl Drop for ReadAhead {
+    fn drop(&mut self) {
+        {
+            let &(ref lock, ref cond) = &*self.state;
+            let mut state = lock.lock().unwrap();
+            state.shutdown = true;
+            cond.notify_all();
+        }
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl fmt::Debug for ReadAhead {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.0.lock().unwrap();
+        f.debug_struct("ReadAhead")
+            .field("queued", &state.queued)
+            .field("next_offset", &state.next_offset)
+            .field("eos", &state.eos)
+            .finish()
+    }
+}
+
+fn read_loop(
+    file: File,
+    state: Arc<(Mutex<ReadState>, Condvar)>,
+    max_size: u64,
+    block_size: usize,
+) {
+    let &(ref lock, ref cond) = &*state;
+
+    fadvise(&file, 0, 0, Advice::Sequential);
+
+    loop {
+        let (offset, generation) = {
+            let mut state = lock.lock().unwrap();
+            while !state.shutdown
+                && (state.eos || state.error.is_some() || state.queued >= max_size)
+            {
+                state = cond.wait(state).unwrap();
+            }
+
+            if state.shutdown {
+                return;
+            }
+
+            (state.next_offset, state.generation)
+        };
+
+        fadvise(
+            &file,
+            offset + block_size as u64,
+            block_size as u64,
+            Advice::WillNeed,
+        );
+
+        let mut data = vec![0; block_size];
+        let res = file.read_at(&mut data, offset);
+
+        let mut state = lock.lock().unwrap();
+        if state.generation != generation {
+            continue;
+        }
+
+        match res {
+            Ok(0) => state.eos = true,
+            Ok(size) => {
+                data.truncate(size);
+                state.queued += size as u64;
+                state.next_offset += size as u64;
+                state.blocks.push_back(Block {
+                    offset: offset,
+                    data: data,
+                });
+            }
+            Err(ref err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => state.error = Some(err),
+        }
+
+        cond.notify_all();
+    }
+}
+
+enum Op {
+    Write(Vec<u8>),
+    Seek(u64),
+    Sync,
+}
+
+#[derive(Default)]
+struct WriteState {
+    ops: VecDeque<Op>,
+    queued: u64,
+    busy: bool,
+    // Kept once set, so that the file is never finished as if it was complete
+    error: Option<ErrorMessage>,
+    shutdown: bool,
+}
+
+// Executes writes, seeks and syncs in order in a background thread, with up
+// to a maximum number of bytes waiting to be written
+pub struct WriteBehind {
+    state: Arc<(Mutex<WriteState>, Condvar)>,
+    max_size: u64,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl WriteBehind {
+    pub fn new(file: File, max_size: u64) -> io::Result<WriteBehind> {
+        let state = Arc::new((Mutex::new(WriteState::default()), Condvar::new()));
+
+        let thread_state = state.clone();
+        let thread = try!(
+            thread::Builder::new()
+                .name("rsfilesink-write-behind".into())
+                .spawn(move || write_loop(file, thread_state))
+        );
+
+        Ok(WriteBehind {
+            state: state,
+            max_size: max_size,
+            thread: Some(thread),
+        })
+    }
+
+    // Returns false if flushing before there was space in the queue
+    pub fn write(&self, data: Vec<u8>, flushing: &Flushing) -> Result<bool, ErrorMessage> {
+        let &(ref lock, ref cond) = &*self.state;
+        let mut state = lock.lock().unwrap();
+
+        loop {
+            if let Some(ref msg) = state.error {
+                return Err(msg.clone());
+            }
+
+            if state.queued < self.max_size {
+                break;
+            }
+
+            if flushing.is_flushing() {
+                return Ok(false);
+            }
+
+            state = cond.wait_timeout(state, Duration::from_millis(WAIT_TIMEOUT_MS))
+                .unwrap()
+                .0;
+        }
+
+        state.queued += data.len() as u64;
+        state.ops.push_back(Op::Write(data));
+        cond.notify_all();
+
+        Ok(true)
+    }
+
+    pub fn seek(&self, offset: u64) -> Result<(), ErrorMessage> {
+        self.push(Op::Seek(offset))
+    }
+
+    // Waits until everything queued so far is written and synchronized to
+    // disk, so that failures are reported to the caller. Returns false if
+    // flushing before that
+    pub fn sync(&self, flushing: &Flushing) -> Result<bool, ErrorMessage> {
+        try!(self.push(Op::Sync));
+        self.drain(flushing)
+    }
+
+    // Waits until everything queued so far was executed. Returns false if
+    // flushing before that
+    pub fn drain(&self, flushing: &Flushing) -> Result<bool, ErrorMessage> {
+        let &(ref lock, ref cond) = &*self.state;
+        let mut state = lock.lock().unwrap();
+
+        while state.error.is_none() && (state.busy || !state.ops.is_empty()) {
+            if flushing.is_flushing() {
+                return Ok(false);
+            }
+
+            state = cond.wait_timeout(state, Duration::from_millis(WAIT_TIMEOUT_MS))
+                .unwrap()
+                .0;
+        }
+
+        match state.error {
+            Some(ref msg) => Err(msg.clone()),
+            None => Ok(true),
+        }
+    }
+
+    fn push(&self, op: Op) -> Result<(), ErrorMessage> {
+        let &(ref lock, ref cond) = &*self.state;
+        let mut state = lock.lock().unwrap();
+
+        if let Some(ref msg) = state.error {
+            return Err(msg.clone());
+        }
+
+        state.ops.push_back(op);
+        cond.notify_all();
+
+        Ok(())
+    }
+}
+
+impl Drop for WriteBehind {
+    fn drop(&mut self) {
+        {
+            let &(ref lock, ref cond) = &*self.state;
+            let mut state = lock.lock().unwrap();
+            state.shutdown = true;
+            cond.notify_all();
+        }
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl fmt::Debug for WriteBehind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.0.lock().unwrap();
+        f.debug_struct("WriteBehind")
+            .field("queued", &state.queued)
+            .field("max_size", &self.max_size)
+            .finish()
+    }
+}
+
+// Remaining operations are still executed on shutdown, unless an error
+// happened before
+fn write_loop(mut file: File, state: Arc<(Mutex<WriteState>, Condvar)>) {
+    let &(ref lock, ref cond) = &*state;
+
+    loop {
+        let op = {
+            let mut state = lock.lock().unwrap();
+            loop {
+                if state.error.is_none() {
+                    if let Some(op) = state.ops.pop_front() {
+                        state.busy = true;
+                        break op;
+                    }
+                }
+
+                if state.shutdown {
+                    return;
+                }
+
+                state = cond.wait(state).unwrap();
+            }
+        };
+
+        let (size, res) = match op {
+            Op::Write(data) => (
+                data.len() as u64,
+                file.write_all(&data).or_else(|err| {
+                    Err(error_msg!(
+                        gst::ResourceError::Write,
+                        ["Failed to write: {}", err]
+                    ))
+                }),
+            ),
+            Op::Seek(offset) => (
+                0,
+                file.seek(SeekFrom::Start(offset)).map(|_| ()).or_else(|err| {
+                    Err(error_msg!(
+                        gst::ResourceError::Seek,
+                        ["Failed to seek to {}: {}", offset, err.to_string()]
+                    ))
+                }),
+            ),
+            // Written data is not read again, so it is dropped from the page
+            // cache once it is on disk
+            Op::Sync => (
+                0,
+                file.sync_data()
+                    .map(|_| fadvise(&file, 0, 0, Advice::DontNeed))
+                    .or_else(|err| {
+                        Err(error_msg!(
+                            gst::ResourceError::Sync,
+                            ["Failed to synchronize file to disk: {}", err.to_string()]
+                        ))
+                    }),
+            ),
+        };
+
+        let mut state = lock.lock().unwrap();
+        state.queued -= size;
+        state.busy = false;
+
+        if let Err(msg) = res {
+            state.error = Some(msg);
+            state.ops.clear();
+            state.queued = 0;
+        }
+
+        cond.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReadAhead, WriteBehind};
+    use gst_plugin_simple::Flushing;
+    use libc;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::{Read, Write};
+    use std::os::unix::io::FromRawFd;
+    use std::path::PathBuf;
+    use std::process;
+    use std::thread;
+    use std::time::Duration;
+
+    fn test_file(name: &str, data: &[u8]) -> PathBuf {
+        let path = env::temp_dir().join(format!("rsiothread-{}-{}", name, process::id()));
+        File::create(&path).unwrap().write_all(data).unwrap();
+        path
+    }
+
+    fn pipe() -> (File, File) {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) }
+    }
+
+    // Reads return whatever is queued already, so loop until everything is there
+    fn read(read_ahead: &ReadAhead, offset: u64, length: usize) -> Vec<u8> {
+        let flushing = Flushing::new();
+        let mut data = vec![0; length];
+        let mut filled = 0;
+        while filled < length {
+            let position = offset + filled as u64;
+            match read_ahead
+                .read(position, &mut data[filled..], &flushing)
+                .unwrap()
+            {
+                Some(0) => break,
+                Some(size) => filled += size,
+                None => unreachable!(),
+            }
+        }
+        data.truncate(filled);
+        data
+    }
+
+    #[test]
+    fn test_read_ahead() {
+        let data = (0..300_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let path = test_file("read", &data);
+        let read_ahead = ReadAhead::new(File::open(&path).unwrap(), 128 * 1024, 4096).unwrap();
+
+        assert_eq!(read(&read_ahead, 0, 1000), &data[..1000]);
+        assert_eq!(read(&read_ahead, 1000, 10_000), &data[1000..11_000]);
+
+        // Seeking forwards and backwards discards everything read ahead
+        assert_eq!(read(&read_ahead, 200_000, 5000), &data[200_000..205_000]);
+        assert_eq!(read(&read_ahead, 100, 100), &data[100..200]);
+        assert_eq!(read(&read_ahead, 200, 100), &data[200..300]);
+
+        assert_eq!(read(&read_ahead, 299_950, 100), &data[299_950..]);
+        assert_eq!(read(&read_ahead, 300_000, 100), &[]);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_ahead_error() {
+        let path = test_file("read-error", b"data");
+
+        // Not opened for reading
+        let read_ahead = ReadAhead::new(File::create(&path).unwrap(), 4096, 4096).unwrap();
+        let flushing = Flushing::new();
+        let mut data = [0; 16];
+
+        // Reported again by every read until the next seek
+        assert!(read_ahead.read(0, &mut data, &flushing).is_err());
+        assert!(read_ahead.read(0, &mut data, &flushing).is_err());
+        assert!(read_ahead.read(4, &mut data, &flushing).is_err());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_write_behind_sync() {
+        let path = test_file("write", b"");
+        let write_behind = WriteBehind::new(File::create(&path).unwrap(), 1024 * 1024).unwrap();
+        let flushing = Flushing::new();
+
+        assert!(write_behind.write(b"hello ".to_vec(), &flushing).unwrap());
+        assert!(write_behind.write(b"world".to_vec(), &flushing).unwrap());
+        write_behind.seek(0).unwrap();
+        assert!(write_behind.write(b"H".to_vec(), &flushing).unwrap());
+
+        // Everything queued before is written once synchronizing finished
+        assert!(write_behind.sync(&flushing).unwrap());
+        let mut written = String::new();
+        File::open(&path)
+            .unwrap()
+            .read_to_string(&mut written)
+            .unwrap();
+        assert_eq!(written, "Hello world");
+
+        drop(write_behind);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_write_behind_queue() {
+        // Nobody reads from the pipe yet, so the thread blocks once it is full
+        let (mut reader, writer) = pipe();
+        let write_behind = WriteBehind::new(writer, 1000).unwrap();
+        let flushing = Flushing::new();
+
+        assert!(write_behind.write(vec![1; 128 * 1024], &flushing).unwrap());
+
+        let unlock = flushing.clone();
+        let unlocker = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            unlock.set_flushing(true);
+        });
+
+        // The queue is full, so writing more waits until flushing
+        assert!(!write_behind.write(vec![2; 10], &flushing).unwrap());
+        unlocker.join().unwrap();
+        assert!(!write_behind.drain(&flushing).unwrap());
+
+        let reader = thread::spawn(move || {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).unwrap();
+            data
+        });
+
+        flushing.set_flushing(false);
+        assert!(write_behind.drain(&flushing).unwrap());
+        drop(write_behind);
+
+        assert_eq!(reader.join().unwrap(), vec![1; 128 * 1024]);
+    }
+
+    #[test]
+    fn test_write_behind_error() {
+        let path = test_file("write-error", b"");
+
+        // Not opened for writing
+        let write_behind = WriteBehind::new(File::open(&path).unwrap(), 1024).unwrap();
+        let flushing = Flushing::new();
+
+        assert!(write_behind.write(b"data".to_vec(), &flushing).unwrap());
+        assert!(write_behind.drain(&flushing).is_err());
+
+        // Every following operation fails too
+        assert!(write_behind.write(b"data".to_vec(), &flushing).is_err());
+        assert!(write_behind.seek(0).is_err());
+        assert!(write_behind.sync(&flushing).is_err());
+
+        drop(write_behind);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -11,8 +11,11 @@
 #[macro_use]
 extern crate gst_plugin;
 extern crate gst_plugin_simple;
+extern crate glib;
+extern crate libc;
 #[macro_use]
 extern crate gstreamer as gst;
+extern crate memmap;
 extern crate url;
 
 use gst_plugin_simple::source::*;
@@ -20,9 +23,26 @@ use gst_plugin_simple::sink::*;
 
 mod filesrc;
 mod filesink;
+mod location;
+mod multifilesink;
+mod multifilesrc;
+mod splitfilesrc;
+#[cfg(unix)]
+mod fdsrc;
+#[cfg(unix)]
+mod fdsink;
+#[cfg(unix)]
+mod iothread;
 
 use filesrc::FileSrc;
 use filesink::FileSink;
+use multifilesink::MultiFileSink;
+use multifilesrc::MultiFileSrc;
+use splitfilesrc::SplitFileSrc;
+#[cfg(unix)]
+use fdsrc::FdSrc;
+#[cfg(unix)]
+use fdsink::FdSink;
 
 fn plugin_init(plugin: &gst::Plugin) -> bool {
     source_register(
@@ -36,6 +56,56 @@ fn plugin_init(plugin: &gst::Plugin) -> bool {
             rank: 256 + 100,
             create_instance: FileSrc::new_boxed,
             protocols: vec!["file".into()],
+            properties: filesrc::PROPERTIES.to_vec(),
+            push_only: false,
+        },
+    );
+
+    source_register(
+        plugin,
+        SourceInfo {
+            name: "rsmultifilesrc".into(),
+            long_name: "Multi File Source".into(),
+            description: "Reads a numbered sequence of local files, one buffer per file".into(),
+            classification: "Source/File".into(),
+            author: "agent <agent@local>".into(),
+            rank: 0,
+            create_instance: MultiFileSrc::new_boxed,
+            protocols: vec!["file".into()],
+            properties: multifilesrc::PROPERTIES.to_vec(),
+            push_only: true,
+        },
+    );
+
+    source_register(
+        plugin,
+        SourceInfo {
+            name: "rssplitfilesrc".into(),
+            long_name: "Split File Source".into(),
+            description: "Reads multiple local files as one continuous stream".into(),
+            classification: "Source/File".into(),
+            author: "agent <agent@local>".into(),
+            rank: 0,
+            create_instance: SplitFileSrc::new_boxed,
+            protocols: vec!["file".into()],
+            properties: splitfilesrc::PROPERTIES.to_vec(),
+            push_only: false,
+        },
+    );
+
+    #[cfg(unix)]
+    source_register(
+        plugin,
+        SourceInfo {
+            name: "rsfdsrc".into(),
+            long_name: "File Descriptor Source".into(),
+            description: "Reads from an open file descriptor, e.g. fd://0 for stdin".into(),
+            classification: "Source/File".into(),
+            author: "agent <agent@local>".into(),
+            rank: 0,
+            create_instance: FdSrc::new_boxed,
+            protocols: vec!["fd".into()],
+            properties: vec![],
             push_only: false,
         },
     );
@@ -51,6 +121,38 @@ fn plugin_init(plugin: &gst::Plugin) -> bool {
             rank: 256 + 100,
             create_instance: FileSink::new_boxed,
             protocols: vec!["file".into()],
+            properties: filesink::PROPERTIES.to_vec(),
+        },
+    );
+
+    sink_register(
+        plugin,
+        SinkInfo {
+            name: "rsmultifilesink".into(),
+            long_name: "Multi File Sink".into(),
+            description: "Writes to multiple local files, split by size or duration".into(),
+            classification: "Sink/File".into(),
+            author: "agent <agent@local>".into(),
+            rank: 0,
+            create_instance: MultiFileSink::new_boxed,
+            protocols: vec!["file".into()],
+            properties: multifilesink::PROPERTIES.to_vec(),
+        },
+    );
+
+    #[cfg(unix)]
+    sink_register(
+        plugin,
+        SinkInfo {
+            name: "rsfdsink".into(),
+            long_name: "File Descriptor Sink".into(),
+            description: "Writes to an open file descriptor, e.g. fd://1 for stdout".into(),
+            classification: "Sink/File".into(),
+            author: "agent <agent@local>".into(),
+            rank: 0,
+            create_instance: FdSink::new_boxed,
+            protocols: vec!["fd".into()],
+            properties: vec![],
         },
     );
 
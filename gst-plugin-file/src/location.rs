@@ -0,0 +1,139 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::env;
+use std::path::Path;
+use url::Url;
+
+use gst;
+use gst::prelude::*;
+
+// Formats a printf-style pattern with a single integer conversion, e.g.
+// "rec-%05d.flv". Returns None if the pattern is not valid
+pub fn format_location(pattern: &str, index: u32) -> Option<String> {
+    let mut location = String::with_capacity(pattern.len() + 10);
+    let mut chars = pattern.chars();
+    let mut have_index = false;
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            location.push(c);
+            continue;
+        }
+
+        let mut zero_pad = false;
+        let mut width = 0;
+        loop {
+            match chars.next() {
+                Some('%') if !zero_pad && width == 0 => {
+                    location.push('%');
+                    break;
+                }
+                Some('0') if !zero_pad && width == 0 => zero_pad = true,
+                Some(c @ '0'...'9') => width = width * 10 + c.to_digit(10).unwrap() as usize,
+                Some('d') | Some('u') | Some('i') if !have_index => {
+                    have_index = true;
+                    if zero_pad {
+                        location.push_str(&format!("{:01$}", index, width));
+                    } else {
+                        location.push_str(&format!("{:1$}", index, width));
+                    }
+                    break;
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    if have_index {
+        Some(location)
+    } else {
+        None
+    }
+}
+
+// Converts a location pattern to a file URI. '%' and wildcards are escaped
+// there, so they are part of the path again when the URI is decoded
+pub fn location_to_uri(location: &str) -> Result<Url, String> {
+    let path = Path::new(location);
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        try!(env::current_dir().map_err(|err| err.to_string())).join(path)
+    };
+
+    Url::from_file_path(&path).map_err(|_| format!("Invalid location '{}'", location))
+}
+
+// Sets the URI of the element from the location property
+pub fn set_location(element: &gst::Element, location: &str) -> Result<(), String> {
+    let uri = try!(location_to_uri(location));
+    let handler = try!(
+        element
+            .clone()
+            .dynamic_cast::<gst::URIHandler>()
+            .map_err(|_| String::from("Element is no URI handler"))
+    );
+
+    handler.set_uri(uri.as_str()).map_err(|err| err.to_string())
+}
+
+pub fn get_location(element: &gst::Element) -> Option<String> {
+    element
+        .clone()
+        .dynamic_cast::<gst::URIHandler>()
+        .ok()
+        .and_then(|handler| handler.get_uri())
+        .and_then(|uri| Url::parse(&uri).ok())
+        .and_then(|uri| uri.to_file_path().ok())
+        .and_then(|location| location.to_str().map(String::from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_location, location_to_uri};
+
+    #[test]
+    fn test_format_location() {
+        assert_eq!(
+            format_location("rec-%05d.flv", 42),
+            Some("rec-00042.flv".into())
+        );
+        assert_eq!(format_location("rec-%d.flv", 42), Some("rec-42.flv".into()));
+        assert_eq!(format_location("rec-%3u.flv", 7), Some("rec-  7.flv".into()));
+        assert_eq!(
+            format_location("100%%-%02d.flv", 1),
+            Some("100%-01.flv".into())
+        );
+    }
+
+    #[test]
+    fn test_format_location_invalid() {
+        assert_eq!(format_location("rec.flv", 1), None);
+        assert_eq!(format_location("rec-%s.flv", 1), None);
+        assert_eq!(format_location("rec-%d-%d.flv", 1), None);
+        assert_eq!(format_location("rec-%", 1), None);
+    }
+
+    #[test]
+    fn test_location_to_uri() {
+        let uri = location_to_uri("/tmp/rec-%05d.flv").unwrap();
+        assert_eq!(uri.as_str(), "file:///tmp/rec-%2505d.flv");
+        assert_eq!(
+            uri.to_file_path().unwrap().to_str(),
+            Some("/tmp/rec-%05d.flv")
+        );
+
+        let uri = location_to_uri("/tmp/chunk-?.ts").unwrap();
+        assert_eq!(uri.query(), None);
+        assert_eq!(uri.to_file_path().unwrap().to_str(), Some("/tmp/chunk-?.ts"));
+
+        let uri = location_to_uri("rec-%d.flv").unwrap();
+        assert!(uri.to_file_path().unwrap().ends_with("rec-%d.flv"));
+    }
+}
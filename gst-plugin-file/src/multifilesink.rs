@@ -0,0 +1,659 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fs::File;
+use std::{u32, u64};
+use url::Url;
+
+use std::io::Write;
+
+use gst_plugin::error::*;
+use gst_plugin::properties::*;
+use gst_plugin_simple::sink::*;
+use gst_plugin_simple::{PropertyHandle, SettingsImpl, SharedSettings, UriValidator};
+
+use glib;
+use gst;
+use gst::prelude::*;
+
+use location::{format_location, get_location, set_location};
+
+const DEFAULT_MAX_SIZE_BYTES: u64 = 0;
+const DEFAULT_MAX_SIZE_TIME: u64 = 0;
+const DEFAULT_SPLIT_ON_KEYFRAME: bool = false;
+const DEFAULT_START_INDEX: u32 = 0;
+
+#[derive(Debug, Clone, Copy)]
+struct Settings {
+    max_size_bytes: u64,
+    max_size_time: u64,
+    split_on_keyframe: bool,
+    start_index: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            max_size_bytes: DEFAULT_MAX_SIZE_BYTES,
+            max_size_time: DEFAULT_MAX_SIZE_TIME,
+            split_on_keyframe: DEFAULT_SPLIT_ON_KEYFRAME,
+            start_index: DEFAULT_START_INDEX,
+        }
+    }
+}
+
+impl SettingsImpl for Settings {
+    fn set_property(&mut self, property: &Property, value: &glib::Value) -> bool {
+        match *property {
+            Property::UInt64("max-size-bytes", ..) => {
+                self.max_size_bytes = value.get().unwrap();
+            }
+            Property::UInt64("max-size-time", ..) => {
+                self.max_size_time = value.get().unwrap();
+            }
+            Property::Boolean("split-on-keyframe", ..) => {
+                self.split_on_keyframe = value.get().unwrap();
+            }
+            Property::UInt("start-index", ..) => {
+                self.start_index = value.get().unwrap();
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn get_property(&self, property: &Property) -> Option<glib::Value> {
+        match *property {
+            Property::UInt64("max-size-bytes", ..) => Some(self.max_size_bytes.to_value()),
+            Property::UInt64("max-size-time", ..) => Some(self.max_size_time.to_value()),
+            Property::Boolean("split-on-keyframe", ..) => Some(self.split_on_keyframe.to_value()),
+            Property::UInt("start-index", ..) => Some(self.start_index.to_value()),
+            _ => None,
+        }
+    }
+}
+
+pub static PROPERTIES: [Property; 5] = [
+    Property::String(
+        "location",
+        "Location",
+        "Location pattern of the files, e.g. rec-%05d.flv. Sets the URI with '%' escaped",
+        None,
+        PropertyMutability::ReadWrite,
+    ),
+    Property::UInt64(
+        "max-size-bytes",
+        "Maximum Size Bytes",
+        "Start a new file once the current one reached this size in bytes (0=unlimited)",
+        (0, u64::MAX),
+        DEFAULT_MAX_SIZE_BYTES,
+        PropertyMutability::ReadWrite,
+    ),
+    Property::UInt64(
+        "max-size-time",
+        "Maximum Size Time",
+        "Start a new file once the current one reached this duration in nanoseconds \
+         (0=unlimited)",
+        (0, u64::MAX),
+        DEFAULT_MAX_SIZE_TIME,
+        PropertyMutability::ReadWrite,
+    ),
+    Property::Boolean(
+        "split-on-keyframe",
+        "Split on Keyframe",
+        "Wait for the next keyframe once a limit was reached before starting a new file",
+        DEFAULT_SPLIT_ON_KEYFRAME,
+        PropertyMutability::ReadWrite,
+    ),
+    Property::UInt(
+        "start-index",
+        "Start Index",
+        "Index of the first file",
+        (0, u32::MAX),
+        DEFAULT_START_INDEX,
+        PropertyMutability::ReadWrite,
+    ),
+];
+
+#[derive(Debug)]
+struct Fragment {
+    file: File,
+    location: String,
+    size: u64,
+    start_pts: u64,
+    end_pts: u64,
+    start_running_time: u64,
+}
+
+#[derive(Debug)]
+enum StreamingState {
+    Stopped,
+    Started {
+        pattern: String,
+        index: u32,
+        fragment: Option<Fragment>,
+    },
+}
+
+#[derive(Debug)]
+pub struct MultiFileSink {
+    streaming_state: StreamingState,
+    settings: Settings,
+    shared_settings: SharedSettings<Settings>,
+    segment: Option<gst::Segment>,
+    headers: Vec<gst::Buffer>,
+    caps_headers: bool,
+    in_headers: bool,
+    cat: gst::DebugCategory,
+}
+
+impl MultiFileSink {
+    pub fn new(_sink: &RsBaseSink) -> MultiFileSink {
+        MultiFileSink {
+            streaming_state: StreamingState::Stopped,
+            settings: Default::default(),
+            shared_settings: SharedSettings::new(Default::default()),
+            segment: None,
+            headers: Vec::new(),
+            caps_headers: false,
+            in_headers: false,
+            cat: gst::DebugCategory::new(
+                "rsmultifilesink",
+                gst::DebugColorFlags::empty(),
+                "Rust multi file sink",
+            ),
+        }
+    }
+
+    pub fn new_boxed(sink: &RsBaseSink) -> Box<SinkImpl> {
+        Box::new(MultiFileSink::new(sink))
+    }
+
+    fn open_fragment(
+        &self,
+        sink: &RsBaseSink,
+        pattern: &str,
+        index: u32,
+        pts: u64,
+        running_time: u64,
+    ) -> Result<Fragment, ErrorMessage> {
+        let location = try!(format_location(pattern, index).ok_or_else(|| {
+            gst_error!(self.cat, obj: sink, "Invalid location pattern {:?}", pattern);
+            error_msg!(
+                gst::ResourceError::OpenWrite,
+                ["Invalid location pattern '{}'", pattern]
+            )
+        }));
+
+        let mut file = try!(File::create(&location).or_else(|err| {
+            gst_error!(
+                self.cat,
+                obj: sink,
+                "Could not open file for writing: {}",
+                err.to_string()
+            );
+            Err(error_msg!(
+                gst::ResourceError::OpenWrite,
+                [
+                    "Could not open file for writing '{}': {}",
+                    location,
+                    err.to_string()
+                ]
+            ))
+        }));
+
+        gst_debug!(self.cat, obj: sink, "Opened file {:?} for index {}", location, index);
+
+        let mut size = 0;
+        for header in &self.headers {
+            let map = header.map_readable().unwrap();
+            let data = map.as_slice();
+
+            try!(file.write_all(data).or_else(|err| {
+                gst_error!(self.cat, obj: sink, "Failed to write header: {}", err);
+                Err(error_msg!(
+                    gst::ResourceError::Write,
+                    ["Failed to write header to '{}': {}", location, err]
+                ))
+            }));
+            size += data.len() as u64;
+        }
+
+        Ok(Fragment {
+            file: file,
+            location: location,
+            size: size,
+            start_pts: pts,
+            end_pts: pts,
+            start_running_time: running_time,
+        })
+    }
+
+    fn close_fragment(
+        &self,
+        sink: &RsBaseSink,
+        index: u32,
+        fragment: Fragment,
+    ) -> Result<(), ErrorMessage> {
+        gst_debug!(
+            self.cat,
+            obj: sink,
+            "Closing file {:?} with size {}",
+            fragment.location,
+            fragment.size
+        );
+
+        // Write errors are only reported once the data is on disk for e.g.
+        // network filesystems, and a fragment is only complete after that
+        try!(fragment.file.sync_data().or_else(|err| {
+            gst_error!(self.cat, obj: sink, "Failed to close file: {}", err);
+            Err(error_msg!(
+                gst::ResourceError::Write,
+                ["Failed to close '{}': {}", fragment.location, err.to_string()]
+            ))
+        }));
+
+        let duration = if fragment.start_pts != gst::CLOCK_TIME_NONE
+            && fragment.end_pts != gst::CLOCK_TIME_NONE
+        {
+            fragment.end_pts - fragment.start_pts
+        } else {
+            gst::CLOCK_TIME_NONE
+        };
+
+        drop(fragment.file);
+
+        let s = gst::Structure::new(
+            "fragment-closed",
+            &[
+                ("location", &fragment.location),
+                ("index", &index),
+                ("size", &fragment.size),
+                ("start-pts", &fragment.start_pts),
+                ("duration", &duration),
+            ],
+        );
+        sink.post_message(&gst::Message::new_element(s).src(Some(sink)).build());
+
+        Ok(())
+    }
+
+    fn close_current_fragment(&mut self, sink: &RsBaseSink) -> Result<(), ErrorMessage> {
+        let fragment = match self.streaming_state {
+            StreamingState::Started {
+                index,
+                ref mut fragment,
+                ..
+            } => fragment.take().map(|fragment| (index, fragment)),
+            StreamingState::Stopped => None,
+        };
+
+        match fragment {
+            Some((index, fragment)) => self.close_fragment(sink, index, fragment),
+            None => Ok(()),
+        }
+    }
+}
+
+// Checks if a new file has to be started before writing a buffer of the
+// given size and running time
+fn split_due(
+    settings: &Settings,
+    fragment: &Fragment,
+    buffer_size: u64,
+    running_time: u64,
+    keyframe: bool,
+) -> bool {
+    let size_reached = settings.max_size_bytes != 0
+        && fragment.size + buffer_size > settings.max_size_bytes;
+    let time_reached = settings.max_size_time != 0
+        && running_time != gst::CLOCK_TIME_NONE
+        && fragment.start_running_time != gst::CLOCK_TIME_NONE
+        && running_time >= fragment.start_running_time
+        && running_time - fragment.start_running_time >= settings.max_size_time;
+
+    (size_reached || time_reached) && (!settings.split_on_keyframe || keyframe)
+}
+
+// The pattern is the decoded path of the URI, so '%' has to be escaped as '%25'
+// there. The location property takes care of that
+fn validate_uri(uri: &Url) -> Result<(), UriError> {
+    let location = try!(uri.to_file_path().or_else(|_| {
+        Err(UriError::new(
+            gst::URIError::UnsupportedProtocol,
+            format!("Unsupported file URI '{}'", uri.as_str()),
+        ))
+    }));
+
+    match location.to_str().and_then(|pattern| format_location(pattern, 0)) {
+        Some(_) => Ok(()),
+        None => Err(UriError::new(
+            gst::URIError::BadUri,
+            format!(
+                "URI '{}' does not contain a valid location pattern, '%' has to be \
+                 escaped as '%25'",
+                uri.as_str()
+            ),
+        )),
+    }
+}
+
+impl SinkImpl for MultiFileSink {
+    fn uri_validator(&self) -> Box<UriValidator> {
+        Box::new(validate_uri)
+    }
+
+    fn property_handle(&self) -> Option<Box<PropertyHandle>> {
+        Some(Box::new(self.shared_settings.clone()))
+    }
+
+    fn start(&mut self, sink: &RsBaseSink, uri: Url) -> Result<(), ErrorMessage> {
+        self.shared_settings.update(&mut self.settings);
+
+        if let StreamingState::Started { .. } = self.streaming_state {
+            return Err(error_msg!(
+                gst::LibraryError::Failed,
+                ["Sink already started"]
+            ));
+        }
+
+        let pattern = try!(
+            uri.to_file_path()
+                .ok()
+                .and_then(|location| location.to_str().map(String::from))
+                .ok_or_else(|| {
+                    gst_error!(
+                        self.cat,
+                        obj: sink,
+                        "Unsupported file URI '{}'",
+                        uri.as_str()
+                    );
+                    error_msg!(
+                        gst::LibraryError::Failed,
+                        ["Unsupported file URI '{}'", uri.as_str()]
+                    )
+                })
+        );
+
+        gst_debug!(self.cat, obj: sink, "Using location pattern {:?}", pattern);
+
+        self.streaming_state = StreamingState::Started {
+            pattern: pattern,
+            index: self.settings.start_index,
+            fragment: None,
+        };
+
+        Ok(())
+    }
+
+    fn stop(&mut self, sink: &RsBaseSink) -> Result<(), ErrorMessage> {
+        let res = self.close_current_fragment(sink);
+
+        self.streaming_state = StreamingState::Stopped;
+        self.segment = None;
+        self.in_headers = false;
+        if !self.caps_headers {
+            self.headers.clear();
+        }
+
+        res
+    }
+
+    fn set_caps(&mut self, sink: &RsBaseSink, caps: &gst::CapsRef) -> Result<(), ErrorMessage> {
+        let headers = caps.get_structure(0)
+            .and_then(|s| s.get::<gst::Array>("streamheader"))
+            .map(|streamheader| {
+                streamheader
+                    .as_slice()
+                    .iter()
+                    .filter_map(|v| v.get::<gst::Buffer>())
+                    .collect::<Vec<_>>()
+            });
+
+        if let Some(headers) = headers {
+            gst_debug!(
+                self.cat,
+                obj: sink,
+                "Got {} stream headers from caps",
+                headers.len()
+            );
+            self.headers = headers;
+            self.caps_headers = true;
+        } else if self.caps_headers {
+            gst_debug!(self.cat, obj: sink, "New caps without stream headers");
+            self.headers.clear();
+            self.caps_headers = false;
+        }
+
+        Ok(())
+    }
+
+    fn segment(&mut self, sink: &RsBaseSink, segment: &gst::Segment) -> Result<(), ErrorMessage> {
+        // Durations are measured in running time, other formats only allow
+        // splitting by size
+        self.segment = if segment.get_format() == gst::Format::Time {
+            Some(segment.clone())
+        } else {
+            gst_debug!(self.cat, obj: sink, "Not splitting by time in {:?} segment", segment);
+            None
+        };
+
+        Ok(())
+    }
+
+    fn render(&mut self, sink: &RsBaseSink, buffer: &gst::BufferRef) -> Result<(), FlowError> {
+        self.shared_settings.update(&mut self.settings);
+
+        let cat = self.cat;
+        let settings = self.settings;
+
+        gst_trace!(cat, obj: sink, "Rendering {:?}", buffer);
+
+        let flags = buffer.get_flags();
+        let is_header = flags.contains(gst::BufferFlags::HEADER);
+
+        // Headers from the caps are written at the beginning of every file already
+        if is_header && self.caps_headers {
+            gst_trace!(cat, obj: sink, "Skipping header buffer");
+            return Ok(());
+        }
+
+        let (pattern, mut index, mut fragment) = match self.streaming_state {
+            StreamingState::Started {
+                ref pattern,
+                index,
+                ref mut fragment,
+            } => (pattern.clone(), index, fragment.take()),
+            StreamingState::Stopped => {
+                return Err(FlowError::Error(
+                    error_msg!(gst::LibraryError::Failed, ["Not started yet"]),
+                ));
+            }
+        };
+
+        let map = match buffer.map_readable() {
+            None => {
+                return Err(FlowError::Error(error_msg!(
+                    gst::LibraryError::Failed,
+                    ["Failed to map buffer"]
+                )));
+            }
+            Some(map) => map,
+        };
+        let data = map.as_slice();
+
+        let pts = buffer.get_pts();
+        let running_time = match self.segment {
+            Some(ref segment) if pts != gst::CLOCK_TIME_NONE => {
+                segment.to_running_time(gst::Format::Time, pts)
+            }
+            _ => gst::CLOCK_TIME_NONE,
+        };
+        let keyframe = !flags.contains(gst::BufferFlags::DELTA_UNIT);
+
+        let split = match fragment {
+            Some(ref fragment) if !is_header => {
+                split_due(&settings, fragment, data.len() as u64, running_time, keyframe)
+            }
+            _ => false,
+        };
+
+        if split {
+            gst_debug!(cat, obj: sink, "Starting new file at {}", pts);
+            let res = self.close_fragment(sink, index, fragment.take().unwrap());
+            index += 1;
+
+            if let Err(msg) = res {
+                if let StreamingState::Started { index: ref mut i, .. } = self.streaming_state {
+                    *i = index;
+                }
+                return Err(FlowError::Error(msg));
+            }
+        }
+
+        let res = match fragment {
+            Some(fragment) => Ok(fragment),
+            None => self.open_fragment(sink, &pattern, index, pts, running_time),
+        };
+
+        let mut fragment = match res {
+            Ok(fragment) => fragment,
+            Err(msg) => {
+                if let StreamingState::Started { index: ref mut i, .. } = self.streaming_state {
+                    *i = index;
+                }
+                return Err(FlowError::Error(msg));
+            }
+        };
+
+        // Header buffers after other data replace the previous headers, e.g.
+        // after the upstream encoder was reconfigured
+        if is_header {
+            if !self.in_headers {
+                self.headers.clear();
+            }
+            self.headers.push(buffer.to_owned());
+        }
+        self.in_headers = is_header;
+
+        let res = fragment.file.write_all(data).or_else(|err| {
+            gst_error!(cat, obj: sink, "Failed to write: {}", err);
+            Err(FlowError::Error(error_msg!(
+                gst::ResourceError::Write,
+                ["Failed to write to '{}': {}", fragment.location, err]
+            )))
+        });
+
+        fragment.size += data.len() as u64;
+        if fragment.start_pts == gst::CLOCK_TIME_NONE {
+            fragment.start_pts = pts;
+        }
+        if fragment.start_running_time == gst::CLOCK_TIME_NONE {
+            fragment.start_running_time = running_time;
+        }
+        if pts != gst::CLOCK_TIME_NONE {
+            let duration = buffer.get_duration();
+            fragment.end_pts = if duration != gst::CLOCK_TIME_NONE {
+                pts + duration
+            } else {
+                pts
+            };
+        }
+
+        if let StreamingState::Started {
+            index: ref mut i,
+            fragment: ref mut f,
+            ..
+        } = self.streaming_state
+        {
+            *i = index;
+            *f = Some(fragment);
+        }
+
+        res
+    }
+
+    fn eos(&mut self, sink: &RsBaseSink) -> Result<(), ErrorMessage> {
+        self.close_current_fragment(sink)
+    }
+
+    fn set_property(&mut self, sink: &RsBaseSink, property: &Property, value: &glib::Value) {
+        match *property {
+            Property::String("location", ..) => {
+                if let Some(location) = value.get::<String>() {
+                    let element = sink.clone().upcast::<gst::Element>();
+                    if let Err(err) = set_location(&element, &location) {
+                        gst_warning!(self.cat, obj: sink, "Failed to set location: {}", err);
+                    }
+                }
+            }
+            _ => {
+                gst_warning!(self.cat, obj: sink, "Unhandled property {:?}", property);
+            }
+        }
+    }
+
+    fn get_property(&self, sink: &RsBaseSink, property: &Property) -> Result<glib::Value, ()> {
+        match *property {
+            Property::String("location", ..) => {
+                Ok(get_location(&sink.clone().upcast::<gst::Element>()).to_value())
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_due, Fragment, Settings};
+    use gst;
+    use std::env;
+    use std::fs::{self, File};
+    use std::process;
+
+    #[test]
+    fn test_split_due() {
+        let path = env::temp_dir().join(format!("rsmultifilesink-split-{}", process::id()));
+        let mut fragment = Fragment {
+            file: File::create(&path).unwrap(),
+            location: String::from("split"),
+            size: 900,
+            start_pts: 10_000,
+            end_pts: 10_000,
+            start_running_time: 1_000,
+        };
+        let mut settings = Settings::default();
+
+        // Unlimited by default
+        assert!(!split_due(&settings, &fragment, 1_000_000, 1_000_000_000, true));
+
+        settings.max_size_bytes = 1000;
+        assert!(!split_due(&settings, &fragment, 100, 1_000, true));
+        assert!(split_due(&settings, &fragment, 101, 1_000, true));
+
+        // Durations are measured in running time, independent of the PTS
+        settings.max_size_bytes = 0;
+        settings.max_size_time = 500;
+        assert!(!split_due(&settings, &fragment, 100, 1_499, true));
+        assert!(split_due(&settings, &fragment, 100, 1_500, true));
+        assert!(!split_due(&settings, &fragment, 100, gst::CLOCK_TIME_NONE, true));
+
+        // Running time going backwards after a new segment
+        assert!(!split_due(&settings, &fragment, 100, 500, true));
+
+        settings.split_on_keyframe = true;
+        assert!(!split_due(&settings, &fragment, 100, 1_500, false));
+        assert!(split_due(&settings, &fragment, 100, 1_500, true));
+
+        fragment.start_running_time = gst::CLOCK_TIME_NONE;
+        assert!(!split_due(&settings, &fragment, 100, 1_500, true));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,386 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{i32, u32};
+use std::io::{ErrorKind, Read};
+use std::fs::File;
+use url::Url;
+
+use gst_plugin::error::*;
+use gst_plugin::properties::*;
+use gst_plugin_simple::source::*;
+use gst_plugin_simple::{PropertyHandle, SettingsImpl, SharedSettings, UriValidator};
+
+use glib;
+use gst;
+use gst::prelude::*;
+
+use location::{format_location, get_location, set_location};
+
+const DEFAULT_START_INDEX: u32 = 0;
+const DEFAULT_STOP_INDEX: i32 = -1;
+
+#[derive(Debug, Clone, Copy)]
+struct Settings {
+    start_index: u32,
+    stop_index: i32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            start_index: DEFAULT_START_INDEX,
+            stop_index: DEFAULT_STOP_INDEX,
+        }
+    }
+}
+
+impl SettingsImpl for Settings {
+    fn set_property(&mut self, property: &Property, value: &glib::Value) -> bool {
+        match *property {
+            Property::UInt("start-index", ..) => {
+                self.start_index = value.get().unwrap();
+            }
+            Property::Int("stop-index", ..) => {
+                self.stop_index = value.get().unwrap();
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn get_property(&self, property: &Property) -> Option<glib::Value> {
+        match *property {
+            Property::UInt("start-index", ..) => Some(self.start_index.to_value()),
+            Property::Int("stop-index", ..) => Some(self.stop_index.to_value()),
+            _ => None,
+        }
+    }
+}
+
+pub static PROPERTIES: [Property; 3] = [
+    Property::String(
+        "location",
+        "Location",
+        "Location pattern of the files, e.g. frame-%05d.jpg. Sets the URI with '%' escaped",
+        None,
+        PropertyMutability::ReadWrite,
+    ),
+    Property::UInt(
+        "start-index",
+        "Start Index",
+        "Index of the first file",
+        (0, u32::MAX),
+        DEFAULT_START_INDEX,
+        PropertyMutability::ReadWrite,
+    ),
+    Property::Int(
+        "stop-index",
+        "Stop Index",
+        "Index of the last file (-1=until a file does not exist)",
+        (-1, i32::MAX),
+        DEFAULT_STOP_INDEX,
+        PropertyMutability::ReadWrite,
+    ),
+];
+
+#[derive(Debug)]
+enum StreamingState {
+    Stopped,
+    Started { pattern: String, index: u32 },
+}
+
+#[derive(Debug)]
+pub struct MultiFileSrc {
+    streaming_state: StreamingState,
+    settings: Settings,
+    shared_settings: SharedSettings<Settings>,
+    cat: gst::DebugCategory,
+}
+
+impl MultiFileSrc {
+    pub fn new(_src: &RsBaseSrc) -> MultiFileSrc {
+        MultiFileSrc {
+            streaming_state: StreamingState::Stopped,
+            settings: Default::default(),
+            shared_settings: SharedSettings::new(Default::default()),
+            cat: gst::DebugCategory::new(
+                "rsmultifilesrc",
+                gst::DebugColorFlags::empty(),
+                "Rust multi file source",
+            ),
+        }
+    }
+
+    pub fn new_boxed(src: &RsBaseSrc) -> Box<SourceImpl> {
+        Box::new(MultiFileSrc::new(src))
+    }
+
+    fn read_next(&mut self, src: &RsBaseSrc) -> Result<gst::Buffer, FlowError> {
+        let cat = self.cat;
+        let settings = self.settings;
+
+        let (pattern, index) = match self.streaming_state {
+            StreamingState::Started {
+                ref pattern,
+                ref mut index,
+            } => (pattern, index),
+            StreamingState::Stopped => {
+                return Err(FlowError::Error(
+                    error_msg!(gst::LibraryError::Failed, ["Not started yet"]),
+                ));
+            }
+        };
+
+        if settings.stop_index >= 0 && *index > settings.stop_index as u32 {
+            gst_debug!(cat, obj: src, "Reached stop index {}", settings.stop_index);
+            return Err(FlowError::Eos);
+        }
+
+        let first = *index == settings.start_index;
+        let (location, data) = match read_file(pattern, *index, first) {
+            Ok(Some(file)) => file,
+            Ok(None) => {
+                gst_debug!(cat, obj: src, "File for index {} does not exist, finishing", *index);
+                return Err(FlowError::Eos);
+            }
+            Err(msg) => {
+                gst_error!(cat, obj: src, "Failed to read file for index {}: {:?}", *index, msg);
+                return Err(FlowError::Error(msg));
+            }
+        };
+
+        gst_debug!(
+            cat,
+            obj: src,
+            "Read file {:?} with size {}",
+            location,
+            data.len()
+        );
+
+        *index += 1;
+
+        Ok(gst::Buffer::from_mut_slice(data).unwrap())
+    }
+}
+
+// Reads the whole file for the given index. A file that does not exist ends
+// the sequence, unless it is the first one
+fn read_file(
+    pattern: &str,
+    index: u32,
+    first: bool,
+) -> Result<Option<(String, Vec<u8>)>, ErrorMessage> {
+    let location = try!(format_location(pattern, index).ok_or_else(|| {
+        error_msg!(
+            gst::ResourceError::OpenRead,
+            ["Invalid location pattern '{}'", pattern]
+        )
+    }));
+
+    let mut file = match File::open(&location) {
+        Ok(file) => file,
+        Err(ref err) if err.kind() == ErrorKind::NotFound && !first => return Ok(None),
+        Err(err) => {
+            return Err(error_msg!(
+                gst::ResourceError::OpenRead,
+                [
+                    "Could not open file for reading '{}': {}",
+                    location,
+                    err.to_string()
+                ]
+            ));
+        }
+    };
+
+    let mut data = Vec::new();
+    try!(file.read_to_end(&mut data).or_else(|err| {
+        Err(error_msg!(
+            gst::ResourceError::Read,
+            ["Failed to read '{}': {}", location, err.to_string()]
+        ))
+    }));
+
+    Ok(Some((location, data)))
+}
+
+// The pattern is the decoded path of the URI, so '%' has to be escaped as '%25'
+// there. The location property takes care of that
+fn validate_uri(uri: &Url) -> Result<(), UriError> {
+    let location = try!(uri.to_file_path().or_else(|_| {
+        Err(UriError::new(
+            gst::URIError::UnsupportedProtocol,
+            format!("Unsupported file URI '{}'", uri.as_str()),
+        ))
+    }));
+
+    match location.to_str().and_then(|pattern| format_location(pattern, 0)) {
+        Some(_) => Ok(()),
+        None => Err(UriError::new(
+            gst::URIError::BadUri,
+            format!(
+                "URI '{}' does not contain a valid location pattern, '%' has to be \
+                 escaped as '%25'",
+                uri.as_str()
+            ),
+        )),
+    }
+}
+
+impl SourceImpl for MultiFileSrc {
+    fn uri_validator(&self) -> Box<UriValidator> {
+        Box::new(validate_uri)
+    }
+
+    fn property_handle(&self) -> Option<Box<PropertyHandle>> {
+        Some(Box::new(self.shared_settings.clone()))
+    }
+
+    fn is_seekable(&self, _src: &RsBaseSrc) -> bool {
+        false
+    }
+
+    fn get_size(&self, _src: &RsBaseSrc) -> Option<u64> {
+        None
+    }
+
+    fn start(&mut self, src: &RsBaseSrc, uri: Url) -> Result<(), ErrorMessage> {
+        self.shared_settings.update(&mut self.settings);
+
+        if let StreamingState::Started { .. } = self.streaming_state {
+            return Err(error_msg!(
+                gst::LibraryError::Failed,
+                ["Source already started"]
+            ));
+        }
+
+        let pattern = try!(
+            uri.to_file_path()
+                .ok()
+                .and_then(|location| location.to_str().map(String::from))
+                .ok_or_else(|| {
+                    gst_error!(
+                        self.cat,
+                        obj: src,
+                        "Unsupported file URI '{}'",
+                        uri.as_str()
+                    );
+                    error_msg!(
+                        gst::LibraryError::Failed,
+                        ["Unsupported file URI '{}'", uri.as_str()]
+                    )
+                })
+        );
+
+        gst_debug!(self.cat, obj: src, "Using location pattern {:?}", pattern);
+
+        self.streaming_state = StreamingState::Started {
+            pattern: pattern,
+            index: self.settings.start_index,
+        };
+
+        Ok(())
+    }
+
+    fn stop(&mut self, _src: &RsBaseSrc) -> Result<(), ErrorMessage> {
+        self.streaming_state = StreamingState::Stopped;
+
+        Ok(())
+    }
+
+    fn fill(
+        &mut self,
+        _src: &RsBaseSrc,
+        _offset: u64,
+        _: u32,
+        _buffer: &mut gst::BufferRef,
+    ) -> Result<(), FlowError> {
+        // Every file is output as a single buffer from create()
+        Err(FlowError::Error(error_msg!(
+            gst::LibraryError::Failed,
+            ["Filling buffers is not supported"]
+        )))
+    }
+
+    fn create(
+        &mut self,
+        src: &RsBaseSrc,
+        _offset: u64,
+        _length: u32,
+    ) -> Option<Result<gst::Buffer, FlowError>> {
+        self.shared_settings.update(&mut self.settings);
+
+        Some(self.read_next(src))
+    }
+
+    fn seek(&mut self, _src: &RsBaseSrc, _: u64, _: Option<u64>) -> Result<(), ErrorMessage> {
+        Ok(())
+    }
+
+    fn set_property(&mut self, src: &RsBaseSrc, property: &Property, value: &glib::Value) {
+        match *property {
+            Property::String("location", ..) => {
+                if let Some(location) = value.get::<String>() {
+                    let element = src.clone().upcast::<gst::Element>();
+                    if let Err(err) = set_location(&element, &location) {
+                        gst_warning!(self.cat, obj: src, "Failed to set location: {}", err);
+                    }
+                }
+            }
+            _ => {
+                gst_warning!(self.cat, obj: src, "Unhandled property {:?}", property);
+            }
+        }
+    }
+
+    fn get_property(&self, src: &RsBaseSrc, property: &Property) -> Result<glib::Value, ()> {
+        match *property {
+            Property::String("location", ..) => {
+                Ok(get_location(&src.clone().upcast::<gst::Element>()).to_value())
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_file;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::process;
+
+    #[test]
+    fn test_read_file() {
+        let dir = env::temp_dir().join(format!("rsmultifilesrc-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for index in 1..4 {
+            let mut file = File::create(dir.join(format!("frame-{:03}.raw", index))).unwrap();
+            file.write_all(&vec![index as u8; index * 10]).unwrap();
+        }
+
+        let pattern = dir.join("frame-%03d.raw");
+        let pattern = pattern.to_str().unwrap();
+
+        for index in 1..4 {
+            let (location, data) = read_file(pattern, index, index == 1).unwrap().unwrap();
+            assert!(location.ends_with(&format!("frame-{:03}.raw", index)));
+            assert_eq!(data, vec![index as u8; index as usize * 10]);
+        }
+
+        // The sequence ends at the first missing file, but a missing first
+        // file is an error
+        assert!(read_file(pattern, 4, false).unwrap().is_none());
+        assert!(read_file(pattern, 0, true).is_err());
+        assert!(read_file(dir.join("frame.raw").to_str().unwrap(), 1, true).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
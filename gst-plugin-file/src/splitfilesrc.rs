@@ -0,0 +1,541 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::cmp::{self, Ordering};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use url::Url;
+
+use gst_plugin::error::*;
+use gst_plugin::properties::*;
+use gst_plugin_simple::source::*;
+use gst_plugin_simple::UriValidator;
+
+use glib;
+use gst;
+use gst::prelude::*;
+
+use filesrc::read_at;
+use location::{get_location, set_location};
+
+pub static PROPERTIES: [Property; 1] = [
+    Property::String(
+        "location",
+        "Location",
+        "Location of the files with '*' and '?' wildcards in the file name. Sets the URI \
+         with the wildcards escaped",
+        None,
+        PropertyMutability::ReadWrite,
+    ),
+];
+
+#[derive(Debug)]
+struct Part {
+    file: File,
+    location: PathBuf,
+    start: u64,
+    size: u64,
+    position: u64,
+}
+
+#[derive(Debug)]
+enum StreamingState {
+    Stopped,
+    Started { parts: Vec<Part>, size: u64 },
+}
+
+#[derive(Debug)]
+pub struct SplitFileSrc {
+    streaming_state: StreamingState,
+    cat: gst::DebugCategory,
+}
+
+impl SplitFileSrc {
+    pub fn new(_src: &RsBaseSrc) -> SplitFileSrc {
+        SplitFileSrc {
+            streaming_state: StreamingState::Stopped,
+            cat: gst::DebugCategory::new(
+                "rssplitfilesrc",
+                gst::DebugColorFlags::empty(),
+                "Rust split file source",
+            ),
+        }
+    }
+
+    pub fn new_boxed(src: &RsBaseSrc) -> Box<SourceImpl> {
+        Box::new(SplitFileSrc::new(src))
+    }
+}
+
+// Matches a file name against a pattern containing '*' and '?' wildcards
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(&b'*'), _) => {
+            glob_match(&pattern[1..], name) || (!name.is_empty() && glob_match(pattern, &name[1..]))
+        }
+        (Some(&b'?'), Some(_)) => glob_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => glob_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+fn is_digit(c: u8) -> bool {
+    c >= b'0' && c <= b'9'
+}
+
+// Compares file names with runs of digits compared by their numeric value,
+// e.g. "chunk-9.ts" sorts before "chunk-10.ts"
+fn natural_cmp(a: &[u8], b: &[u8]) -> Ordering {
+    let (mut a, mut b) = (a, b);
+
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(&x), Some(&y)) if is_digit(x) && is_digit(y) => {
+                let a_len = a.iter().take_while(|&&c| is_digit(c)).count();
+                let b_len = b.iter().take_while(|&&c| is_digit(c)).count();
+                let a_zeros = a[..a_len].iter().take_while(|&&c| c == b'0').count();
+                let b_zeros = b[..b_len].iter().take_while(|&&c| c == b'0').count();
+                let (a_num, b_num) = (&a[a_zeros..a_len], &b[b_zeros..b_len]);
+
+                // Equal numbers with fewer leading zeros sort first
+                let ord = a_num
+                    .len()
+                    .cmp(&b_num.len())
+                    .then_with(|| a_num.cmp(b_num))
+                    .then(a_len.cmp(&b_len));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+
+                a = &a[a_len..];
+                b = &b[b_len..];
+            }
+            (Some(&x), Some(&y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+
+                a = &a[1..];
+                b = &b[1..];
+            }
+        }
+    }
+}
+
+// Only the file name can contain wildcards. All matching files are sorted by
+// name, with numbers in the names sorted by their value
+fn find_files(location: &Path) -> Result<Vec<PathBuf>, String> {
+    let dir = location.parent().unwrap_or_else(|| Path::new("."));
+    let pattern = try!(
+        location
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| String::from("Invalid file name pattern"))
+    );
+
+    let entries = try!(fs::read_dir(dir).map_err(|err| err.to_string()));
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = try!(entry.map_err(|err| err.to_string()));
+        let name = entry.file_name();
+        let matches = name.to_str()
+            .map_or(false, |name| glob_match(pattern.as_bytes(), name.as_bytes()));
+
+        if matches {
+            files.push(entry.path());
+        }
+    }
+
+    // The names are valid UTF-8 as they matched the pattern
+    files.sort_by(|a, b| {
+        let a = a.file_name().and_then(|name| name.to_str()).unwrap_or("");
+        let b = b.file_name().and_then(|name| name.to_str()).unwrap_or("");
+        natural_cmp(a.as_bytes(), b.as_bytes())
+    });
+
+    Ok(files)
+}
+
+// Opens all files, which are placed one after another in the stream
+fn open_parts(locations: Vec<PathBuf>) -> Result<(Vec<Part>, u64), ErrorMessage> {
+    let mut parts = Vec::with_capacity(locations.len());
+    let mut size = 0;
+
+    for location in locations {
+        let file = try!(File::open(&location).or_else(|err| {
+            Err(error_msg!(
+                gst::ResourceError::OpenRead,
+                [
+                    "Could not open file for reading '{}': {}",
+                    location.to_str().unwrap_or("Non-UTF8 path"),
+                    err.to_string()
+                ]
+            ))
+        }));
+
+        let part_size = try!(file.metadata().map(|m| m.len()).or_else(|err| {
+            Err(error_msg!(
+                gst::ResourceError::OpenRead,
+                [
+                    "Could not query size of '{}': {}",
+                    location.to_str().unwrap_or("Non-UTF8 path"),
+                    err.to_string()
+                ]
+            ))
+        }));
+
+        parts.push(Part {
+            file: file,
+            location: location,
+            start: size,
+            size: part_size,
+            position: 0,
+        });
+        size += part_size;
+    }
+
+    Ok((parts, size))
+}
+
+// Reads at the given offset of the whole stream. Reads can span multiple files
+fn read_parts(parts: &mut [Part], offset: u64, data: &mut [u8]) -> Result<usize, ErrorMessage> {
+    let mut filled = 0;
+
+    while filled < data.len() {
+        let position = offset + filled as u64;
+        let part = match parts
+            .iter_mut()
+            .find(|part| position < part.start + part.size)
+        {
+            Some(part) => part,
+            None => break,
+        };
+
+        let part_position = position - part.start;
+        let to_read = cmp::min((data.len() - filled) as u64, part.size - part_position) as usize;
+
+        let read = try!(
+            read_at(
+                &mut part.file,
+                &mut part.position,
+                part_position,
+                &mut data[filled..(filled + to_read)],
+            ).or_else(|err| {
+                Err(error_msg!(
+                    gst::ResourceError::Read,
+                    [
+                        "Failed to read at {} in '{}': {}",
+                        part_position,
+                        part.location.to_str().unwrap_or("Non-UTF8 path"),
+                        err.to_string()
+                    ]
+                ))
+            })
+        );
+
+        // File was truncated in the meantime
+        if read == 0 {
+            break;
+        }
+
+        filled += read;
+    }
+
+    Ok(filled)
+}
+
+// The wildcards are part of the decoded path of the URI, so '?' has to be
+// escaped as '%3F' there. The location property takes care of that
+fn validate_uri(uri: &Url) -> Result<(), UriError> {
+    let _ = try!(uri.to_file_path().or_else(|_| {
+        Err(UriError::new(
+            gst::URIError::UnsupportedProtocol,
+            format!("Unsupported file URI '{}'", uri.as_str()),
+        ))
+    }));
+
+    if uri.query().is_some() {
+        return Err(UriError::new(
+            gst::URIError::BadUri,
+            format!(
+                "URI '{}' contains a query, '?' has to be escaped as '%3F'",
+                uri.as_str()
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+impl SourceImpl for SplitFileSrc {
+    fn uri_validator(&self) -> Box<UriValidator> {
+        Box::new(validate_uri)
+    }
+
+    fn is_seekable(&self, _src: &RsBaseSrc) -> bool {
+        true
+    }
+
+    fn get_size(&self, _src: &RsBaseSrc) -> Option<u64> {
+        match self.streaming_state {
+            StreamingState::Started { size, .. } => Some(size),
+            StreamingState::Stopped => None,
+        }
+    }
+
+    fn start(&mut self, src: &RsBaseSrc, uri: Url) -> Result<(), ErrorMessage> {
+        if let StreamingState::Started { .. } = self.streaming_state {
+            return Err(error_msg!(
+                gst::LibraryError::Failed,
+                ["Source already started"]
+            ));
+        }
+
+        let location = try!(uri.to_file_path().or_else(|_| {
+            gst_error!(
+                self.cat,
+                obj: src,
+                "Unsupported file URI '{}'",
+                uri.as_str()
+            );
+            Err(error_msg!(
+                gst::LibraryError::Failed,
+                ["Unsupported file URI '{}'", uri.as_str()]
+            ))
+        }));
+
+        let locations = try!(find_files(&location).or_else(|err| {
+            gst_error!(self.cat, obj: src, "Failed to find files: {}", err);
+            Err(error_msg!(
+                gst::ResourceError::OpenRead,
+                [
+                    "Failed to find files matching '{}': {}",
+                    location.to_str().unwrap_or("Non-UTF8 path"),
+                    err
+                ]
+            ))
+        }));
+
+        if locations.is_empty() {
+            return Err(error_msg!(
+                gst::ResourceError::NotFound,
+                [
+                    "No files matching '{}'",
+                    location.to_str().unwrap_or("Non-UTF8 path")
+                ]
+            ));
+        }
+
+        let (parts, size) = try!(open_parts(locations).or_else(|msg| {
+            gst_error!(self.cat, obj: src, "Failed to open files: {:?}", msg);
+            Err(msg)
+        }));
+
+        for part in &parts {
+            gst_debug!(
+                self.cat,
+                obj: src,
+                "Added file {:?} at offset {} with size {}",
+                part.location,
+                part.start,
+                part.size
+            );
+        }
+
+        self.streaming_state = StreamingState::Started {
+            parts: parts,
+            size: size,
+        };
+
+        Ok(())
+    }
+
+    fn stop(&mut self, _src: &RsBaseSrc) -> Result<(), ErrorMessage> {
+        self.streaming_state = StreamingState::Stopped;
+
+        Ok(())
+    }
+
+    fn fill(
+        &mut self,
+        src: &RsBaseSrc,
+        offset: u64,
+        _: u32,
+        buffer: &mut gst::BufferRef,
+    ) -> Result<(), FlowError> {
+        let cat = self.cat;
+
+        let (parts, size) = match self.streaming_state {
+            StreamingState::Started {
+                ref mut parts,
+                size,
+            } => (parts, size),
+            StreamingState::Stopped => {
+                return Err(FlowError::Error(
+                    error_msg!(gst::LibraryError::Failed, ["Not started yet"]),
+                ));
+            }
+        };
+
+        if offset >= size {
+            return Err(FlowError::Eos);
+        }
+
+        let filled = {
+            let mut map = match buffer.map_writable() {
+                None => {
+                    return Err(FlowError::Error(error_msg!(
+                        gst::LibraryError::Failed,
+                        ["Failed to map buffer"]
+                    )));
+                }
+                Some(map) => map,
+            };
+
+            try!(read_parts(parts, offset, map.as_mut_slice()).or_else(|msg| {
+                gst_error!(cat, obj: src, "Failed to read: {:?}", msg);
+                Err(FlowError::Error(msg))
+            }))
+        };
+
+        buffer.set_size(filled);
+
+        Ok(())
+    }
+
+    fn seek(&mut self, _src: &RsBaseSrc, _: u64, _: Option<u64>) -> Result<(), ErrorMessage> {
+        Ok(())
+    }
+
+    fn set_property(&mut self, src: &RsBaseSrc, property: &Property, value: &glib::Value) {
+        match *property {
+            Property::String("location", ..) => {
+                if let Some(location) = value.get::<String>() {
+                    let element = src.clone().upcast::<gst::Element>();
+                    if let Err(err) = set_location(&element, &location) {
+                        gst_warning!(self.cat, obj: src, "Failed to set location: {}", err);
+                    }
+                }
+            }
+            _ => {
+                gst_warning!(self.cat, obj: src, "Unhandled property {:?}", property);
+            }
+        }
+    }
+
+    fn get_property(&self, src: &RsBaseSrc, property: &Property) -> Result<glib::Value, ()> {
+        match *property {
+            Property::String("location", ..) => {
+                Ok(get_location(&src.clone().upcast::<gst::Element>()).to_value())
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_files, glob_match, natural_cmp, open_parts, read_parts};
+    use std::cmp::Ordering;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::PathBuf;
+    use std::process;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("rssplitfilesrc-{}-{}", name, process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"chunk-*.ts", b"chunk-00001.ts"));
+        assert!(glob_match(b"chunk-*.ts", b"chunk-.ts"));
+        assert!(glob_match(b"chunk-?.ts", b"chunk-1.ts"));
+        assert!(glob_match(b"*", b"anything"));
+        assert!(!glob_match(b"chunk-?.ts", b"chunk-12.ts"));
+        assert!(!glob_match(b"chunk-*.ts", b"chunk-00001.mp4"));
+        assert!(!glob_match(b"chunk.ts", b"other.ts"));
+    }
+
+    #[test]
+    fn test_natural_cmp() {
+        assert_eq!(natural_cmp(b"chunk-9.ts", b"chunk-10.ts"), Ordering::Less);
+        assert_eq!(natural_cmp(b"chunk-10.ts", b"chunk-9.ts"), Ordering::Greater);
+        assert_eq!(natural_cmp(b"chunk-009.ts", b"chunk-010.ts"), Ordering::Less);
+        assert_eq!(natural_cmp(b"chunk-9.ts", b"chunk-09.ts"), Ordering::Less);
+        assert_eq!(natural_cmp(b"a2b10", b"a2b9"), Ordering::Greater);
+        assert_eq!(natural_cmp(b"chunk.ts", b"chunk-1.ts"), Ordering::Greater);
+        assert_eq!(natural_cmp(b"chunk-1.ts", b"chunk-1.ts"), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_find_files() {
+        let dir = test_dir("find");
+        for name in &["chunk-10.ts", "chunk-9.ts", "chunk-100.ts", "other.ts"] {
+            File::create(dir.join(name)).unwrap();
+        }
+
+        let files = find_files(&dir.join("chunk-*.ts")).unwrap();
+        let names = files
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["chunk-9.ts", "chunk-10.ts", "chunk-100.ts"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_parts() {
+        let dir = test_dir("read");
+        let data = (0..25).collect::<Vec<u8>>();
+        let mut locations = Vec::new();
+        for (i, range) in [(0, 10), (10, 10), (10, 17), (17, 25)].iter().enumerate() {
+            let location = dir.join(format!("part-{}", i));
+            File::create(&location)
+                .unwrap()
+                .write_all(&data[range.0..range.1])
+                .unwrap();
+            locations.push(location);
+        }
+
+        let (mut parts, size) = open_parts(locations).unwrap();
+        assert_eq!(size, 25);
+        assert_eq!(
+            parts.iter().map(|part| (part.start, part.size)).collect::<Vec<_>>(),
+            vec![(0, 10), (10, 0), (10, 7), (17, 8)]
+        );
+
+        let mut read = |offset: u64, length: usize| {
+            let mut buffer = vec![0; length];
+            let filled = read_parts(&mut parts, offset, &mut buffer).unwrap();
+            buffer.truncate(filled);
+            buffer
+        };
+
+        // Within the first file, across the empty file and across all files
+        assert_eq!(read(2, 5), &data[2..7]);
+        assert_eq!(read(8, 6), &data[8..14]);
+        assert_eq!(read(0, 25), &data[..]);
+
+        // Backwards, and limited by the end of the last file
+        assert_eq!(read(15, 5), &data[15..20]);
+        assert_eq!(read(20, 10), &data[20..]);
+        assert_eq!(read(25, 10), &[]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
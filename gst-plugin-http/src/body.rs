@@ -0,0 +1,233 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::cmp;
+use std::fmt;
+use std::io::{self, ErrorKind, Read};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use gst_plugin_simple::Flushing;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+const MAX_QUEUED_CHUNKS: usize = 4;
+
+// Interval in which waiting for data checks if it should be interrupted
+const WAIT_TIMEOUT_MS: u64 = 100;
+
+// Reads a response body in a separate thread, so that waiting for data can
+// be interrupted by flushing and can time out. The thread finishes once the
+// reader is dropped and its current read returned
+pub struct BodyReader {
+    receiver: Receiver<io::Result<Vec<u8>>>,
+    pending: Vec<u8>,
+    offset: usize,
+    flushing: Flushing,
+    timeout: Option<Duration>,
+}
+
+impl BodyReader {
+    pub fn new<R: Read + Send + 'static>(
+        mut reader: R,
+        flushing: Flushing,
+        timeout: Option<Duration>,
+    ) -> io::Result<BodyReader> {
+        let (sender, receiver) = mpsc::sync_channel(MAX_QUEUED_CHUNKS);
+
+        try!(
+            thread::Builder::new()
+                .name("rshttpsrc-body".into())
+                .spawn(move || {
+                    loop {
+                        let mut chunk = vec![0; CHUNK_SIZE];
+                        let res = match reader.read(&mut chunk) {
+                            Ok(0) => break,
+                            Ok(size) => {
+                                chunk.truncate(size);
+                                Ok(chunk)
+                            }
+                            Err(ref err) if err.kind() == ErrorKind::Interrupted => continue,
+                            Err(err) => Err(err),
+                        };
+
+                        let failed = res.is_err();
+                        if sender.send(res).is_err() || failed {
+                            break;
+                        }
+                    }
+
+                    // Releases the connection before the end is signalled
+                    drop(reader);
+                })
+        );
+
+        Ok(BodyReader {
+            receiver: receiver,
+            pending: Vec::new(),
+            offset: 0,
+            flushing: flushing,
+            timeout: timeout,
+        })
+    }
+
+    // Reads and drops the remaining data, as only connections of completely
+    // read responses are reused. Gives up after more than max bytes
+    pub fn drain(&mut self, max: u64) -> io::Result<bool> {
+        let mut scratch = vec![0; CHUNK_SIZE];
+        let mut drained = 0;
+
+        loop {
+            match try!(self.read(&mut scratch)) {
+                0 => return Ok(true),
+                size => {
+                    drained += size as u64;
+                    if drained > max {
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Read for BodyReader {
+    // Fails with ErrorKind::Other if flushing, not with ErrorKind::Interrupted
+    // as that would make callers retry immediately
+    fn read(&mut self, data: &mut [u8]) -> io::Result<usize> {
+        if self.offset == self.pending.len() {
+            let start = Instant::now();
+
+            loop {
+                if self.flushing.is_flushing() {
+                    return Err(io::Error::new(ErrorKind::Other, "Flushing"));
+                }
+
+                match self.receiver
+                    .recv_timeout(Duration::from_millis(WAIT_TIMEOUT_MS))
+                {
+                    Ok(Ok(chunk)) => {
+                        self.pending = chunk;
+                        self.offset = 0;
+                        break;
+                    }
+                    Ok(Err(err)) => return Err(err),
+                    Err(RecvTimeoutError::Timeout) => {
+                        if self.timeout.map_or(false, |timeout| start.elapsed() >= timeout) {
+                            return Err(io::Error::new(
+                                ErrorKind::TimedOut,
+                                "Timed out waiting for data",
+                            ));
+                        }
+                    }
+                    Err(RecvTimeoutError::Disconnected) => return Ok(0),
+                }
+            }
+        }
+
+        let size = cmp::min(data.len(), self.pending.len() - self.offset);
+        data[..size].copy_from_slice(&self.pending[self.offset..(self.offset + size)]);
+        self.offset += size;
+
+        Ok(size)
+    }
+}
+
+impl fmt::Debug for BodyReader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BodyReader")
+            .field("pending", &(self.pending.len() - self.offset))
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::{TcpListener, TcpStream};
+    use reqwest::Client;
+
+    fn read_head(stream: &mut TcpStream) -> bool {
+        let mut data = Vec::new();
+        let mut buf = [0u8; 1];
+
+        while !data.ends_with(b"\r\n\r\n") {
+            match stream.read(&mut buf) {
+                Ok(1) => data.push(buf[0]),
+                _ => return false,
+            }
+        }
+
+        true
+    }
+
+    // Serves the given number of requests with a body of the given size and
+    // returns the number of connections that were needed for that
+    fn serve(listener: TcpListener, requests: usize, size: usize) -> usize {
+        let body = vec![0u8; size];
+        let mut connections = 0;
+        let mut served = 0;
+
+        while served < requests {
+            let (mut stream, _) = listener.accept().unwrap();
+            connections += 1;
+
+            while served < requests && read_head(&mut stream) {
+                served += 1;
+                let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", size);
+                if stream.write_all(head.as_bytes()).is_err() || stream.write_all(&body).is_err() {
+                    break;
+                }
+            }
+        }
+
+        connections
+    }
+
+    fn read_partially(client: &Client, uri: &str) -> BodyReader {
+        let response = client.get(uri).send().unwrap();
+        let mut body = BodyReader::new(response, Flushing::new(), None).unwrap();
+        let mut buf = [0u8; 16];
+        body.read_exact(&mut buf).unwrap();
+        body
+    }
+
+    #[test]
+    fn test_drain_reuses_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/", listener.local_addr().unwrap());
+        let server = thread::spawn(move || serve(listener, 3, 100 * 1024));
+
+        let client = Client::new();
+        for _ in 0..3 {
+            let mut body = read_partially(&client, &uri);
+            assert!(body.drain(256 * 1024).unwrap());
+            // The connection is returned to the pool asynchronously
+            thread::sleep(Duration::from_millis(100));
+        }
+
+        assert_eq!(server.join().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_drop_closes_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/", listener.local_addr().unwrap());
+        let server = thread::spawn(move || serve(listener, 3, 16 * 1024 * 1024));
+
+        let client = Client::new();
+        for _ in 0..3 {
+            let mut body = read_partially(&client, &uri);
+            assert!(!body.drain(256 * 1024).unwrap());
+        }
+
+        assert_eq!(server.join().unwrap(), 3);
+    }
+}
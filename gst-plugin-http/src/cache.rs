@@ -0,0 +1,145 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::cmp;
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read};
+
+// Least recently used blocks of a fixed size, indexed by offset / block size
+#[derive(Debug)]
+pub struct BlockCache {
+    block_size: u64,
+    max_blocks: usize,
+    blocks: VecDeque<(u64, Vec<u8>)>,
+}
+
+impl BlockCache {
+    pub fn new(block_size: u64, max_blocks: usize) -> BlockCache {
+        assert!(block_size > 0);
+
+        BlockCache {
+            block_size: block_size,
+            max_blocks: cmp::max(max_blocks, 1),
+            blocks: VecDeque::new(),
+        }
+    }
+
+    pub fn block_size(&self) -> u64 {
+        self.block_size
+    }
+
+    pub fn contains(&self, index: u64) -> bool {
+        self.blocks.iter().any(|&(i, _)| i == index)
+    }
+
+    pub fn get(&mut self, index: u64) -> Option<&[u8]> {
+        let idx = match self.blocks.iter().position(|&(i, _)| i == index) {
+            Some(idx) => idx,
+            None => return None,
+        };
+
+        let block = self.blocks.remove(idx).unwrap();
+        self.blocks.push_back(block);
+        self.blocks.back().map(|&(_, ref data)| data.as_slice())
+    }
+
+    pub fn insert(&mut self, index: u64, data: Vec<u8>) {
+        if let Some(idx) = self.blocks.iter().position(|&(i, _)| i == index) {
+            self.blocks.remove(idx);
+        }
+
+        while self.blocks.len() >= self.max_blocks {
+            self.blocks.pop_front();
+        }
+
+        self.blocks.push_back((index, data));
+    }
+
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+    }
+}
+
+// Reads whole blocks from position until stop, where position is updated
+// for every read so that it stays valid if reading fails in between
+pub fn read_blocks<R: Read>(
+    reader: &mut R,
+    position: &mut u64,
+    stop: u64,
+    block_size: u64,
+) -> io::Result<Vec<Vec<u8>>> {
+    let mut blocks = Vec::new();
+
+    while *position < stop {
+        let mut block = vec![0; cmp::min(block_size, stop - *position) as usize];
+        let mut filled = 0;
+
+        while filled < block.len() {
+            match reader.read(&mut block[filled..]) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "Connection closed before the end",
+                    ))
+                }
+                Ok(size) => {
+                    filled += size;
+                    *position += size as u64;
+                }
+                Err(ref err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        blocks.push(block);
+    }
+
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_block_cache() {
+        let mut cache = BlockCache::new(4, 2);
+
+        cache.insert(0, vec![0; 4]);
+        cache.insert(1, vec![1; 4]);
+        assert_eq!(cache.get(0), Some(&[0u8; 4][..]));
+
+        // Block 1 is the least recently used one now
+        cache.insert(2, vec![2; 4]);
+        assert!(cache.contains(0));
+        assert!(!cache.contains(1));
+        assert!(cache.contains(2));
+        assert_eq!(cache.get(1), None);
+
+        cache.clear();
+        assert!(!cache.contains(0));
+    }
+
+    #[test]
+    fn test_read_blocks() {
+        let data = (0..10).collect::<Vec<u8>>();
+
+        let mut cursor = Cursor::new(&data[4..]);
+        let mut position = 4;
+        let blocks = read_blocks(&mut cursor, &mut position, 10, 4).unwrap();
+        assert_eq!(blocks, vec![vec![4, 5, 6, 7], vec![8, 9]]);
+        assert_eq!(position, 10);
+
+        let mut cursor = Cursor::new(&data[..6]);
+        let mut position = 0;
+        let err = read_blocks(&mut cursor, &mut position, 8, 4).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        assert_eq!(position, 6);
+    }
+}
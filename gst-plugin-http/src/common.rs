@@ -0,0 +1,524 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{cmp, u32, u64};
+use std::time::Duration;
+
+use reqwest::{Client, Proxy, RedirectPolicy};
+use reqwest::header::{qitem, AcceptEncoding, Encoding, Headers, UserAgent};
+
+use gst_plugin::error::*;
+use gst_plugin::properties::*;
+use gst_plugin_simple::{Flushing, SettingsImpl};
+
+use glib;
+use gst;
+use gst::prelude::*;
+
+use proxy::ProxyConfig;
+use tls;
+
+pub const DEFAULT_RETRIES: u32 = 3;
+pub const DEFAULT_RETRY_DELAY: u64 = gst::SECOND;
+pub const DEFAULT_TIMEOUT: u32 = 15;
+
+const DEFAULT_COMPRESS: bool = false;
+const DEFAULT_SSL_STRICT: bool = true;
+const DEFAULT_MAX_REDIRECTS: u32 = 10;
+
+const MAX_RETRY_DELAY: u64 = 30 * gst::SECOND;
+
+// Settings that all HTTP elements have, see properties()
+#[derive(Debug, Clone)]
+pub struct RequestSettings {
+    default_user_agent: &'static str,
+    pub user_agent: String,
+    pub extra_headers: Option<gst::Structure>,
+    pub user_id: Option<String>,
+    pub user_pw: Option<String>,
+    pub retries: u32,
+    pub retry_delay: u64,
+    pub timeout: u32,
+}
+
+impl RequestSettings {
+    pub fn new(default_user_agent: &'static str) -> RequestSettings {
+        RequestSettings {
+            default_user_agent: default_user_agent,
+            user_agent: default_user_agent.into(),
+            extra_headers: None,
+            user_id: None,
+            user_pw: None,
+            retries: DEFAULT_RETRIES,
+            retry_delay: DEFAULT_RETRY_DELAY,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    // Sets the User-Agent and the extra headers, which replace any headers of
+    // the same name that were set before
+    pub fn set_headers(&self, cat: gst::DebugCategory, obj: &glib::Object, headers: &mut Headers) {
+        headers.set(UserAgent::new(self.user_agent.clone()));
+
+        if let Some(ref extra_headers) = self.extra_headers {
+            for (name, value) in extra_headers.iter() {
+                match value.get::<String>() {
+                    Some(value) => headers.set_raw(String::from(name), value),
+                    None => {
+                        gst_warning!(
+                            cat,
+                            obj: obj,
+                            "Ignoring non-string extra header {}: {:?}",
+                            name,
+                            value
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    // Exponential backoff before the given retry, starting at 1. Returns false
+    // if this was interrupted by flushing
+    pub fn wait_retry(
+        &self,
+        cat: gst::DebugCategory,
+        obj: &glib::Object,
+        flushing: &Flushing,
+        retry: u32,
+    ) -> bool {
+        let delay = cmp::min(
+            self.retry_delay.saturating_mul(1 << cmp::min(retry - 1, 32)),
+            MAX_RETRY_DELAY,
+        );
+
+        gst_debug!(
+            cat,
+            obj: obj,
+            "Retrying in {}ns (attempt {} of {})",
+            delay,
+            retry,
+            self.retries
+        );
+
+        if flushing.wait_timeout(Duration::new(
+            delay / gst::SECOND,
+            (delay % gst::SECOND) as u32,
+        )) {
+            gst_debug!(cat, obj: obj, "Flushing while waiting to retry");
+            return false;
+        }
+
+        true
+    }
+}
+
+impl SettingsImpl for RequestSettings {
+    fn set_property(&mut self, property: &Property, value: &glib::Value) -> bool {
+        match *property {
+            Property::String("user-agent", ..) => {
+                self.user_agent = value
+                    .get()
+                    .unwrap_or_else(|| self.default_user_agent.into());
+            }
+            Property::Boxed("extra-headers", ..) => {
+                self.extra_headers = value.get();
+            }
+            Property::String("user-id", ..) => {
+                self.user_id = value.get();
+            }
+            Property::String("user-pw", ..) => {
+                self.user_pw = value.get();
+            }
+            Property::UInt("retries", ..) => {
+                self.retries = value.get().unwrap();
+            }
+            Property::UInt64("retry-delay", ..) => {
+                self.retry_delay = value.get().unwrap();
+            }
+            Property::UInt("timeout", ..) => {
+                self.timeout = value.get().unwrap();
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn get_property(&self, property: &Property) -> Option<glib::Value> {
+        match *property {
+            Property::String("user-agent", ..) => Some(self.user_agent.to_value()),
+            Property::Boxed("extra-headers", ..) => Some(self.extra_headers.to_value()),
+            Property::String("user-id", ..) => Some(self.user_id.to_value()),
+            Property::String("user-pw", ..) => Some(self.user_pw.to_value()),
+            Property::UInt("retries", ..) => Some(self.retries.to_value()),
+            Property::UInt64("retry-delay", ..) => Some(self.retry_delay.to_value()),
+            Property::UInt("timeout", ..) => Some(self.timeout.to_value()),
+            _ => None,
+        }
+    }
+}
+
+// Properties of RequestSettings. What a retry is and what the timeout applies
+// to differs between the elements, so they pass the blurbs for these.
+// Boxed types are only known at runtime
+pub fn properties(
+    default_user_agent: &'static str,
+    retries_blurb: &'static str,
+    timeout_blurb: &'static str,
+) -> Vec<Property<'static>> {
+    vec![
+        Property::String(
+            "user-agent",
+            "User Agent",
+            "Value of the User-Agent HTTP request header",
+            Some(default_user_agent),
+            PropertyMutability::ReadWrite,
+        ),
+        Property::Boxed(
+            "extra-headers",
+            "Extra Headers",
+            "Extra HTTP request headers, with the field names as header names \
+             and string values",
+            gst::Structure::static_type(),
+            PropertyMutability::ReadWrite,
+        ),
+        Property::String(
+            "user-id",
+            "User ID",
+            "User name for authentication, instead of the one from the URI",
+            None,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::String(
+            "user-pw",
+            "User Password",
+            "Password for authentication, instead of the one from the URI",
+            None,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::UInt(
+            "retries",
+            "Retries",
+            retries_blurb,
+            (0, u32::MAX),
+            DEFAULT_RETRIES,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::UInt64(
+            "retry-delay",
+            "Retry Delay",
+            "Delay in nanoseconds before the first retry, doubled for every \
+             further attempt",
+            (0, u64::MAX),
+            DEFAULT_RETRY_DELAY,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::UInt(
+            "timeout",
+            "Timeout",
+            timeout_blurb,
+            (0, 3600),
+            DEFAULT_TIMEOUT,
+            PropertyMutability::ReadWrite,
+        ),
+    ]
+}
+
+// Settings of the HTTP client of the source elements, see client_properties()
+#[derive(Debug, Clone)]
+pub struct ClientSettings {
+    pub cookies: Option<String>,
+    pub proxy: Option<String>,
+    pub proxy_id: Option<String>,
+    pub proxy_pw: Option<String>,
+    pub compress: bool,
+    pub ssl_ca_file: Option<String>,
+    pub ssl_strict: bool,
+    pub ssl_client_cert_file: Option<String>,
+    pub ssl_client_cert_pw: Option<String>,
+    pub max_redirects: u32,
+}
+
+impl Default for ClientSettings {
+    fn default() -> Self {
+        ClientSettings {
+            cookies: None,
+            proxy: None,
+            proxy_id: None,
+            proxy_pw: None,
+            compress: DEFAULT_COMPRESS,
+            ssl_ca_file: None,
+            ssl_strict: DEFAULT_SSL_STRICT,
+            ssl_client_cert_file: None,
+            ssl_client_cert_pw: None,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+        }
+    }
+}
+
+impl ClientSettings {
+    // Sets the Accept-Encoding and Cookie headers
+    pub fn set_headers(&self, headers: &mut Headers) {
+        // Ranges of compressed responses are not known, so only ask for
+        // compression if seeking is not needed
+        headers.set(AcceptEncoding(if self.compress {
+            vec![qitem(Encoding::Gzip), qitem(Encoding::Deflate)]
+        } else {
+            vec![qitem(Encoding::Identity)]
+        }));
+
+        if let Some(ref cookies) = self.cookies {
+            headers.set_raw("Cookie", cookies.clone());
+        }
+    }
+
+    pub fn proxy_config(
+        &self,
+        cat: gst::DebugCategory,
+        obj: &glib::Object,
+    ) -> Result<ProxyConfig, ErrorMessage> {
+        let config = try!(
+            ProxyConfig::new(
+                self.proxy.as_ref().map(String::as_str),
+                self.proxy_id.as_ref().map(String::as_str),
+                self.proxy_pw.as_ref().map(String::as_str),
+            ).or_else(|err| {
+                gst_error!(cat, obj: obj, "Invalid proxy configuration: {}", err);
+                Err(error_msg!(
+                    gst::LibraryError::Settings,
+                    ["Invalid proxy configuration: {}", err]
+                ))
+            })
+        );
+
+        gst_debug!(cat, obj: obj, "Using proxy configuration {:?}", config);
+
+        Ok(config)
+    }
+
+    // Without follow_redirects, redirect responses are returned to the caller.
+    // The timeout only makes sure that abandoned requests don't keep their
+    // connection forever, the callers enforce their timeouts while waiting
+    pub fn create_client(
+        &self,
+        cat: gst::DebugCategory,
+        obj: &glib::Object,
+        config: ProxyConfig,
+        follow_redirects: bool,
+        timeout: Option<Duration>,
+    ) -> Result<Client, ErrorMessage> {
+        // Decompression is done by us as reqwest only supports gzip
+        let mut builder = Client::builder();
+        builder.gzip(false);
+        builder.redirect(if follow_redirects {
+            RedirectPolicy::limited(self.max_redirects as usize)
+        } else {
+            RedirectPolicy::none()
+        });
+        if let Some(timeout) = timeout {
+            builder.timeout(timeout);
+        }
+        if config.is_enabled() {
+            let (config, relay) = try!(config.with_connect_relay().or_else(|err| {
+                gst_error!(cat, obj: obj, "Failed to start proxy relay: {}", err);
+                Err(error_msg!(
+                    gst::LibraryError::Init,
+                    ["Failed to start proxy relay: {}", err.to_string()]
+                ))
+            }));
+
+            // The relay lives as long as the client that uses it
+            builder.proxy(Proxy::custom(move |url| {
+                let _ = &relay;
+                config.proxy_for_url(url)
+            }));
+        }
+
+        if let Some(ref path) = self.ssl_ca_file {
+            let certificates = try!(tls::load_certificates(path).or_else(|err| {
+                gst_error!(cat, obj: obj, "Failed to load CA file: {}", err);
+                Err(error_msg!(
+                    gst::ResourceError::OpenRead,
+                    ("Failed to load CA certificates from '{}'", path),
+                    ["{}", err]
+                ))
+            }));
+
+            gst_debug!(cat, obj: obj, "Adding {} CA certificates", certificates.len());
+            for certificate in certificates {
+                builder.add_root_certificate(certificate);
+            }
+        }
+
+        if let Some(ref path) = self.ssl_client_cert_file {
+            let password = self.ssl_client_cert_pw
+                .as_ref()
+                .map(String::as_str)
+                .unwrap_or("");
+            let identity = try!(tls::load_identity(path, password).or_else(|err| {
+                gst_error!(cat, obj: obj, "Failed to load client certificate: {}", err);
+                Err(error_msg!(
+                    gst::ResourceError::OpenRead,
+                    ("Failed to load client certificate from '{}'", path),
+                    ["{}", err]
+                ))
+            }));
+            builder.identity(identity);
+        }
+
+        if !self.ssl_strict {
+            gst_warning!(cat, obj: obj, "Server certificates are not verified");
+            builder.danger_disable_certificate_validation_entirely();
+        }
+
+        builder.build().or_else(|err| {
+            gst_error!(cat, obj: obj, "Failed to create HTTP client: {:?}", err);
+            Err(error_msg!(
+                gst::LibraryError::Init,
+                ["Failed to create HTTP client: {}", err.to_string()]
+            ))
+        })
+    }
+}
+
+impl SettingsImpl for ClientSettings {
+    fn set_property(&mut self, property: &Property, value: &glib::Value) -> bool {
+        match *property {
+            Property::String("cookies", ..) => {
+                self.cookies = value.get();
+            }
+            Property::String("proxy", ..) => {
+                self.proxy = value.get();
+            }
+            Property::String("proxy-id", ..) => {
+                self.proxy_id = value.get();
+            }
+            Property::String("proxy-pw", ..) => {
+                self.proxy_pw = value.get();
+            }
+            Property::Boolean("compress", ..) => {
+                self.compress = value.get().unwrap();
+            }
+            Property::String("ssl-ca-file", ..) => {
+                self.ssl_ca_file = value.get();
+            }
+            Property::Boolean("ssl-strict", ..) => {
+                self.ssl_strict = value.get().unwrap();
+            }
+            Property::String("ssl-client-cert-file", ..) => {
+                self.ssl_client_cert_file = value.get();
+            }
+            Property::String("ssl-client-cert-pw", ..) => {
+                self.ssl_client_cert_pw = value.get();
+            }
+            Property::UInt("max-redirects", ..) => {
+                self.max_redirects = value.get().unwrap();
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn get_property(&self, property: &Property) -> Option<glib::Value> {
+        match *property {
+            Property::String("cookies", ..) => Some(self.cookies.to_value()),
+            Property::String("proxy", ..) => Some(self.proxy.to_value()),
+            Property::String("proxy-id", ..) => Some(self.proxy_id.to_value()),
+            Property::String("proxy-pw", ..) => Some(self.proxy_pw.to_value()),
+            Property::Boolean("compress", ..) => Some(self.compress.to_value()),
+            Property::String("ssl-ca-file", ..) => Some(self.ssl_ca_file.to_value()),
+            Property::Boolean("ssl-strict", ..) => Some(self.ssl_strict.to_value()),
+            Property::String("ssl-client-cert-file", ..) => {
+                Some(self.ssl_client_cert_file.to_value())
+            }
+            Property::String("ssl-client-cert-pw", ..) => Some(self.ssl_client_cert_pw.to_value()),
+            Property::UInt("max-redirects", ..) => Some(self.max_redirects.to_value()),
+            _ => None,
+        }
+    }
+}
+
+// Properties of ClientSettings
+pub fn client_properties() -> Vec<Property<'static>> {
+    vec![
+        Property::String(
+            "cookies",
+            "Cookies",
+            "Value of the Cookie HTTP request header, e.g. 'name1=value1; name2=value2'",
+            None,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::String(
+            "proxy",
+            "Proxy",
+            "HTTP proxy server URI, e.g. http://proxy:3128 \
+             (default: from the http_proxy, https_proxy and no_proxy environment variables)",
+            None,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::String(
+            "proxy-id",
+            "Proxy User ID",
+            "User name for proxy authentication",
+            None,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::String(
+            "proxy-pw",
+            "Proxy Password",
+            "Password for proxy authentication",
+            None,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::Boolean(
+            "compress",
+            "Compress",
+            "Allow gzip and deflate compressed responses, which are not seekable",
+            DEFAULT_COMPRESS,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::String(
+            "ssl-ca-file",
+            "SSL CA File",
+            "File with additional trusted CA certificates, in PEM or DER format",
+            None,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::Boolean(
+            "ssl-strict",
+            "SSL Strict",
+            "Fail if the server certificate can't be verified",
+            DEFAULT_SSL_STRICT,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::String(
+            "ssl-client-cert-file",
+            "SSL Client Certificate File",
+            "PKCS #12 file with the client certificate and private key",
+            None,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::String(
+            "ssl-client-cert-pw",
+            "SSL Client Certificate Password",
+            "Password of the client certificate file",
+            None,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::UInt(
+            "max-redirects",
+            "Max Redirects",
+            "Maximum number of redirects to follow for a request",
+            (0, 100),
+            DEFAULT_MAX_REDIRECTS,
+            PropertyMutability::ReadWrite,
+        ),
+    ]
+}
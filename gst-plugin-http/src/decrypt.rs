@@ -0,0 +1,228 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// AES-128 decryption in CBC mode with PKCS#7 padding, as used for HLS
+// segments
+
+use std::cmp;
+use std::io::{self, ErrorKind, Read};
+
+use aes::Aes128;
+use block_modes::{BlockMode, Cbc};
+use block_modes::block_padding::NoPadding;
+use block_modes::cipher::generic_array::GenericArray;
+
+const BLOCK_SIZE: usize = 16;
+
+// The padding is removed by the reader, which only knows the last block once
+// the end of the data is reached
+type Aes128Cbc = Cbc<Aes128, NoPadding>;
+
+// Decrypts the data of the reader. The last block is only passed on once
+// the end of the data is reached, so that the padding can be removed
+pub struct DecryptReader<R: Read> {
+    reader: R,
+    cipher: Aes128Cbc,
+    encrypted: Vec<u8>,
+    decrypted: Vec<u8>,
+    offset: usize,
+    eos: bool,
+}
+
+impl<R: Read> DecryptReader<R> {
+    pub fn new(reader: R, key: &[u8; 16], iv: &[u8; 16]) -> DecryptReader<R> {
+        DecryptReader {
+            reader: reader,
+            cipher: Aes128Cbc::new_fix(GenericArray::from_slice(key), GenericArray::from_slice(iv)),
+            encrypted: Vec::new(),
+            decrypted: Vec::new(),
+            offset: 0,
+            eos: false,
+        }
+    }
+
+    fn decrypt_blocks(&mut self) {
+        let len = self.encrypted.len() - self.encrypted.len() % BLOCK_SIZE;
+
+        let mut blocks = self.encrypted[..len]
+            .chunks(BLOCK_SIZE)
+            .map(GenericArray::clone_from_slice)
+            .collect::<Vec<_>>();
+        self.cipher.decrypt_blocks(&mut blocks);
+
+        for block in &blocks {
+            self.decrypted.extend_from_slice(block);
+        }
+
+        self.encrypted.drain(..len);
+    }
+
+    fn remove_padding(&mut self) -> io::Result<()> {
+        if !self.encrypted.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Encrypted data is not a multiple of the block size",
+            ));
+        }
+
+        let padding = self.decrypted.last().map_or(0, |&padding| padding as usize);
+        if padding == 0 || padding > BLOCK_SIZE || padding > self.decrypted.len()
+            || self.decrypted[(self.decrypted.len() - padding)..]
+                .iter()
+                .any(|&b| b as usize != padding)
+        {
+            return Err(io::Error::new(ErrorKind::InvalidData, "Invalid padding"));
+        }
+
+        let len = self.decrypted.len() - padding;
+        self.decrypted.truncate(len);
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecryptReader<R> {
+    fn read(&mut self, data: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let available = if self.eos {
+                self.decrypted.len()
+            } else {
+                self.decrypted.len().saturating_sub(BLOCK_SIZE)
+            };
+
+            if self.offset < available {
+                let size = cmp::min(data.len(), available - self.offset);
+                data[..size].copy_from_slice(&self.decrypted[self.offset..(self.offset + size)]);
+                self.offset += size;
+                return Ok(size);
+            }
+
+            if self.eos {
+                return Ok(0);
+            }
+
+            self.decrypted.drain(..self.offset);
+            self.offset = 0;
+
+            let mut buf = [0u8; 4096];
+            let size = try!(self.reader.read(&mut buf));
+            if size == 0 {
+                try!(self.remove_padding());
+                self.eos = true;
+                continue;
+            }
+
+            self.encrypted.extend_from_slice(&buf[..size]);
+            self.decrypt_blocks();
+        }
+    }
+}
+
+// Encryption is only needed for creating test data
+#[cfg(test)]
+pub fn encrypt_cbc(key: &[u8; 16], iv: &[u8; 16], data: &[u8]) -> Vec<u8> {
+    use block_modes::block_padding::Pkcs7;
+
+    Cbc::<Aes128, Pkcs7>::new_fix(GenericArray::from_slice(key), GenericArray::from_slice(iv))
+        .encrypt_vec(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn from_hex(s: &str) -> Vec<u8> {
+        (0..(s.len() / 2))
+            .map(|i| u8::from_str_radix(&s[(2 * i)..(2 * i + 2)], 16).unwrap())
+            .collect()
+    }
+
+    fn array(data: &[u8]) -> [u8; 16] {
+        let mut array = [0u8; 16];
+        array.copy_from_slice(data);
+        array
+    }
+
+    fn read_all<R: Read>(reader: &mut R, chunk_size: usize) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut buf = vec![0u8; chunk_size];
+
+        loop {
+            let size = try!(reader.read(&mut buf));
+            if size == 0 {
+                return Ok(data);
+            }
+            data.extend_from_slice(&buf[..size]);
+        }
+    }
+
+    // NIST SP 800-38A F.2.2, followed by a block that only contains padding
+    #[test]
+    fn test_cbc() {
+        let key = array(&from_hex("2b7e151628aed2a6abf7158809cf4f3c"));
+        let iv = array(&from_hex("000102030405060708090a0b0c0d0e0f"));
+        let plaintext = from_hex(
+            "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+             30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710",
+        );
+        let encrypted = from_hex(
+            "7649abac8119b246cee98e9b12e9197d5086cb9b507219ee95db113a917678b2\
+             73bed6b8e3c1743b7116e69e222295163ff1caa1681fac09120eca307586e1a7",
+        );
+
+        let padded = encrypt_cbc(&key, &iv, &plaintext);
+        assert_eq!(&padded[..64], &encrypted[..]);
+        assert_eq!(padded.len(), 80);
+
+        for &chunk_size in &[1, 7, 16, 4096] {
+            let mut reader = DecryptReader::new(Cursor::new(padded.clone()), &key, &iv);
+            assert_eq!(read_all(&mut reader, chunk_size).unwrap(), plaintext);
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let key = [0x2a; 16];
+        let iv = [0x17; 16];
+
+        for len in 0..50 {
+            let data = (0..len).map(|i| i as u8).collect::<Vec<_>>();
+            let encrypted = encrypt_cbc(&key, &iv, &data);
+            let mut reader = DecryptReader::new(Cursor::new(encrypted), &key, &iv);
+            assert_eq!(read_all(&mut reader, 5).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_invalid_data() {
+        let key = [0u8; 16];
+        let iv = [0u8; 16];
+
+        // Not a multiple of the block size
+        let mut reader = DecryptReader::new(Cursor::new(vec![0u8; 20]), &key, &iv);
+        assert_eq!(
+            read_all(&mut reader, 4096).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+
+        // Wrong key
+        let encrypted = encrypt_cbc(&[1; 16], &iv, b"HLS segment");
+        let mut reader = DecryptReader::new(Cursor::new(encrypted), &key, &iv);
+        assert_eq!(
+            read_all(&mut reader, 4096).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+
+        let mut reader = DecryptReader::new(Cursor::new(Vec::new()), &key, &iv);
+        assert_eq!(
+            read_all(&mut reader, 4096).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::{self, BufRead, BufReader, Read};
+
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use reqwest::header::Encoding;
+
+pub fn is_compressed(encodings: &[Encoding]) -> bool {
+    encodings.iter().any(|encoding| *encoding != Encoding::Identity)
+}
+
+// Content encodings are undone in the reverse order they were applied in
+pub fn decoder(
+    reader: Box<Read + Send>,
+    encodings: &[Encoding],
+) -> Result<Box<Read + Send>, String> {
+    let mut reader = reader;
+
+    for encoding in encodings.iter().rev() {
+        reader = match *encoding {
+            Encoding::Identity => reader,
+            Encoding::Gzip => Box::new(GzDecoder::new(reader)),
+            Encoding::Deflate => Box::new(DeflateReader {
+                reader: Some(BufReader::new(reader)),
+                decoder: None,
+            }),
+            ref encoding => return Err(format!("Unsupported content encoding '{}'", encoding)),
+        };
+    }
+
+    Ok(reader)
+}
+
+// "deflate" is supposed to be zlib data, but some servers send raw deflate
+// data instead. Which one it is is decided on the first read so that this
+// does not block
+struct DeflateReader<R: BufRead + Send + 'static> {
+    reader: Option<R>,
+    decoder: Option<Box<Read + Send>>,
+}
+
+impl<R: BufRead + Send + 'static> Read for DeflateReader<R> {
+    fn read(&mut self, data: &mut [u8]) -> io::Result<usize> {
+        if let Some(mut reader) = self.reader.take() {
+            let zlib = match reader.fill_buf() {
+                Ok(header) => is_zlib_header(header),
+                Err(err) => {
+                    self.reader = Some(reader);
+                    return Err(err);
+                }
+            };
+
+            self.decoder = Some(if zlib {
+                Box::new(ZlibDecoder::new(reader))
+            } else {
+                Box::new(DeflateDecoder::new(reader))
+            });
+        }
+
+        self.decoder.as_mut().unwrap().read(data)
+    }
+}
+
+// Compression method 8, a window size of at most 32kB and the check bits that
+// make the first two bytes a multiple of 31
+fn is_zlib_header(header: &[u8]) -> bool {
+    if header.is_empty() {
+        return true;
+    }
+
+    let cmf = header[0];
+    if cmf & 0x0f != 8 || cmf >> 4 > 7 {
+        return false;
+    }
+
+    header.len() < 2 || (u16::from(cmf) * 256 + u16::from(header[1])) % 31 == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use flate2::Compression;
+    use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+
+    const DATA: &[u8] = b"#EXTM3U\n#EXT-X-TARGETDURATION:10\n#EXTINF:10,\nsegment0.ts\n";
+
+    fn decode(data: Vec<u8>, encodings: &[Encoding]) -> Vec<u8> {
+        let mut reader = decoder(Box::new(Cursor::new(data)), encodings).unwrap();
+        let mut decoded = Vec::new();
+        reader.read_to_end(&mut decoded).unwrap();
+        decoded
+    }
+
+    #[test]
+    fn test_decoder() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(DATA).unwrap();
+        assert_eq!(decode(encoder.finish().unwrap(), &[Encoding::Gzip]), DATA);
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(DATA).unwrap();
+        assert_eq!(decode(encoder.finish().unwrap(), &[Encoding::Deflate]), DATA);
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(DATA).unwrap();
+        assert_eq!(decode(encoder.finish().unwrap(), &[Encoding::Deflate]), DATA);
+
+        assert_eq!(decode(DATA.to_vec(), &[Encoding::Identity]), DATA);
+        assert!(!is_compressed(&[Encoding::Identity]));
+        assert!(is_compressed(&[Encoding::Gzip]));
+    }
+
+    // A non-final stored block with a padding bit set looks like a zlib
+    // header, except for the check bits
+    #[test]
+    fn test_raw_deflate() {
+        let len = DATA.len() as u8;
+        let mut data = vec![0x08, len, 0x00, !len, 0xff];
+        data.extend_from_slice(DATA);
+        data.extend_from_slice(&[0x01, 0x00, 0x00, 0xff, 0xff]);
+
+        assert!(!is_zlib_header(&data));
+        assert!(is_zlib_header(&[0x78, 0x9c]));
+        assert_eq!(decode(data, &[Encoding::Deflate]), DATA);
+    }
+
+    #[test]
+    fn test_unsupported_encoding() {
+        assert!(decoder(Box::new(Cursor::new(Vec::new())), &[Encoding::Brotli]).is_err());
+    }
+}
@@ -0,0 +1,893 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{cmp, fmt};
+use std::io::{self, ErrorKind, Read};
+use std::time::{Duration, Instant};
+use url::{Position, Url};
+use reqwest::{self, Client, Response, StatusCode};
+use reqwest::header::{qitem, AcceptEncoding, ByteRangeSpec, ContentEncoding, Encoding, Headers,
+                      Range};
+
+use gst_plugin_simple::Flushing;
+
+use auth::{self, Challenge};
+use body::BodyReader;
+use decrypt::DecryptReader;
+use encoding;
+use m3u8::{self, ByteRange, MediaPlaylist, Playlist, Segment, Variant};
+use proxy::ProxyConfig;
+use request::{self, SendError};
+
+// Live streams start this many segments before the end of the playlist
+const LIVE_START_SEGMENTS: usize = 3;
+
+// Variants are selected with some headroom to the measured bitrate
+const BANDWIDTH_FACTOR_PERCENT: u64 = 80;
+
+const MAX_PLAYLIST_SIZE: u64 = 4 * 1024 * 1024;
+
+// Live playlists are not reloaded more often than this, e.g. if their target
+// duration is 0
+const MIN_RELOAD_INTERVAL_MS: u64 = 500;
+
+#[derive(Debug)]
+pub enum Error {
+    Request(Url, reqwest::Error),
+    TimedOut(Url),
+    Status(Url, StatusCode),
+    Read(Url, io::Error),
+    Playlist(Url, String),
+    Key(Url, String),
+    Decrypt(Url, io::Error),
+    Flushing,
+}
+
+// A segment whose data is read while it is downloaded
+pub struct Fragment {
+    pub uri: Url,
+    pub sequence: u64,
+    pub duration: Duration,
+    // The previous fragment was not directly followed by this one, e.g.
+    // because of an EXT-X-DISCONTINUITY, a variant switch or because
+    // segments were missed in a live stream
+    pub discontinuity: bool,
+    encrypted: bool,
+    reader: Box<Read + Send>,
+    // Only the time spent waiting for data counts for the bitrate, not the
+    // time in which the data is processed downstream
+    size: u64,
+    download_time: Duration,
+}
+
+impl Fragment {
+    // Reads the decrypted data. Returns 0 once the segment was read completely
+    pub fn read(&mut self, data: &mut [u8]) -> Result<usize, Error> {
+        let start = Instant::now();
+        let res = self.reader.read(data);
+        self.download_time += start.elapsed();
+
+        match res {
+            Ok(size) => {
+                self.size += size as u64;
+                Ok(size)
+            }
+            Err(ref err) if err.kind() == ErrorKind::TimedOut => {
+                Err(Error::TimedOut(self.uri.clone()))
+            }
+            // Decrypting fails for data that was modified or truncated
+            Err(err) => if self.encrypted && err.kind() == ErrorKind::InvalidData {
+                Err(Error::Decrypt(self.uri.clone(), err))
+            } else {
+                Err(Error::Read(self.uri.clone(), err))
+            },
+        }
+    }
+
+    // Drops the given number of bytes, e.g. the ones that were already read
+    // before the segment had to be requested again
+    pub fn skip(&mut self, size: u64) -> Result<(), Error> {
+        let mut scratch = vec![0; 64 * 1024];
+        let mut skipped = 0;
+
+        while skipped < size {
+            let len = cmp::min(scratch.len() as u64, size - skipped) as usize;
+            match try!(self.read(&mut scratch[..len])) {
+                0 => break,
+                len => skipped += len as u64,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Fragment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Fragment")
+            .field("uri", &self.uri)
+            .field("sequence", &self.sequence)
+            .field("duration", &self.duration)
+            .field("discontinuity", &self.discontinuity)
+            .field("encrypted", &self.encrypted)
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+// Configuration for the requests, which are done the same way as in rshttpsrc
+#[derive(Debug, Clone)]
+pub struct RequestConfig {
+    pub headers: Headers,
+    pub proxy: ProxyConfig,
+    pub user_id: Option<String>,
+    pub user_pw: Option<String>,
+    pub timeout: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub struct Hls {
+    client: Client,
+    config: RequestConfig,
+    flushing: Flushing,
+    // Credentials are only sent to the host of the master playlist
+    credentials: Option<(String, String)>,
+    origin: Option<String>,
+    auth: Option<Challenge>,
+    max_bitrate: u64,
+    bitrate: Option<u64>,
+    variants: Vec<Variant>,
+    variant: Option<usize>,
+    playlist_uri: Url,
+    playlist: MediaPlaylist,
+    reloaded: Instant,
+    playlist_changed: bool,
+    next_sequence: u64,
+    discontinuity: bool,
+    key: Option<(Url, [u8; 16])>,
+}
+
+impl Hls {
+    // Loads the playlist and for master playlists the media playlist of the
+    // initial variant, which is the first one unless limited by max_bitrate
+    pub fn new(
+        client: Client,
+        config: RequestConfig,
+        flushing: Flushing,
+        uri: &Url,
+        max_bitrate: u64,
+    ) -> Result<Hls, Error> {
+        let credentials = auth::credentials(
+            uri,
+            config.user_id.as_ref().map(String::as_str),
+            config.user_pw.as_ref().map(String::as_str),
+        );
+
+        let mut uri = uri.clone();
+        let _ = uri.set_username("");
+        let _ = uri.set_password(None);
+
+        let mut hls = Hls {
+            client: client,
+            config: config,
+            flushing: flushing,
+            credentials: credentials,
+            origin: uri.host_str().map(String::from),
+            auth: None,
+            max_bitrate: max_bitrate,
+            bitrate: None,
+            variants: Vec::new(),
+            variant: None,
+            playlist_uri: uri.clone(),
+            playlist: MediaPlaylist {
+                target_duration: Duration::from_secs(0),
+                media_sequence: 0,
+                discontinuity_sequence: 0,
+                segments: Vec::new(),
+                end_list: false,
+            },
+            reloaded: Instant::now(),
+            playlist_changed: true,
+            next_sequence: 0,
+            discontinuity: false,
+            key: None,
+        };
+
+        let (playlist_uri, playlist) = match try!(hls.fetch_playlist(&uri)) {
+            (playlist_uri, Playlist::Media(playlist)) => (playlist_uri, playlist),
+            (_, Playlist::Master(master)) => {
+                let variant = if max_bitrate != 0 {
+                    m3u8::select_variant(&master.variants, max_bitrate).unwrap()
+                } else {
+                    0
+                };
+
+                hls.variants = master.variants;
+                hls.variant = Some(variant);
+                let variant_uri = hls.variants[variant].uri.clone();
+                try!(hls.fetch_media_playlist(&variant_uri))
+            }
+        };
+
+        hls.next_sequence = if playlist.end_list {
+            playlist.media_sequence
+        } else {
+            let start = playlist.segments.len().saturating_sub(LIVE_START_SEGMENTS);
+            playlist.media_sequence + start as u64
+        };
+        hls.playlist_uri = playlist_uri;
+        hls.playlist = playlist;
+
+        Ok(hls)
+    }
+
+    pub fn variant(&self) -> Option<&Variant> {
+        self.variant.map(|variant| &self.variants[variant])
+    }
+
+    pub fn is_live(&self) -> bool {
+        !self.playlist.end_list
+    }
+
+    // Smoothed bitrate of the segment downloads in bits per second
+    pub fn bitrate(&self) -> Option<u64> {
+        self.bitrate
+    }
+
+    fn get(&mut self, uri: &Url, range: Option<&ByteRange>) -> Result<Response, Error> {
+        let mut uri = uri.clone();
+        let _ = uri.set_username("");
+        let _ = uri.set_password(None);
+
+        let credentials = if uri.host_str().map(String::from) == self.origin {
+            self.credentials.clone()
+        } else {
+            None
+        };
+
+        // Retry once with the new challenge, e.g. for the first request or if
+        // the Digest nonce is stale
+        let mut authenticated = false;
+        loop {
+            let mut req = self.client.get(uri.clone());
+
+            let mut headers = self.config.headers.clone();
+            if let Some(authorization) = self.config.proxy.authorization_for_url(&uri) {
+                headers.set_raw("Proxy-Authorization", authorization);
+            }
+            if let (Some(auth), Some(&(ref user, ref password))) =
+                (self.auth.as_mut(), credentials.as_ref())
+            {
+                let target = &uri[Position::BeforePath..];
+                headers.set_raw(
+                    "Authorization",
+                    auth.authorization(user, password, "GET", target),
+                );
+            }
+            req.headers(headers);
+
+            // Ranges would be of the compressed data
+            if let Some(range) = range {
+                let end = range.offset + range.length - 1;
+                req.header(Range::Bytes(vec![ByteRangeSpec::FromTo(range.offset, end)]));
+                req.header(AcceptEncoding(vec![qitem(Encoding::Identity)]));
+            }
+
+            let response = try!(
+                request::send(req, &self.flushing, self.config.timeout).map_err(|err| match err {
+                    SendError::Flushing => Error::Flushing,
+                    SendError::TimedOut => Error::TimedOut(uri.clone()),
+                    SendError::Thread(err) => Error::Read(uri.clone(), err),
+                    err @ SendError::TooManyRequests => Error::Read(
+                        uri.clone(),
+                        io::Error::new(io::ErrorKind::Other, err.to_string()),
+                    ),
+                    SendError::Request(err) => Error::Request(uri.clone(), err),
+                    SendError::Read(err) => Error::Read(uri.clone(), err),
+                })
+            );
+
+            if response.status() == StatusCode::Unauthorized && credentials.is_some()
+                && !authenticated
+            {
+                if let Some(challenge) = auth::response_challenge(&response) {
+                    self.auth = Some(challenge);
+                    authenticated = true;
+                    continue;
+                }
+            }
+
+            if !response.status().is_success() {
+                return Err(Error::Status(uri, response.status()));
+            }
+
+            return Ok(response);
+        }
+    }
+
+    // Reads the decompressed body in a separate thread, limited to the range
+    fn body(
+        &self,
+        uri: &Url,
+        response: Response,
+        range: Option<&ByteRange>,
+    ) -> Result<Box<Read + Send>, Error> {
+        // Servers ignoring the Range header reply with the whole resource
+        let skip = match range {
+            Some(range) if response.status() != StatusCode::PartialContent => range.offset,
+            _ => 0,
+        };
+
+        let encodings = response
+            .headers()
+            .get()
+            .map(|&ContentEncoding(ref encodings)| encodings.clone())
+            .unwrap_or_default();
+        let reader = try!(encoding::decoder(Box::new(response), &encodings).map_err(|err| {
+            Error::Read(uri.clone(), io::Error::new(ErrorKind::InvalidData, err))
+        }));
+
+        let mut body = try!(
+            BodyReader::new(reader, self.flushing.clone(), self.config.timeout)
+                .map_err(|err| Error::Read(uri.clone(), err))
+        );
+
+        try!(
+            io::copy(&mut body.by_ref().take(skip), &mut io::sink())
+                .map_err(|err| Error::Read(uri.clone(), err))
+        );
+
+        Ok(match range {
+            Some(range) => Box::new(body.take(range.length)),
+            None => Box::new(body),
+        })
+    }
+
+    fn download(&mut self, uri: &Url, limit: u64) -> Result<Vec<u8>, Error> {
+        let response = try!(self.get(uri, None));
+        let body = try!(self.body(uri, response, None));
+
+        let mut data = Vec::new();
+        try!(
+            body.take(limit)
+                .read_to_end(&mut data)
+                .map_err(|err| Error::Read(uri.clone(), err))
+        );
+
+        Ok(data)
+    }
+
+    // Relative URIs in the playlist are relative to the final URI after
+    // redirects
+    fn fetch_playlist(&mut self, uri: &Url) -> Result<(Url, Playlist), Error> {
+        let response = try!(self.get(uri, None));
+        let final_uri = response.url().clone();
+        let body = try!(self.body(uri, response, None));
+
+        let mut data = String::new();
+        try!(
+            body.take(MAX_PLAYLIST_SIZE)
+                .read_to_string(&mut data)
+                .map_err(|err| Error::Read(uri.clone(), err))
+        );
+
+        let playlist = try!(
+            m3u8::parse(&data, &final_uri).map_err(|err| Error::Playlist(uri.clone(), err))
+        );
+
+        self.reloaded = Instant::now();
+
+        Ok((final_uri, playlist))
+    }
+
+    fn fetch_media_playlist(&mut self, uri: &Url) -> Result<(Url, MediaPlaylist), Error> {
+        match try!(self.fetch_playlist(uri)) {
+            (final_uri, Playlist::Media(playlist)) => Ok((final_uri, playlist)),
+            (_, Playlist::Master(_)) => Err(Error::Playlist(
+                uri.clone(),
+                "Expected a media playlist".into(),
+            )),
+        }
+    }
+
+    fn reload(&mut self) -> Result<(), Error> {
+        let uri = self.playlist_uri.clone();
+        let (_, playlist) = try!(self.fetch_media_playlist(&uri));
+
+        // Servers restarting the stream start with a lower sequence number
+        if playlist.end_sequence() < self.playlist.media_sequence {
+            self.next_sequence = playlist.media_sequence;
+            self.discontinuity = true;
+        }
+
+        self.playlist_changed = playlist != self.playlist;
+        self.playlist = playlist;
+
+        Ok(())
+    }
+
+    fn fetch_key(&mut self, uri: &Url) -> Result<[u8; 16], Error> {
+        if let Some((ref key_uri, key)) = self.key {
+            if key_uri == uri {
+                return Ok(key);
+            }
+        }
+
+        let data = match self.download(uri, 17) {
+            Ok(data) => data,
+            Err(Error::Status(_, status)) => {
+                return Err(Error::Key(uri.clone(), format!("HTTP status code {}", status)));
+            }
+            Err(err) => return Err(err),
+        };
+
+        if data.len() != 16 {
+            return Err(Error::Key(uri.clone(), format!("Invalid key size {}", data.len())));
+        }
+
+        let mut key = [0u8; 16];
+        key.copy_from_slice(&data);
+        self.key = Some((uri.clone(), key));
+
+        Ok(key)
+    }
+
+    fn open_segment(&mut self, segment: &Segment) -> Result<Box<Read + Send>, Error> {
+        let key = match segment.key {
+            None => None,
+            Some(ref key) => Some((try!(self.fetch_key(&key.uri)), key.iv)),
+        };
+
+        let response = try!(self.get(&segment.uri, segment.byte_range.as_ref()));
+        let body = try!(self.body(&segment.uri, response, segment.byte_range.as_ref()));
+
+        Ok(match key {
+            None => body,
+            Some((secret, iv)) => Box::new(DecryptReader::new(body, &secret, &iv)),
+        })
+    }
+
+    // If the playlist did not change the last time, this is half the target
+    // duration as per the spec
+    fn reload_interval(&self) -> Duration {
+        let interval = if self.playlist_changed {
+            self.playlist.target_duration
+        } else {
+            self.playlist.target_duration / 2
+        };
+
+        cmp::max(interval, Duration::from_millis(MIN_RELOAD_INTERVAL_MS))
+    }
+
+    // Waits until the playlist is due to be reloaded
+    fn wait_reload(&self) -> Result<(), Error> {
+        let interval = self.reload_interval();
+
+        let elapsed = self.reloaded.elapsed();
+        if elapsed < interval && self.flushing.wait_timeout(interval - elapsed) {
+            return Err(Error::Flushing);
+        }
+
+        Ok(())
+    }
+
+    // Requests the next segment, reloading live playlists as needed. Returns
+    // None once the end of the playlist is reached. The same segment is
+    // requested again until finish_fragment() was called for it
+    pub fn next_fragment(&mut self) -> Result<Option<Fragment>, Error> {
+        loop {
+            // Segments were removed from the live playlist before we got them
+            if self.next_sequence < self.playlist.media_sequence {
+                self.next_sequence = self.playlist.media_sequence;
+                self.discontinuity = true;
+            }
+
+            if let Some(segment) = self.playlist.segment(self.next_sequence).cloned() {
+                let start = Instant::now();
+                let reader = try!(self.open_segment(&segment));
+
+                return Ok(Some(Fragment {
+                    uri: segment.uri,
+                    sequence: segment.sequence,
+                    duration: segment.duration,
+                    discontinuity: segment.discontinuity || self.discontinuity,
+                    encrypted: segment.key.is_some(),
+                    reader: reader,
+                    size: 0,
+                    download_time: start.elapsed(),
+                }));
+            }
+
+            if self.playlist.end_list {
+                return Ok(None);
+            }
+
+            try!(self.wait_reload());
+            try!(self.reload());
+        }
+    }
+
+    // Continues with the segment after the fragment, which was read
+    // completely, and updates the measured bitrate
+    pub fn finish_fragment(&mut self, fragment: &Fragment) {
+        self.next_sequence = fragment.sequence + 1;
+        self.discontinuity = false;
+        self.update_bitrate(fragment.size, fragment.download_time);
+    }
+
+    fn update_bitrate(&mut self, bytes: u64, elapsed: Duration) {
+        let nsecs = elapsed.as_secs() * 1_000_000_000 + u64::from(elapsed.subsec_nanos());
+        let bitrate = bytes
+            .saturating_mul(8_000_000_000)
+            .checked_div(cmp::max(nsecs, 1))
+            .unwrap_or(0);
+
+        self.bitrate = Some(match self.bitrate {
+            Some(previous) => (previous * 3 + bitrate) / 4,
+            None => bitrate,
+        });
+    }
+
+    // Switches to the variant that fits the measured bitrate best. Returns if
+    // the variant was changed
+    pub fn switch_variant(&mut self) -> Result<bool, Error> {
+        let bitrate = match self.bitrate {
+            Some(bitrate) if self.variants.len() > 1 => bitrate,
+            _ => return Ok(false),
+        };
+
+        let mut limit = cmp::max(bitrate / 100 * BANDWIDTH_FACTOR_PERCENT, 1);
+        if self.max_bitrate != 0 {
+            limit = cmp::min(limit, self.max_bitrate);
+        }
+
+        let variant = m3u8::select_variant(&self.variants, limit);
+        if variant == self.variant {
+            return Ok(false);
+        }
+
+        let variant = variant.unwrap();
+        let uri = self.variants[variant].uri.clone();
+        let (playlist_uri, playlist) = try!(self.fetch_media_playlist(&uri));
+
+        // Segments of all variants are aligned by their sequence numbers
+        self.variant = Some(variant);
+        self.playlist_uri = playlist_uri;
+        self.playlist_changed = true;
+        self.playlist = playlist;
+        self.discontinuity = true;
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::Write;
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use decrypt;
+
+    type Files = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+    fn handle_connection(mut stream: TcpStream, files: Files) {
+        let mut head = Vec::new();
+        let mut buf = [0u8; 1];
+
+        loop {
+            // Byte by byte to not read into the next request
+            match stream.read(&mut buf) {
+                Ok(1) => head.push(buf[0]),
+                _ => return,
+            }
+            if !head.ends_with(b"\r\n\r\n") {
+                continue;
+            }
+
+            let path = String::from_utf8(head.clone())
+                .unwrap()
+                .split(' ')
+                .nth(1)
+                .unwrap()
+                .to_string();
+            head.clear();
+
+            let response = match files.lock().unwrap().get(&path) {
+                Some(data) => {
+                    let mut response =
+                        format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", data.len())
+                            .into_bytes();
+                    response.extend_from_slice(data);
+                    response
+                }
+                None => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_vec(),
+            };
+
+            if stream.write_all(&response).is_err() {
+                return;
+            }
+        }
+    }
+
+    // Minimal HTTP server for the given paths
+    fn serve(files: Files) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+
+        thread::spawn(move || for stream in listener.incoming() {
+            let files = files.clone();
+            thread::spawn(move || handle_connection(stream.unwrap(), files));
+        });
+
+        uri
+    }
+
+    fn files(files: &[(&str, &[u8])]) -> Files {
+        Arc::new(Mutex::new(
+            files
+                .iter()
+                .map(|&(path, data)| (String::from(path), data.to_vec()))
+                .collect(),
+        ))
+    }
+
+    fn config() -> RequestConfig {
+        RequestConfig {
+            headers: Headers::new(),
+            proxy: ProxyConfig::default(),
+            user_id: None,
+            user_pw: None,
+            timeout: Some(Duration::from_secs(5)),
+        }
+    }
+
+    fn next(hls: &mut Hls) -> Option<(u64, bool, Vec<u8>)> {
+        let mut fragment = match hls.next_fragment().unwrap() {
+            Some(fragment) => fragment,
+            None => return None,
+        };
+
+        let mut data = Vec::new();
+        let mut buf = [0u8; 7];
+        loop {
+            match fragment.read(&mut buf).unwrap() {
+                0 => break,
+                size => data.extend_from_slice(&buf[..size]),
+            }
+        }
+        hls.finish_fragment(&fragment);
+
+        Some((fragment.sequence, fragment.discontinuity, data))
+    }
+
+    #[test]
+    fn test_vod() {
+        let key = [0x42; 16];
+        let iv = [0x17; 16];
+        let encrypted = decrypt::encrypt_cbc(&key, &iv, b"second segment");
+
+        let files = files(&[
+            (
+                "/master.m3u8",
+                b"#EXTM3U\n\
+                  #EXT-X-STREAM-INF:BANDWIDTH=5000000\n\
+                  high/index.m3u8\n\
+                  #EXT-X-STREAM-INF:BANDWIDTH=100000\n\
+                  low/index.m3u8\n",
+            ),
+            (
+                "/low/index.m3u8",
+                b"#EXTM3U\n\
+                  #EXT-X-TARGETDURATION:2\n\
+                  #EXTINF:2,\n\
+                  0.ts\n\
+                  #EXT-X-KEY:METHOD=AES-128,URI=\"/key\",IV=0x17171717171717171717171717171717\n\
+                  #EXTINF:2,\n\
+                  1.ts\n\
+                  #EXT-X-KEY:METHOD=NONE\n\
+                  #EXT-X-DISCONTINUITY\n\
+                  #EXTINF:2,\n\
+                  2.ts\n\
+                  #EXT-X-ENDLIST\n",
+            ),
+            ("/low/0.ts", b"first segment"),
+            ("/low/1.ts", &encrypted),
+            ("/low/2.ts", b"third segment"),
+            ("/key", &key),
+        ]);
+        let uri = serve(files).join("master.m3u8").unwrap();
+
+        let mut hls =
+            Hls::new(Client::new(), config(), Flushing::new(), &uri, 1_000_000).unwrap();
+        assert_eq!(hls.variant().unwrap().bandwidth, 100_000);
+        assert!(!hls.is_live());
+
+        assert_eq!(next(&mut hls), Some((0, false, b"first segment".to_vec())));
+        assert!(hls.bitrate().is_some());
+
+        // Segments that were not read completely are requested again
+        let fragment = hls.next_fragment().unwrap().unwrap();
+        assert_eq!(fragment.sequence, 1);
+        drop(fragment);
+        assert_eq!(next(&mut hls), Some((1, false, b"second segment".to_vec())));
+        assert_eq!(next(&mut hls), Some((2, true, b"third segment".to_vec())));
+        assert_eq!(next(&mut hls), None);
+
+        // The high variant is above max-bitrate
+        assert!(!hls.switch_variant().unwrap());
+    }
+
+    #[test]
+    fn test_switch_variant() {
+        let playlist = b"#EXTM3U\n\
+                         #EXT-X-TARGETDURATION:2\n\
+                         #EXTINF:2,\n\
+                         0.ts\n\
+                         #EXTINF:2,\n\
+                         1.ts\n\
+                         #EXTINF:2,\n\
+                         2.ts\n\
+                         #EXT-X-ENDLIST\n";
+        // Big enough that even a slow download measures far more than the
+        // bandwidth of the high variant
+        let segment = vec![0x47; 64 * 1024];
+
+        let files = files(&[
+            (
+                "/master.m3u8",
+                b"#EXTM3U\n\
+                  #EXT-X-STREAM-INF:BANDWIDTH=1000\n\
+                  low/index.m3u8\n\
+                  #EXT-X-STREAM-INF:BANDWIDTH=10000\n\
+                  high/index.m3u8\n",
+            ),
+            ("/low/index.m3u8", playlist),
+            ("/high/index.m3u8", playlist),
+            ("/low/0.ts", &segment),
+            ("/low/1.ts", b"low 1"),
+            ("/high/1.ts", b"high 1"),
+            ("/high/2.ts", b"high 2"),
+        ]);
+        let uri = serve(files).join("master.m3u8").unwrap();
+
+        // Without max-bitrate the first variant is used until there is a
+        // measured bitrate
+        let mut hls = Hls::new(Client::new(), config(), Flushing::new(), &uri, 0).unwrap();
+        assert_eq!(hls.variant().unwrap().bandwidth, 1000);
+        assert!(!hls.switch_variant().unwrap());
+
+        assert_eq!(next(&mut hls), Some((0, false, segment.clone())));
+        assert!(hls.bitrate().unwrap() > 12_500);
+
+        // Continues with the next segment of the other variant
+        assert!(hls.switch_variant().unwrap());
+        assert_eq!(hls.variant().unwrap().bandwidth, 10000);
+        assert_eq!(next(&mut hls), Some((1, true, b"high 1".to_vec())));
+
+        assert!(!hls.switch_variant().unwrap());
+        assert_eq!(next(&mut hls), Some((2, false, b"high 2".to_vec())));
+        assert_eq!(next(&mut hls), None);
+    }
+
+    #[test]
+    fn test_live() {
+        let files = files(&[
+            (
+                "/live.m3u8",
+                b"#EXTM3U\n\
+                  #EXT-X-TARGETDURATION:1\n\
+                  #EXT-X-MEDIA-SEQUENCE:5\n\
+                  #EXTINF:1,\n\
+                  5.ts\n\
+                  #EXTINF:1,\n\
+                  6.ts\n\
+                  #EXTINF:1,\n\
+                  7.ts\n\
+                  #EXTINF:1,\n\
+                  8.ts\n",
+            ),
+        ]);
+        for i in 5..13 {
+            files
+                .lock()
+                .unwrap()
+                .insert(format!("/{}.ts", i), format!("segment {}", i).into_bytes());
+        }
+        let uri = serve(files.clone()).join("live.m3u8").unwrap();
+
+        let mut hls = Hls::new(Client::new(), config(), Flushing::new(), &uri, 0).unwrap();
+        assert!(hls.is_live());
+        assert_eq!(hls.variant(), None);
+
+        // Starts three segments before the end
+        assert_eq!(next(&mut hls), Some((6, false, b"segment 6".to_vec())));
+        assert_eq!(next(&mut hls), Some((7, false, b"segment 7".to_vec())));
+        assert_eq!(next(&mut hls), Some((8, false, b"segment 8".to_vec())));
+
+        // Segment 9 was missed when the playlist is reloaded
+        files.lock().unwrap().insert(
+            "/live.m3u8".into(),
+            b"#EXTM3U\n\
+              #EXT-X-TARGETDURATION:1\n\
+              #EXT-X-MEDIA-SEQUENCE:10\n\
+              #EXTINF:1,\n\
+              10.ts\n\
+              #EXTINF:1,\n\
+              11.ts\n\
+              #EXT-X-ENDLIST\n"
+                .to_vec(),
+        );
+
+        assert_eq!(next(&mut hls), Some((10, true, b"segment 10".to_vec())));
+        assert_eq!(next(&mut hls), Some((11, false, b"segment 11".to_vec())));
+        assert_eq!(next(&mut hls), None);
+    }
+
+    #[test]
+    fn test_reload_interval() {
+        let files = files(&[
+            (
+                "/live.m3u8",
+                b"#EXTM3U\n\
+                  #EXT-X-TARGETDURATION:0\n\
+                  #EXTINF:0,\n\
+                  0.ts\n",
+            ),
+        ]);
+        let uri = serve(files).join("live.m3u8").unwrap();
+
+        let hls = Hls::new(Client::new(), config(), Flushing::new(), &uri, 0).unwrap();
+        assert_eq!(
+            hls.reload_interval(),
+            Duration::from_millis(MIN_RELOAD_INTERVAL_MS)
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let files = files(&[
+            ("/invalid.m3u8", b"<html></html>"),
+            (
+                "/index.m3u8",
+                b"#EXTM3U\n\
+                  #EXT-X-TARGETDURATION:1\n\
+                  #EXTINF:1,\n\
+                  missing.ts\n\
+                  #EXT-X-ENDLIST\n",
+            ),
+        ]);
+        let uri = serve(files);
+
+        match Hls::new(
+            Client::new(),
+            config(),
+            Flushing::new(),
+            &uri.join("invalid.m3u8").unwrap(),
+            0,
+        ) {
+            Err(Error::Playlist(..)) => (),
+            res => panic!("Unexpected result {:?}", res),
+        }
+
+        let mut hls = Hls::new(
+            Client::new(),
+            config(),
+            Flushing::new(),
+            &uri.join("index.m3u8").unwrap(),
+            0,
+        ).unwrap();
+        match hls.next_fragment() {
+            Err(Error::Status(_, StatusCode::NotFound)) => (),
+            res => panic!("Unexpected result {:?}", res),
+        }
+    }
+}
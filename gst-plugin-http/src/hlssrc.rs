@@ -0,0 +1,793 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{cmp, u64};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use url::Url;
+use reqwest::header::Headers;
+
+use gst_plugin::error::*;
+use gst_plugin::properties::*;
+use gst_plugin_simple::source::*;
+use gst_plugin_simple::{Flushing, PropertyHandle, SettingsImpl, SharedSettings, Unlock,
+                        UriValidator};
+
+use glib;
+use gst;
+use gst::prelude::*;
+
+use auth;
+use common::{self, ClientSettings, RequestSettings};
+use hls::{self, Fragment, Hls, RequestConfig};
+use status;
+use tls;
+
+const DEFAULT_USER_AGENT: &str = "GStreamer rshlssrc";
+const DEFAULT_MAX_BITRATE: u64 = 0;
+
+#[derive(Debug, Clone)]
+struct Settings {
+    request: RequestSettings,
+    client: ClientSettings,
+    max_bitrate: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            request: RequestSettings::new(DEFAULT_USER_AGENT),
+            client: Default::default(),
+            max_bitrate: DEFAULT_MAX_BITRATE,
+        }
+    }
+}
+
+impl SettingsImpl for Settings {
+    fn set_property(&mut self, property: &Property, value: &glib::Value) -> bool {
+        if self.request.set_property(property, value) || self.client.set_property(property, value)
+        {
+            return true;
+        }
+
+        match *property {
+            Property::UInt64("max-bitrate", ..) => {
+                self.max_bitrate = value.get().unwrap();
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn get_property(&self, property: &Property) -> Option<glib::Value> {
+        if let Some(value) = self.request
+            .get_property(property)
+            .or_else(|| self.client.get_property(property))
+        {
+            return Some(value);
+        }
+
+        match *property {
+            Property::UInt64("max-bitrate", ..) => Some(self.max_bitrate.to_value()),
+            _ => None,
+        }
+    }
+}
+
+pub fn properties() -> Vec<Property<'static>> {
+    let mut properties = common::properties(
+        DEFAULT_USER_AGENT,
+        "Maximum number of retries for every playlist, key or segment (0=never)",
+        "Seconds to wait for connecting and for data (0=no timeout)",
+    );
+    properties.extend(common::client_properties());
+    properties.extend(vec![
+        Property::UInt64(
+            "max-bitrate",
+            "Maximum Bitrate",
+            "Maximum bandwidth in bits per second of the selected variant \
+             (0=unlimited)",
+            (0, u64::MAX),
+            DEFAULT_MAX_BITRATE,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::UInt64(
+            "current-bandwidth",
+            "Current Bandwidth",
+            "Bandwidth in bits per second of the current variant (0=unknown)",
+            (0, u64::MAX),
+            0,
+            PropertyMutability::Readable,
+        ),
+        Property::UInt64(
+            "download-bitrate",
+            "Download Bitrate",
+            "Measured bitrate in bits per second of the segment downloads",
+            (0, u64::MAX),
+            0,
+            PropertyMutability::Readable,
+        ),
+    ]);
+
+    properties
+}
+
+#[derive(Debug)]
+enum StreamingState {
+    Stopped,
+    Started {
+        hls: Hls,
+        fragment: Option<Fragment>,
+        // Bytes of the current segment that were output. If reading it fails,
+        // these are skipped once it was requested again
+        fragment_offset: u64,
+        resume: Option<u64>,
+        position: u64,
+        has_credentials: bool,
+    },
+}
+
+// What happened to the segment in fill()
+#[derive(Debug)]
+enum Step {
+    Opened,
+    Finished,
+    EndOfPlaylist,
+}
+
+// Values that change while streaming, which are read by properties without
+// waiting for the streaming thread, e.g. while it is downloading a segment
+#[derive(Debug, Default)]
+struct Info {
+    bandwidth: u64,
+    bitrate: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+struct SharedInfo(Arc<Mutex<Info>>);
+
+impl SharedInfo {
+    fn lock(&self) -> MutexGuard<Info> {
+        self.0.lock().unwrap()
+    }
+}
+
+impl PropertyHandle for SharedInfo {
+    fn get_property(&self, property: &Property) -> Option<glib::Value> {
+        let info = self.lock();
+
+        match *property {
+            Property::UInt64("current-bandwidth", ..) => Some(info.bandwidth.to_value()),
+            Property::UInt64("download-bitrate", ..) => Some(info.bitrate.to_value()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct HlsSrc {
+    streaming_state: StreamingState,
+    settings: Settings,
+    shared_settings: SharedSettings<Settings>,
+    flushing: Flushing,
+    info: SharedInfo,
+    cat: gst::DebugCategory,
+}
+
+impl HlsSrc {
+    pub fn new(_src: &RsBaseSrc) -> HlsSrc {
+        HlsSrc {
+            streaming_state: StreamingState::Stopped,
+            settings: Default::default(),
+            shared_settings: SharedSettings::new(Default::default()),
+            flushing: Flushing::new(),
+            info: SharedInfo::default(),
+            cat: gst::DebugCategory::new(
+                "rshlssrc",
+                gst::DebugColorFlags::empty(),
+                "Rust HLS source",
+            ),
+        }
+    }
+
+    pub fn new_boxed(src: &RsBaseSrc) -> Box<SourceImpl> {
+        Box::new(HlsSrc::new(src))
+    }
+
+    fn request_headers(&self, src: &RsBaseSrc) -> Headers {
+        let mut headers = Headers::new();
+
+        self.settings.client.set_headers(&mut headers);
+        self.settings
+            .request
+            .set_headers(self.cat, &src.clone().upcast(), &mut headers);
+
+        headers
+    }
+
+    fn update_info(&self) {
+        if let StreamingState::Started { ref hls, .. } = self.streaming_state {
+            let mut info = self.info.lock();
+            info.bandwidth = hls.variant().map_or(0, |variant| variant.bandwidth);
+            info.bitrate = hls.bitrate().unwrap_or(0);
+        }
+    }
+
+    fn post_variant(&self, src: &RsBaseSrc) {
+        let variant = match self.streaming_state {
+            StreamingState::Started { ref hls, .. } => hls.variant().cloned(),
+            StreamingState::Stopped => None,
+        };
+
+        if let Some(variant) = variant {
+            gst_debug!(self.cat, obj: src, "Selected variant {:?}", variant);
+
+            let s = gst::Structure::new(
+                "hls-variant",
+                &[
+                    ("uri", &variant.uri.as_str()),
+                    ("bandwidth", &variant.bandwidth),
+                ],
+            );
+            src.post_message(&gst::Message::new_element(s).src(Some(src)).build());
+        }
+    }
+
+    // Exponential backoff before the given retry, interrupted by flushing
+    fn wait_retry(&self, src: &RsBaseSrc, retry: u32) -> Result<(), FlowError> {
+        if !self.settings.request.wait_retry(
+            self.cat,
+            &src.clone().upcast(),
+            &self.flushing,
+            retry,
+        ) {
+            return Err(FlowError::Flushing);
+        }
+
+        Ok(())
+    }
+
+    // Switches the variant between two segments, if the measured bitrate
+    // makes another one fit better
+    fn switch_variant(&mut self, src: &RsBaseSrc) {
+        let switched = match self.streaming_state {
+            StreamingState::Started { ref mut hls, .. } => hls.switch_variant(),
+            StreamingState::Stopped => return,
+        };
+        self.update_info();
+
+        // The current variant can still be used
+        match switched {
+            Ok(true) => self.post_variant(src),
+            Ok(false) => (),
+            Err(err) => {
+                gst_warning!(self.cat, obj: src, "Failed to switch variant: {:?}", err);
+            }
+        }
+    }
+}
+
+fn error_msg(err: &hls::Error, has_credentials: bool, max_redirects: u32) -> ErrorMessage {
+    match *err {
+        hls::Error::Request(ref uri, ref err) if err.is_redirect() => error_msg!(
+            gst::ResourceError::Read,
+            ("Too many redirects for {}", uri),
+            [
+                "{} (max-redirects is {})",
+                err.to_string(),
+                max_redirects
+            ]
+        ),
+        hls::Error::Request(ref uri, ref err) if tls::is_certificate_error(err) => error_msg!(
+            gst::ResourceError::OpenRead,
+            ("Failed to verify the server certificate of {}", uri),
+            [
+                "{} (use ssl-ca-file for private CAs, or disable ssl-strict)",
+                err.to_string()
+            ]
+        ),
+        hls::Error::Request(ref uri, ref err) => error_msg!(
+            gst::ResourceError::Read,
+            ["Failed to fetch {}: {}", uri, err.to_string()]
+        ),
+        hls::Error::TimedOut(ref uri) => error_msg!(
+            gst::ResourceError::Read,
+            ("Timed out waiting for {}", uri)
+        ),
+        hls::Error::Status(ref uri, status) => {
+            status::status_error(status, uri, has_credentials, gst::ResourceError::Read)
+        }
+        hls::Error::Read(ref uri, ref err) => error_msg!(
+            gst::ResourceError::Read,
+            ["Failed to read {}: {}", uri, err.to_string()]
+        ),
+        hls::Error::Playlist(ref uri, ref err) => error_msg!(
+            gst::StreamError::Format,
+            ("Invalid playlist {}", uri),
+            ["{}", err]
+        ),
+        hls::Error::Key(ref uri, ref err) => error_msg!(
+            gst::StreamError::DecryptNokey,
+            ("Failed to get key {}", uri),
+            ["{}", err]
+        ),
+        hls::Error::Decrypt(ref uri, ref err) => error_msg!(
+            gst::StreamError::Decrypt,
+            ("Failed to decrypt {}", uri),
+            ["{}", err.to_string()]
+        ),
+        hls::Error::Flushing => error_msg!(gst::LibraryError::Failed, ["Flushing"]),
+    }
+}
+
+fn validate_uri(uri: &Url) -> Result<(), UriError> {
+    if uri.scheme() != "http" && uri.scheme() != "https" {
+        return Err(UriError::new(
+            gst::URIError::UnsupportedProtocol,
+            format!("Unsupported URI '{}'", uri.as_str()),
+        ));
+    }
+
+    Ok(())
+}
+
+impl SourceImpl for HlsSrc {
+    fn uri_validator(&self) -> Box<UriValidator> {
+        Box::new(validate_uri)
+    }
+
+    fn unlock_handle(&self) -> Option<Box<Unlock>> {
+        Some(Box::new(self.flushing.clone()))
+    }
+
+    fn property_handle(&self) -> Option<Box<PropertyHandle>> {
+        Some(Box::new((self.shared_settings.clone(), self.info.clone())))
+    }
+
+    fn is_seekable(&self, _src: &RsBaseSrc) -> bool {
+        false
+    }
+
+    fn get_size(&self, _src: &RsBaseSrc) -> Option<u64> {
+        None
+    }
+
+    fn start(&mut self, src: &RsBaseSrc, uri: Url) -> Result<(), ErrorMessage> {
+        let cat = self.cat;
+
+        self.shared_settings.update(&mut self.settings);
+        self.streaming_state = StreamingState::Stopped;
+
+        let timeout = if self.settings.request.timeout != 0 {
+            Some(Duration::from_secs(u64::from(self.settings.request.timeout)))
+        } else {
+            None
+        };
+
+        let proxy = try!(
+            self.settings
+                .client
+                .proxy_config(cat, &src.clone().upcast())
+        );
+        let client = try!(self.settings.client.create_client(
+            cat,
+            &src.clone().upcast(),
+            proxy.clone(),
+            true,
+            timeout,
+        ));
+
+        let config = RequestConfig {
+            headers: self.request_headers(src),
+            proxy: proxy,
+            user_id: self.settings.request.user_id.clone(),
+            user_pw: self.settings.request.user_pw.clone(),
+            timeout: timeout,
+        };
+
+        let has_credentials = auth::credentials(
+            &uri,
+            config.user_id.as_ref().map(String::as_str),
+            config.user_pw.as_ref().map(String::as_str),
+        ).is_some();
+        let max_redirects = self.settings.client.max_redirects;
+        let hls = try!(
+            Hls::new(
+                client,
+                config,
+                self.flushing.clone(),
+                &uri,
+                self.settings.max_bitrate,
+            ).or_else(|err| {
+                gst_error!(cat, obj: src, "Failed to load playlist: {:?}", err);
+                Err(error_msg(&err, has_credentials, max_redirects))
+            })
+        );
+
+        gst_debug!(cat, obj: src, "Loaded playlist (live {})", hls.is_live());
+
+        self.streaming_state = StreamingState::Started {
+            hls: hls,
+            fragment: None,
+            fragment_offset: 0,
+            resume: None,
+            position: 0,
+            has_credentials: has_credentials,
+        };
+        self.update_info();
+        self.post_variant(src);
+
+        Ok(())
+    }
+
+    fn stop(&mut self, _src: &RsBaseSrc) -> Result<(), ErrorMessage> {
+        self.streaming_state = StreamingState::Stopped;
+        *self.info.lock() = Info::default();
+
+        Ok(())
+    }
+
+    // Segments are only passed on as a continuous stream
+    fn seek(
+        &mut self,
+        _src: &RsBaseSrc,
+        start: u64,
+        stop: Option<u64>,
+    ) -> Result<(), ErrorMessage> {
+        match self.streaming_state {
+            StreamingState::Started { position, .. } if position == start && stop.is_none() => {
+                Ok(())
+            }
+            _ => Err(error_msg!(
+                gst::ResourceError::Seek,
+                ["Seeking is not supported"]
+            )),
+        }
+    }
+
+    // Segments are read while they are downloaded, at most the requested
+    // length per call
+    fn fill(
+        &mut self,
+        src: &RsBaseSrc,
+        offset: u64,
+        length: u32,
+        buffer: &mut gst::BufferRef,
+    ) -> Result<(), FlowError> {
+        let cat = self.cat;
+        let mut retry = 0;
+
+        loop {
+            let (res, has_credentials) = match self.streaming_state {
+                StreamingState::Started {
+                    ref mut hls,
+                    ref mut fragment,
+                    ref mut fragment_offset,
+                    ref mut resume,
+                    ref mut position,
+                    has_credentials,
+                } => {
+                    let res = match fragment.take() {
+                        Some(mut current) => {
+                            let res = match buffer.map_writable() {
+                                None => {
+                                    return Err(FlowError::Error(error_msg!(
+                                        gst::LibraryError::Failed,
+                                        ["Failed to map buffer"]
+                                    )));
+                                }
+                                Some(mut map) => {
+                                    let data = map.as_mut_slice();
+                                    let size = cmp::min(data.len(), length as usize);
+                                    current.read(&mut data[..size])
+                                }
+                            };
+
+                            match res {
+                                Ok(0) => {
+                                    gst_debug!(
+                                        cat,
+                                        obj: src,
+                                        "Finished segment {} ({} bytes)",
+                                        current.sequence,
+                                        *fragment_offset
+                                    );
+                                    hls.finish_fragment(&current);
+                                    *fragment_offset = 0;
+                                    Ok(Step::Finished)
+                                }
+                                Ok(size) => {
+                                    if *fragment_offset == 0 && current.discontinuity {
+                                        buffer.set_flags(gst::BufferFlags::DISCONT);
+                                    }
+
+                                    buffer.set_size(size);
+                                    *fragment_offset += size as u64;
+                                    *position = offset + size as u64;
+                                    *fragment = Some(current);
+
+                                    return Ok(());
+                                }
+                                Err(err) => {
+                                    *resume = Some(current.sequence);
+                                    Err(err)
+                                }
+                            }
+                        }
+                        None => match hls.next_fragment() {
+                            Ok(Some(mut next)) => {
+                                gst_debug!(
+                                    cat,
+                                    obj: src,
+                                    "Got segment {} ({}, discontinuity {})",
+                                    next.sequence,
+                                    next.uri,
+                                    next.discontinuity
+                                );
+
+                                // After an error the data continues where it stopped,
+                                // unless the segment is not available anymore
+                                let skipped = match resume.take() {
+                                    Some(sequence) if sequence == next.sequence => {
+                                        next.skip(*fragment_offset)
+                                    }
+                                    Some(_) => {
+                                        next.discontinuity = true;
+                                        *fragment_offset = 0;
+                                        Ok(())
+                                    }
+                                    None => Ok(()),
+                                };
+
+                                match skipped {
+                                    Ok(()) => {
+                                        *fragment = Some(next);
+                                        Ok(Step::Opened)
+                                    }
+                                    Err(err) => {
+                                        *resume = Some(next.sequence);
+                                        Err(err)
+                                    }
+                                }
+                            }
+                            Ok(None) => Ok(Step::EndOfPlaylist),
+                            Err(err) => Err(err),
+                        },
+                    };
+
+                    (res, has_credentials)
+                }
+                StreamingState::Stopped => {
+                    return Err(FlowError::Error(
+                        error_msg!(gst::LibraryError::Failed, ["Not started yet"]),
+                    ));
+                }
+            };
+
+            match res {
+                Ok(Step::Opened) => (),
+                Ok(Step::Finished) => self.switch_variant(src),
+                Ok(Step::EndOfPlaylist) => {
+                    gst_debug!(cat, obj: src, "End of playlist");
+                    return Err(FlowError::Eos);
+                }
+                Err(_) if self.flushing.is_flushing() => {
+                    gst_debug!(cat, obj: src, "Flushing");
+                    return Err(FlowError::Flushing);
+                }
+                Err(err) => {
+                    if retry >= self.settings.request.retries {
+                        gst_error!(cat, obj: src, "Failed to get segment: {:?}", err);
+                        return Err(FlowError::Error(error_msg(
+                            &err,
+                            has_credentials,
+                            self.settings.client.max_redirects,
+                        )));
+                    }
+
+                    gst_warning!(cat, obj: src, "Failed to get segment: {:?}", err);
+                    retry += 1;
+                    try!(self.wait_retry(src, retry));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    use decrypt;
+
+    // Minimal HTTP server for the given paths, with one request per connection
+    fn serve(files: Vec<(&'static str, Vec<u8>)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}", listener.local_addr().unwrap());
+
+        thread::spawn(move || for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut head = Vec::new();
+            let mut buf = [0u8; 1];
+            while !head.ends_with(b"\r\n\r\n") {
+                match stream.read(&mut buf) {
+                    Ok(1) => head.push(buf[0]),
+                    _ => break,
+                }
+            }
+
+            let head = String::from_utf8(head).unwrap();
+            let path = head.split(' ').nth(1).unwrap_or("");
+            let response = match files.iter().find(|&&(p, _)| p == path) {
+                Some(&(_, ref data)) => {
+                    let mut response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        data.len()
+                    ).into_bytes();
+                    response.extend_from_slice(data);
+                    response
+                }
+                None => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_vec(),
+            };
+            let _ = stream.write_all(&response);
+        });
+
+        uri
+    }
+
+    fn create_src(uri: &str) -> (gst::Element, gst::Bus) {
+        gst::init().unwrap();
+
+        let type_ = source_register_type(SourceInfo {
+            name: "rshlssrc-test".into(),
+            long_name: "HLS Source".into(),
+            description: "Reads HTTP Live Streaming streams".into(),
+            classification: "Source/Network".into(),
+            author: "agent <agent@local>".into(),
+            rank: 0,
+            create_instance: HlsSrc::new_boxed,
+            protocols: vec!["http".into(), "https".into()],
+            properties: properties(),
+            push_only: true,
+        });
+
+        let src = glib::Object::new(type_, &[])
+            .unwrap()
+            .downcast::<gst::Element>()
+            .unwrap();
+        src.set_property("uri", &uri.to_value()).unwrap();
+
+        let bus = gst::Bus::new();
+        src.set_bus(Some(&bus));
+
+        (src, bus)
+    }
+
+    // Links a pad to the source that sends the data of every buffer, and None
+    // for EOS
+    fn link(src: &gst::Element) -> (gst::Pad, mpsc::Receiver<Option<Vec<u8>>>) {
+        let (sender, receiver) = mpsc::channel();
+
+        let sinkpad = gst::Pad::new("sink", gst::PadDirection::Sink);
+        let data_sender = Mutex::new(sender.clone());
+        sinkpad.set_chain_function(move |_pad, _parent, buffer| {
+            let map = buffer.map_readable().unwrap();
+            let _ = data_sender
+                .lock()
+                .unwrap()
+                .send(Some(map.as_slice().to_vec()));
+            gst::FlowReturn::Ok
+        });
+        let eos_sender = Mutex::new(sender);
+        sinkpad.set_event_function(move |_pad, _parent, event| {
+            if let gst::EventView::Eos(..) = event.view() {
+                let _ = eos_sender.lock().unwrap().send(None);
+            }
+            true
+        });
+        sinkpad.set_active(true).unwrap();
+
+        let srcpad = src.get_static_pad("src").unwrap();
+        assert_eq!(srcpad.link(&sinkpad), gst::PadLinkReturn::Ok);
+
+        (sinkpad, receiver)
+    }
+
+    #[test]
+    fn test_hls_src() {
+        let key = [0x42; 16];
+        let iv = [0x17; 16];
+        let segment = vec![0x47; 100 * 1024];
+
+        let uri = serve(vec![
+            (
+                "/master.m3u8",
+                b"#EXTM3U\n\
+                  #EXT-X-STREAM-INF:BANDWIDTH=100000\n\
+                  index.m3u8\n"
+                    .to_vec(),
+            ),
+            (
+                "/index.m3u8",
+                b"#EXTM3U\n\
+                  #EXT-X-TARGETDURATION:2\n\
+                  #EXTINF:2,\n\
+                  0.ts\n\
+                  #EXT-X-KEY:METHOD=AES-128,URI=\"/key\",IV=0x17171717171717171717171717171717\n\
+                  #EXTINF:2,\n\
+                  1.ts\n\
+                  #EXT-X-ENDLIST\n"
+                    .to_vec(),
+            ),
+            ("/0.ts", b"first segment".to_vec()),
+            ("/1.ts", decrypt::encrypt_cbc(&key, &iv, &segment)),
+            ("/key", key.to_vec()),
+        ]);
+
+        let (src, bus) = create_src(&format!("{}/master.m3u8", uri));
+        src.set_property("cookies", &"a=b".to_value()).unwrap();
+        assert_eq!(
+            src.get_property("cookies").unwrap().get::<String>(),
+            Some("a=b".into())
+        );
+        assert_eq!(
+            src.get_property("ssl-strict").unwrap().get::<bool>(),
+            Some(true)
+        );
+        assert_eq!(
+            src.get_property("current-bandwidth").unwrap().get::<u64>(),
+            Some(0)
+        );
+
+        let (_sinkpad, receiver) = link(&src);
+        assert_ne!(
+            src.set_state(gst::State::Playing),
+            gst::StateChangeReturn::Failure
+        );
+
+        // The segments are output while they are downloaded, in buffers of at
+        // most the block size
+        let mut data = Vec::new();
+        let mut buffers = 0;
+        while let Some(buffer) = receiver.recv_timeout(Duration::from_secs(5)).unwrap() {
+            assert!(buffer.len() <= 4096);
+            data.extend_from_slice(&buffer);
+            buffers += 1;
+        }
+        let mut expected = b"first segment".to_vec();
+        expected.extend_from_slice(&segment);
+        assert_eq!(data, expected);
+        assert!(buffers > 2);
+
+        assert_eq!(
+            src.get_property("current-bandwidth").unwrap().get::<u64>(),
+            Some(100_000)
+        );
+        assert!(src.get_property("download-bitrate").unwrap().get::<u64>().unwrap() > 0);
+
+        let mut variants = Vec::new();
+        while let Some(msg) = bus.pop() {
+            if let gst::MessageView::Element(..) = msg.view() {
+                variants.push(msg.get_structure().unwrap().to_owned());
+            }
+        }
+        assert_eq!(variants.len(), 1);
+        assert_eq!(variants[0].get_name(), "hls-variant");
+        assert_eq!(variants[0].get::<u64>("bandwidth"), Some(100_000));
+
+        assert_eq!(
+            src.set_state(gst::State::Null),
+            gst::StateChangeReturn::Success
+        );
+    }
+}
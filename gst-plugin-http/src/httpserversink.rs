@@ -0,0 +1,501 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::{u32, u64};
+use url::Url;
+
+use gst_plugin::error::*;
+use gst_plugin::properties::*;
+use gst_plugin_simple::sink::*;
+use gst_plugin_simple::{PropertyHandle, SettingsImpl, SharedSettings, UriValidator};
+
+use glib;
+use gst;
+use gst::prelude::*;
+
+use server::{ClientEvent, Server, SlowClientPolicy};
+
+const DEFAULT_MAX_QUEUE_BYTES: u64 = 4 * 1024 * 1024;
+const DEFAULT_SLOW_CLIENT_POLICY: &str = "drop";
+
+pub static PROPERTIES: [Property; 4] = [
+    Property::UInt64(
+        "max-queue-bytes",
+        "Maximum Queue Bytes",
+        "Maximum number of bytes queued for every client (0=unlimited)",
+        (0, u64::MAX),
+        DEFAULT_MAX_QUEUE_BYTES,
+        PropertyMutability::ReadWrite,
+    ),
+    Property::String(
+        "slow-client-policy",
+        "Slow Client Policy",
+        "What to do with clients whose queue is full: drop data until the next \
+         keyframe (drop) or disconnect them (disconnect)",
+        Some(DEFAULT_SLOW_CLIENT_POLICY),
+        PropertyMutability::ReadWrite,
+    ),
+    Property::String(
+        "content-type",
+        "Content Type",
+        "Value of the Content-Type HTTP response header (NULL=from the caps)",
+        None,
+        PropertyMutability::ReadWrite,
+    ),
+    Property::UInt(
+        "num-clients",
+        "Number of Clients",
+        "Number of clients currently receiving the stream",
+        (0, u32::MAX),
+        0,
+        PropertyMutability::Readable,
+    ),
+];
+
+#[derive(Debug, Clone)]
+struct Settings {
+    max_queue_bytes: u64,
+    slow_client_policy: String,
+    content_type: Option<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            max_queue_bytes: DEFAULT_MAX_QUEUE_BYTES,
+            slow_client_policy: DEFAULT_SLOW_CLIENT_POLICY.into(),
+            content_type: None,
+        }
+    }
+}
+
+impl SettingsImpl for Settings {
+    fn set_property(&mut self, property: &Property, value: &glib::Value) -> bool {
+        match *property {
+            Property::UInt64("max-queue-bytes", ..) => {
+                self.max_queue_bytes = value.get().unwrap();
+            }
+            Property::String("slow-client-policy", ..) => {
+                self.slow_client_policy = value
+                    .get()
+                    .unwrap_or_else(|| DEFAULT_SLOW_CLIENT_POLICY.into());
+            }
+            Property::String("content-type", ..) => {
+                self.content_type = value.get();
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn get_property(&self, property: &Property) -> Option<glib::Value> {
+        match *property {
+            Property::UInt64("max-queue-bytes", ..) => Some(self.max_queue_bytes.to_value()),
+            Property::String("slow-client-policy", ..) => {
+                Some(self.slow_client_policy.to_value())
+            }
+            Property::String("content-type", ..) => Some(self.content_type.to_value()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum StreamingState {
+    Stopped,
+    Started { server: Server },
+}
+
+#[derive(Debug)]
+pub struct HttpServerSink {
+    streaming_state: StreamingState,
+    settings: Settings,
+    shared_settings: SharedSettings<Settings>,
+    caps_headers: bool,
+    cat: gst::DebugCategory,
+}
+
+impl HttpServerSink {
+    pub fn new(_sink: &RsBaseSink) -> HttpServerSink {
+        HttpServerSink {
+            streaming_state: StreamingState::Stopped,
+            settings: Default::default(),
+            shared_settings: SharedSettings::new(Default::default()),
+            caps_headers: false,
+            cat: gst::DebugCategory::new(
+                "rshttpserversink",
+                gst::DebugColorFlags::empty(),
+                "Rust HTTP server sink",
+            ),
+        }
+    }
+
+    pub fn new_boxed(sink: &RsBaseSink) -> Box<SinkImpl> {
+        Box::new(HttpServerSink::new(sink))
+    }
+}
+
+// Called from the client threads
+fn post_client_event(element: &gst::Element, cat: gst::DebugCategory, event: ClientEvent) {
+    gst_debug!(cat, obj: element, "{:?}", event);
+
+    let s = match event {
+        ClientEvent::Added { id, address } => gst::Structure::new(
+            "client-added",
+            &[("id", &id), ("address", &address.to_string())],
+        ),
+        ClientEvent::Removed {
+            id,
+            address,
+            reason,
+        } => gst::Structure::new(
+            "client-removed",
+            &[
+                ("id", &id),
+                ("address", &address.to_string()),
+                ("reason", &reason),
+            ],
+        ),
+    };
+
+    element.post_message(&gst::Message::new_element(s).src(Some(element)).build());
+}
+
+// The URI is the address and path the server listens on, e.g.
+// http://0.0.0.0:8080/live
+fn validate_uri(uri: &Url) -> Result<(), UriError> {
+    if uri.scheme() != "http" {
+        return Err(UriError::new(
+            gst::URIError::UnsupportedProtocol,
+            format!("Unsupported URI '{}'", uri.as_str()),
+        ));
+    }
+
+    if uri.host().is_none() {
+        return Err(UriError::new(
+            gst::URIError::BadUri,
+            format!("No address to listen on in URI '{}'", uri.as_str()),
+        ));
+    }
+
+    Ok(())
+}
+
+impl SinkImpl for HttpServerSink {
+    fn uri_validator(&self) -> Box<UriValidator> {
+        Box::new(validate_uri)
+    }
+
+    fn property_handle(&self) -> Option<Box<PropertyHandle>> {
+        Some(Box::new(self.shared_settings.clone()))
+    }
+
+    fn start(&mut self, sink: &RsBaseSink, uri: Url) -> Result<(), ErrorMessage> {
+        let cat = self.cat;
+
+        self.shared_settings.update(&mut self.settings);
+
+        if let StreamingState::Started { .. } = self.streaming_state {
+            return Err(error_msg!(
+                gst::LibraryError::Failed,
+                ["Sink already started"]
+            ));
+        }
+
+        let policy = match self.settings.slow_client_policy.as_str() {
+            "drop" => SlowClientPolicy::Drop,
+            "disconnect" => SlowClientPolicy::Disconnect,
+            policy => {
+                return Err(error_msg!(
+                    gst::LibraryError::Settings,
+                    ["Unsupported slow client policy '{}'", policy]
+                ));
+            }
+        };
+
+        let element = sink.clone().upcast::<gst::Element>();
+        let server = try!(
+            uri.with_default_port(|_| Ok(80))
+                .and_then(|address| {
+                    Server::new(
+                        address,
+                        uri.path(),
+                        self.settings.max_queue_bytes as usize,
+                        policy,
+                        Box::new(move |event| post_client_event(&element, cat, event)),
+                    )
+                })
+                .or_else(|err| {
+                    gst_error!(cat, obj: sink, "Failed to listen on {}: {}", uri, err);
+                    Err(error_msg!(
+                        gst::ResourceError::OpenWrite,
+                        ["Failed to listen on {}: {}", uri, err.to_string()]
+                    ))
+                })
+        );
+
+        gst_debug!(cat, obj: sink, "Listening on {}", server.local_addr());
+
+        if let Some(ref content_type) = self.settings.content_type {
+            server.set_content_type(content_type);
+        }
+
+        self.caps_headers = false;
+        self.streaming_state = StreamingState::Started { server: server };
+
+        Ok(())
+    }
+
+    fn stop(&mut self, sink: &RsBaseSink) -> Result<(), ErrorMessage> {
+        // Disconnects all clients and waits for their threads
+        self.streaming_state = StreamingState::Stopped;
+
+        gst_debug!(self.cat, obj: sink, "Stopped server");
+
+        Ok(())
+    }
+
+    fn set_caps(&mut self, sink: &RsBaseSink, caps: &gst::CapsRef) -> Result<(), ErrorMessage> {
+        let server = match self.streaming_state {
+            StreamingState::Started { ref server } => server,
+            StreamingState::Stopped => {
+                return Err(error_msg!(gst::LibraryError::Failed, ["Not started yet"]));
+            }
+        };
+
+        self.shared_settings.update(&mut self.settings);
+
+        let s = match caps.get_structure(0) {
+            Some(s) => s,
+            None => return Ok(()),
+        };
+
+        if self.settings.content_type.is_none() {
+            server.set_content_type(s.get_name());
+        }
+
+        let headers = s.get::<gst::Array>("streamheader").map(|streamheader| {
+            streamheader
+                .as_slice()
+                .iter()
+                .filter_map(|v| v.get::<gst::Buffer>())
+                .filter_map(|header| header.map_readable().map(|map| map.as_slice().to_vec()))
+                .collect::<Vec<_>>()
+        });
+
+        // Without stream headers in the caps, the header buffers that follow
+        // replace the ones of the previous caps
+        gst_debug!(
+            self.cat,
+            obj: sink,
+            "Got {} stream headers from caps",
+            headers.as_ref().map_or(0, |headers| headers.len())
+        );
+        self.caps_headers = headers.is_some();
+        server.set_headers(headers.unwrap_or_default());
+
+        Ok(())
+    }
+
+    fn render(&mut self, sink: &RsBaseSink, buffer: &gst::BufferRef) -> Result<(), FlowError> {
+        let cat = self.cat;
+
+        gst_trace!(cat, obj: sink, "Rendering {:?}", buffer);
+
+        let server = match self.streaming_state {
+            StreamingState::Started { ref server } => server,
+            StreamingState::Stopped => {
+                return Err(FlowError::Error(
+                    error_msg!(gst::LibraryError::Failed, ["Not started yet"]),
+                ));
+            }
+        };
+
+        let flags = buffer.get_flags();
+        let is_header = flags.contains(gst::BufferFlags::HEADER);
+
+        // Headers from the caps are sent to every new client already
+        if is_header && self.caps_headers {
+            gst_trace!(cat, obj: sink, "Skipping header buffer");
+            return Ok(());
+        }
+
+        let map = match buffer.map_readable() {
+            None => {
+                return Err(FlowError::Error(error_msg!(
+                    gst::LibraryError::Failed,
+                    ["Failed to map buffer"]
+                )));
+            }
+            Some(map) => map,
+        };
+        let data = map.as_slice();
+
+        if is_header {
+            server.add_header(data.to_vec());
+            return Ok(());
+        }
+
+        // New clients and clients that dropped data start at a keyframe
+        let keyframe = !flags.contains(gst::BufferFlags::DELTA_UNIT);
+        server.send(data.to_vec(), keyframe);
+
+        Ok(())
+    }
+
+    fn get_property(&self, sink: &RsBaseSink, property: &Property) -> Result<glib::Value, ()> {
+        match *property {
+            Property::UInt("num-clients", ..) => match self.streaming_state {
+                StreamingState::Started { ref server } => {
+                    Ok((server.num_clients() as u32).to_value())
+                }
+                StreamingState::Stopped => Ok(0u32.to_value()),
+            },
+            _ => {
+                gst_warning!(self.cat, obj: sink, "Unhandled property {:?}", property);
+                Err(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    fn create_sink() -> (gst::Element, gst::Bus, u16) {
+        gst::init().unwrap();
+
+        let type_ = sink_register_type(SinkInfo {
+            name: "rshttpserversink-test".into(),
+            long_name: "HTTP Server Sink".into(),
+            description: "Serves a live stream to HTTP clients".into(),
+            classification: "Sink/Network".into(),
+            author: "agent <agent@local>".into(),
+            rank: 0,
+            create_instance: HttpServerSink::new_boxed,
+            protocols: vec!["http".into()],
+            properties: PROPERTIES.to_vec(),
+        });
+
+        // Pick a free port
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        let sink = glib::Object::new(type_, &[])
+            .unwrap()
+            .downcast::<gst::Element>()
+            .unwrap();
+        let uri = format!("http://127.0.0.1:{}/live", port);
+        sink.set_property("uri", &uri.to_value()).unwrap();
+        sink.set_property("sync", &false.to_value()).unwrap();
+        sink.set_property("async", &false.to_value()).unwrap();
+
+        let bus = gst::Bus::new();
+        sink.set_bus(Some(&bus));
+
+        (sink, bus, port)
+    }
+
+    fn num_clients(sink: &gst::Element) -> u32 {
+        sink.get_property("num-clients")
+            .unwrap()
+            .get::<u32>()
+            .unwrap()
+    }
+
+    // Waits for the next client-added or client-removed message
+    fn client_message(bus: &gst::Bus) -> gst::Structure {
+        loop {
+            let msg = bus.timed_pop(5 * gst::SECOND).unwrap();
+            match msg.view() {
+                gst::MessageView::Element(..) => {
+                    return msg.get_structure().unwrap().to_owned();
+                }
+                gst::MessageView::Error(..) => panic!("Unexpected error {:?}", msg),
+                _ => (),
+            }
+        }
+    }
+
+    fn buffer(data: &[u8], flags: gst::BufferFlags) -> gst::Buffer {
+        let mut buffer = gst::Buffer::from_slice(data.to_vec()).unwrap();
+        buffer.get_mut().unwrap().set_flags(flags);
+        buffer
+    }
+
+    #[test]
+    fn test_http_server_sink() {
+        let (sink, bus, port) = create_sink();
+        assert_eq!(
+            sink.set_state(gst::State::Playing),
+            gst::StateChangeReturn::Success
+        );
+        assert_eq!(num_clients(&sink), 0);
+
+        let pad = sink.get_static_pad("sink").unwrap();
+        assert!(pad.send_event(gst::Event::new_stream_start("test").build()));
+        let header = gst::Buffer::from_slice(b"OLD".to_vec()).unwrap();
+        let caps = gst::Caps::new_simple(
+            "video/x-matroska",
+            &[("streamheader", &gst::Array::new(&[&header]))],
+        );
+        assert!(pad.send_event(gst::Event::new_caps(&caps).build()));
+        let mut segment = gst::Segment::default();
+        segment.init(gst::Format::Bytes);
+        assert!(pad.send_event(gst::Event::new_segment(&segment).build()));
+
+        // New caps without stream headers, so the header buffers are used
+        let caps = gst::Caps::new_simple("video/webm", &[]);
+        assert!(pad.send_event(gst::Event::new_caps(&caps).build()));
+        assert_eq!(
+            pad.chain(buffer(b"NEW", gst::BufferFlags::HEADER)),
+            gst::FlowReturn::Ok
+        );
+
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        write!(stream, "GET /live HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+
+        let s = client_message(&bus);
+        assert_eq!(s.get_name(), "client-added");
+        assert_eq!(s.get::<u64>("id"), Some(0));
+        assert_eq!(num_clients(&sink), 1);
+
+        assert_eq!(
+            pad.chain(buffer(b"KEY", gst::BufferFlags::empty())),
+            gst::FlowReturn::Ok
+        );
+
+        let expected = b"HTTP/1.0 200 OK\r\n\
+                         Content-Type: video/webm\r\n\
+                         Cache-Control: no-cache\r\n\
+                         Connection: close\r\n\
+                         \r\n\
+                         NEWKEY";
+        let mut data = vec![0; expected.len()];
+        stream.read_exact(&mut data).unwrap();
+        assert_eq!(&data[..], &expected[..]);
+
+        assert_eq!(
+            sink.set_state(gst::State::Null),
+            gst::StateChangeReturn::Success
+        );
+
+        let s = client_message(&bus);
+        assert_eq!(s.get_name(), "client-removed");
+        assert_eq!(s.get::<u64>("id"), Some(0));
+        assert_eq!(s.get::<String>("reason"), Some("Server stopped".into()));
+        assert_eq!(num_clients(&sink), 0);
+    }
+}
@@ -0,0 +1,1154 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::u64;
+use std::io::ErrorKind;
+use std::time::Duration;
+use url::{Position, Url};
+use reqwest::{Client, Method, StatusCode};
+use reqwest::header::Headers;
+
+use gst_plugin::error::*;
+use gst_plugin::properties::*;
+use gst_plugin_simple::sink::*;
+use gst_plugin_simple::{Flushing, PropertyHandle, SettingsImpl, SharedSettings, Unlock,
+                        UriValidator};
+
+use glib;
+use gst;
+use gst::prelude::*;
+
+use auth::{self, Challenge};
+use common::{self, RequestSettings};
+use request::{self, SendError};
+use status;
+use upload::Upload;
+
+const DEFAULT_METHOD: &str = "PUT";
+const DEFAULT_USER_AGENT: &str = "GStreamer rshttpsink";
+const DEFAULT_AUTH_METHOD: &str = "auto";
+const DEFAULT_MAX_SIZE_BYTES: u64 = 0;
+const DEFAULT_SPLIT_ON_KEY_UNIT_EVENT: bool = false;
+
+// Requests are only repeated if they sent at most this much data
+const MAX_REPLAY_SIZE: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone)]
+struct Settings {
+    request: RequestSettings,
+    method: String,
+    content_type: Option<String>,
+    auth_method: String,
+    max_size_bytes: u64,
+    split_on_key_unit_event: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            request: RequestSettings::new(DEFAULT_USER_AGENT),
+            method: DEFAULT_METHOD.into(),
+            content_type: None,
+            auth_method: DEFAULT_AUTH_METHOD.into(),
+            max_size_bytes: DEFAULT_MAX_SIZE_BYTES,
+            split_on_key_unit_event: DEFAULT_SPLIT_ON_KEY_UNIT_EVENT,
+        }
+    }
+}
+
+impl SettingsImpl for Settings {
+    fn set_property(&mut self, property: &Property, value: &glib::Value) -> bool {
+        if self.request.set_property(property, value) {
+            return true;
+        }
+
+        match *property {
+            Property::String("method", ..) => {
+                self.method = value.get().unwrap_or_else(|| DEFAULT_METHOD.into());
+            }
+            Property::String("content-type", ..) => {
+                self.content_type = value.get();
+            }
+            Property::String("auth-method", ..) => {
+                self.auth_method = value
+                    .get()
+                    .unwrap_or_else(|| DEFAULT_AUTH_METHOD.into());
+            }
+            Property::UInt64("max-size-bytes", ..) => {
+                self.max_size_bytes = value.get().unwrap();
+            }
+            Property::Boolean("split-on-key-unit-event", ..) => {
+                self.split_on_key_unit_event = value.get().unwrap();
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn get_property(&self, property: &Property) -> Option<glib::Value> {
+        if let Some(value) = self.request.get_property(property) {
+            return Some(value);
+        }
+
+        match *property {
+            Property::String("method", ..) => Some(self.method.to_value()),
+            Property::String("content-type", ..) => Some(self.content_type.to_value()),
+            Property::String("auth-method", ..) => Some(self.auth_method.to_value()),
+            Property::UInt64("max-size-bytes", ..) => Some(self.max_size_bytes.to_value()),
+            Property::Boolean("split-on-key-unit-event", ..) => {
+                Some(self.split_on_key_unit_event.to_value())
+            }
+            _ => None,
+        }
+    }
+}
+
+pub fn properties() -> Vec<Property<'static>> {
+    let mut properties = common::properties(
+        DEFAULT_USER_AGENT,
+        "Maximum number of new requests after a request failed because of the connection \
+         or the server (0=never). Only requests that sent at most 1 MiB are repeated",
+        "Seconds to wait for connecting and for the server (0=no timeout)",
+    );
+    properties.extend(vec![
+        Property::String(
+            "method",
+            "Method",
+            "HTTP method of the upload requests, PUT or POST",
+            Some(DEFAULT_METHOD),
+            PropertyMutability::ReadWrite,
+        ),
+        Property::String(
+            "content-type",
+            "Content Type",
+            "Value of the Content-Type HTTP request header",
+            None,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::String(
+            "auth-method",
+            "Authentication Method",
+            "auto to ask the server for its authentication scheme with a HEAD request first, \
+             or basic to send Basic credentials right away",
+            Some(DEFAULT_AUTH_METHOD),
+            PropertyMutability::ReadWrite,
+        ),
+        Property::UInt64(
+            "max-size-bytes",
+            "Maximum Size Bytes",
+            "Start a new request once the current one reached this size in bytes \
+             (0=unlimited). With PUT the URI path needs an index pattern like %05d",
+            (0, u64::MAX),
+            DEFAULT_MAX_SIZE_BYTES,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::Boolean(
+            "split-on-key-unit-event",
+            "Split on Key Unit Event",
+            "Start a new request on every downstream GstForceKeyUnit event. \
+             With PUT the URI path needs an index pattern like %05d",
+            DEFAULT_SPLIT_ON_KEY_UNIT_EVENT,
+            PropertyMutability::ReadWrite,
+        ),
+    ]);
+
+    properties
+}
+
+#[derive(Debug)]
+enum StreamingState {
+    Stopped,
+    Started {
+        uri: Url,
+        method: Method,
+        index: u32,
+        upload: Option<Upload>,
+        // Number of times the request of the current fragment was repeated
+        retry: u32,
+        split_pending: bool,
+    },
+}
+
+#[derive(Debug)]
+pub struct HttpSink {
+    streaming_state: StreamingState,
+    settings: Settings,
+    shared_settings: SharedSettings<Settings>,
+    headers: Vec<gst::Buffer>,
+    caps_headers: bool,
+    flushing: Flushing,
+    auth: Option<Challenge>,
+    auth_probed: bool,
+    cat: gst::DebugCategory,
+    client: Client,
+}
+
+impl HttpSink {
+    pub fn new(_sink: &RsBaseSink) -> HttpSink {
+        HttpSink {
+            streaming_state: StreamingState::Stopped,
+            settings: Default::default(),
+            shared_settings: SharedSettings::new(Default::default()),
+            headers: Vec::new(),
+            caps_headers: false,
+            flushing: Flushing::new(),
+            auth: None,
+            auth_probed: false,
+            cat: gst::DebugCategory::new(
+                "rshttpsink",
+                gst::DebugColorFlags::empty(),
+                "Rust HTTP sink",
+            ),
+            client: Client::new(),
+        }
+    }
+
+    pub fn new_boxed(sink: &RsBaseSink) -> Box<SinkImpl> {
+        Box::new(HttpSink::new(sink))
+    }
+
+    fn request_headers(&mut self, sink: &RsBaseSink, uri: &Url, method: &Method) -> Headers {
+        let mut headers = Headers::new();
+
+        if let Some(ref content_type) = self.settings.content_type {
+            headers.set_raw("Content-Type", content_type.clone());
+        }
+
+        self.settings
+            .request
+            .set_headers(self.cat, &sink.clone().upcast(), &mut headers);
+
+        // Credentials are only sent once it's known how the server wants them
+        let credentials = self.credentials(uri);
+        if let (Some(auth), Some((user, password))) = (self.auth.as_mut(), credentials) {
+            let target = &uri[Position::BeforePath..];
+            headers.set_raw(
+                "Authorization",
+                auth.authorization(&user, &password, method.as_ref(), target),
+            );
+        }
+
+        headers
+    }
+
+    fn credentials(&self, uri: &Url) -> Option<(String, String)> {
+        auth::credentials(
+            uri,
+            self.settings.request.user_id.as_ref().map(String::as_str),
+            self.settings.request.user_pw.as_ref().map(String::as_str),
+        )
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        if self.settings.request.timeout != 0 {
+            Some(Duration::from_secs(u64::from(self.settings.request.timeout)))
+        } else {
+            None
+        }
+    }
+
+    fn fragment(&self, index: u32) -> Result<(Url, Method), ErrorMessage> {
+        match self.streaming_state {
+            StreamingState::Started {
+                ref uri,
+                ref method,
+                ..
+            } => Ok((
+                fragment_uri(uri, index).unwrap_or_else(|| uri.clone()),
+                method.clone(),
+            )),
+            StreamingState::Stopped => {
+                Err(error_msg!(gst::LibraryError::Failed, ["Not started yet"]))
+            }
+        }
+    }
+
+    // The body can't be sent again after a 401 response, so the challenge is
+    // requested with a HEAD request first, which has no side effects
+    fn probe_challenge(&self, sink: &RsBaseSink, uri: &Url) -> Option<Challenge> {
+        let cat = self.cat;
+
+        let mut req = self.client.request(Method::Head, uri.clone());
+        let mut headers = Headers::new();
+        self.settings
+            .request
+            .set_headers(cat, &sink.clone().upcast(), &mut headers);
+        req.headers(headers);
+
+        match request::send(req, &self.flushing, self.timeout()) {
+            Ok(ref response) if response.status() == StatusCode::Unauthorized => {
+                let challenge = auth::response_challenge(response);
+                gst_debug!(cat, obj: sink, "Server asked for {:?}", challenge);
+                challenge
+            }
+            Ok(response) => {
+                gst_debug!(cat, obj: sink, "No authentication needed: {}", response.status());
+                None
+            }
+            Err(err) => {
+                gst_warning!(cat, obj: sink, "Failed to ask for authentication: {}", err);
+                None
+            }
+        }
+    }
+
+    // Starts the request of a fragment and sends the given data first, i.e.
+    // the stream headers or everything that was sent with a failed request
+    fn open_upload(
+        &mut self,
+        sink: &RsBaseSink,
+        index: u32,
+        data: Vec<u8>,
+    ) -> Result<Upload, ErrorMessage> {
+        let cat = self.cat;
+        let (uri, method) = try!(self.fragment(index));
+
+        // Credentials are only ever sent in the Authorization header
+        let mut request_uri = uri.clone();
+        let _ = request_uri.set_username("");
+        let _ = request_uri.set_password(None);
+
+        if self.auth.is_none() && !self.auth_probed && self.credentials(&uri).is_some() {
+            self.auth_probed = true;
+            self.auth = self.probe_challenge(sink, &request_uri);
+        }
+
+        let mut req = self.client.request(method.clone(), request_uri.clone());
+        req.headers(self.request_headers(sink, &uri, &method));
+
+        gst_debug!(cat, obj: sink, "Starting new request {:?}", req);
+
+        let mut upload = try!(Upload::new(req, MAX_REPLAY_SIZE).or_else(|err| {
+            gst_error!(cat, obj: sink, "Failed to start request: {}", err);
+            Err(error_msg!(
+                gst::LibraryError::Failed,
+                ["Failed to start request: {}", err.to_string()]
+            ))
+        }));
+
+        // A failed request is noticed with the next write or when finishing
+        if !data.is_empty() {
+            if let Err(err) = upload.write(data, &self.flushing) {
+                gst_debug!(cat, obj: sink, "Failed to send initial data: {:?}", err);
+            }
+        }
+
+        Ok(upload)
+    }
+
+    // Waits for the response, and remembers the authentication challenge for
+    // the next request if the server rejected this one. Failures come with
+    // whether repeating the request could help
+    fn finish_upload(
+        &mut self,
+        sink: &RsBaseSink,
+        upload: Upload,
+        index: u32,
+    ) -> Result<(), (ErrorMessage, bool)> {
+        let cat = self.cat;
+        let (uri, _) = try!(self.fragment(index).map_err(|msg| (msg, false)));
+
+        let size = upload.size();
+        let response = match upload.finish(&self.flushing, self.timeout()) {
+            Ok(response) => response,
+            Err(err) => {
+                gst_warning!(cat, obj: sink, "Request failed: {}", err);
+                let retry = match err {
+                    SendError::Request(_) | SendError::TimedOut => true,
+                    _ => false,
+                };
+                return Err((
+                    error_msg!(
+                        gst::ResourceError::Write,
+                        ["Failed to upload to {}: {}", uri, err.to_string()]
+                    ),
+                    retry,
+                ));
+            }
+        };
+
+        gst_debug!(cat, obj: sink, "Got response {:?}", response);
+
+        let status = response.status();
+        let has_credentials =
+            self.settings.request.user_id.is_some() || !uri.username().is_empty();
+        if status == StatusCode::Unauthorized {
+            self.auth = auth::response_challenge(&response);
+        }
+
+        if !status.is_success() {
+            let retry = status.is_server_error() || status == StatusCode::RequestTimeout
+                || (status == StatusCode::Unauthorized && has_credentials && self.auth.is_some());
+            return Err((
+                status::status_error(status, &uri, has_credentials, gst::ResourceError::Write),
+                retry,
+            ));
+        }
+
+        let s = gst::Structure::new(
+            "fragment-uploaded",
+            &[
+                ("index", &index),
+                ("uri", &uri.as_str()),
+                ("size", &size),
+                ("status-code", &u32::from(status.as_u16())),
+            ],
+        );
+        sink.post_message(&gst::Message::new_element(s).src(Some(sink)).build());
+
+        Ok(())
+    }
+
+    // Starts the request of a fragment again after it failed, if repeating it
+    // could help and everything that was sent with it is still available
+    fn repeat_upload(
+        &mut self,
+        sink: &RsBaseSink,
+        index: u32,
+        replay: Option<Vec<u8>>,
+        failure: (ErrorMessage, bool),
+        retry: &mut u32,
+    ) -> Result<Upload, FlowError> {
+        let cat = self.cat;
+        let (msg, can_retry) = failure;
+
+        if self.flushing.is_flushing() {
+            gst_debug!(cat, obj: sink, "Flushing");
+            return Err(FlowError::Flushing);
+        }
+
+        let replay = match replay {
+            Some(replay) if can_retry && *retry < self.settings.request.retries => replay,
+            _ => {
+                gst_error!(cat, obj: sink, "Failed to upload: {:?}", msg);
+                return Err(FlowError::Error(msg));
+            }
+        };
+
+        gst_warning!(cat, obj: sink, "Failed to upload: {:?}", msg);
+        *retry += 1;
+        if !self.settings
+            .request
+            .wait_retry(cat, &sink.clone().upcast(), &self.flushing, *retry)
+        {
+            return Err(FlowError::Flushing);
+        }
+
+        self.open_upload(sink, index, replay).map_err(FlowError::Error)
+    }
+
+    // Finishes the request of a fragment, and repeats it as long as it fails
+    // and that could help
+    fn complete_upload(
+        &mut self,
+        sink: &RsBaseSink,
+        upload: Upload,
+        index: u32,
+        retry: &mut u32,
+    ) -> Result<(), FlowError> {
+        let mut upload = upload;
+        loop {
+            let replay = upload.take_replay();
+            let failure = match self.finish_upload(sink, upload, index) {
+                Ok(()) => return Ok(()),
+                Err(failure) => failure,
+            };
+
+            upload = try!(self.repeat_upload(sink, index, replay, failure, retry));
+        }
+    }
+
+    // Writes the buffer to the request of the current fragment, which is
+    // started first if needed and repeated if it failed. On flushing the
+    // request is kept without the buffer
+    fn write_buffer(
+        &mut self,
+        sink: &RsBaseSink,
+        upload: &mut Option<Upload>,
+        index: u32,
+        data: &[u8],
+        retry: &mut u32,
+    ) -> Result<(), FlowError> {
+        let mut current = match upload.take() {
+            Some(current) => current,
+            None => {
+                let headers = self.stream_headers();
+                try!(
+                    self.open_upload(sink, index, headers)
+                        .map_err(FlowError::Error)
+                )
+            }
+        };
+
+        match current.write(data.to_vec(), &self.flushing) {
+            Ok(()) => {
+                *upload = Some(current);
+                Ok(())
+            }
+            Err(ref err) if err.kind() != ErrorKind::BrokenPipe => {
+                gst_debug!(self.cat, obj: sink, "Flushing");
+                *upload = Some(current);
+                Err(FlowError::Flushing)
+            }
+            Err(_) => {
+                // The request finished early and its response has the reason.
+                // What was sent with it includes this buffer already
+                let replay = current.take_replay();
+                let failure = match self.finish_upload(sink, current, index) {
+                    Err(failure) => failure,
+                    Ok(()) => (
+                        error_msg!(
+                            gst::ResourceError::Write,
+                            ["Server finished the request before the end"]
+                        ),
+                        false,
+                    ),
+                };
+
+                *upload = Some(try!(
+                    self.repeat_upload(sink, index, replay, failure, retry)
+                ));
+                Ok(())
+            }
+        }
+    }
+
+    fn stream_headers(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for header in &self.headers {
+            data.extend_from_slice(header.map_readable().unwrap().as_slice());
+        }
+
+        data
+    }
+}
+
+// Fragments are uploaded to their own URI if its path contains %d or
+// %0<width>d, e.g. http://server/fragment-%05d.ts. URIs can't contain control
+// characters, so this does not clash with escaped characters as long as %d is
+// not followed by a hex digit
+fn fragment_uri(uri: &Url, index: u32) -> Option<Url> {
+    let path = uri.path();
+    let bytes = path.as_bytes();
+    let is_hex = |i: usize| bytes.get(i).map_or(false, |&b| (b as char).is_digit(16));
+
+    let mut start = 0;
+    while let Some(pos) = path[start..].find('%') {
+        let pos = start + pos;
+        let (width, end) = match (bytes.get(pos + 1), bytes.get(pos + 2), bytes.get(pos + 3)) {
+            (Some(&b'd'), _, _) if !is_hex(pos + 2) => (0, pos + 2),
+            (Some(&b'0'), Some(&width @ b'1'...b'9'), Some(&b'd')) => {
+                (usize::from(width - b'0'), pos + 4)
+            }
+            _ => {
+                start = pos + 1;
+                continue;
+            }
+        };
+
+        let mut fragment = uri.clone();
+        fragment.set_path(&format!(
+            "{0}{1:02$}{3}",
+            &path[..pos],
+            index,
+            width,
+            &path[end..]
+        ));
+        return Some(fragment);
+    }
+
+    None
+}
+
+fn validate_uri(uri: &Url) -> Result<(), UriError> {
+    if uri.scheme() != "http" && uri.scheme() != "https" {
+        return Err(UriError::new(
+            gst::URIError::UnsupportedProtocol,
+            format!("Unsupported URI '{}'", uri.as_str()),
+        ));
+    }
+
+    Ok(())
+}
+
+impl SinkImpl for HttpSink {
+    fn uri_validator(&self) -> Box<UriValidator> {
+        Box::new(validate_uri)
+    }
+
+    fn unlock_handle(&self) -> Option<Box<Unlock>> {
+        Some(Box::new(self.flushing.clone()))
+    }
+
+    fn property_handle(&self) -> Option<Box<PropertyHandle>> {
+        Some(Box::new(self.shared_settings.clone()))
+    }
+
+    fn start(&mut self, sink: &RsBaseSink, uri: Url) -> Result<(), ErrorMessage> {
+        self.shared_settings.update(&mut self.settings);
+
+        let method = match self.settings.method.to_uppercase().as_str() {
+            "PUT" => Method::Put,
+            "POST" => Method::Post,
+            _ => {
+                return Err(error_msg!(
+                    gst::LibraryError::Settings,
+                    ["Unsupported method '{}'", self.settings.method]
+                ));
+            }
+        };
+
+        // Every fragment would replace the previous one otherwise
+        let split = self.settings.max_size_bytes != 0 || self.settings.split_on_key_unit_event;
+        if split && method == Method::Put && fragment_uri(&uri, 0).is_none() {
+            gst_error!(self.cat, obj: sink, "No index pattern in {} for splitting", uri);
+            return Err(error_msg!(
+                gst::LibraryError::Settings,
+                (
+                    "Splitting with method PUT needs an index pattern like %05d in the URI \
+                     path, or method POST"
+                )
+            ));
+        }
+
+        let mut builder = Client::builder();
+        if let Some(timeout) = self.timeout() {
+            builder.timeout(timeout);
+        }
+        self.client = try!(builder.build().or_else(|err| {
+            gst_error!(self.cat, obj: sink, "Failed to create HTTP client: {:?}", err);
+            Err(error_msg!(
+                gst::LibraryError::Init,
+                ["Failed to create HTTP client: {}", err.to_string()]
+            ))
+        }));
+
+        self.auth = match self.settings.auth_method.to_lowercase().as_str() {
+            "auto" => None,
+            "basic" => Some(Challenge::Basic),
+            _ => {
+                return Err(error_msg!(
+                    gst::LibraryError::Settings,
+                    [
+                        "Unsupported authentication method '{}'",
+                        self.settings.auth_method
+                    ]
+                ));
+            }
+        };
+        self.auth_probed = false;
+        self.streaming_state = StreamingState::Started {
+            uri: uri,
+            method: method,
+            index: 0,
+            upload: None,
+            retry: 0,
+            split_pending: false,
+        };
+
+        Ok(())
+    }
+
+    fn stop(&mut self, sink: &RsBaseSink) -> Result<(), ErrorMessage> {
+        // Without EOS the data is incomplete, so the request is aborted
+        // instead of being finished
+        if let StreamingState::Started {
+            upload: Some(ref upload),
+            ..
+        } = self.streaming_state
+        {
+            gst_debug!(self.cat, obj: sink, "Aborting unfinished {:?}", upload);
+        }
+
+        self.streaming_state = StreamingState::Stopped;
+        if !self.caps_headers {
+            self.headers.clear();
+        }
+
+        Ok(())
+    }
+
+    fn set_caps(&mut self, sink: &RsBaseSink, caps: &gst::CapsRef) -> Result<(), ErrorMessage> {
+        let headers = caps.get_structure(0)
+            .and_then(|s| s.get::<gst::Array>("streamheader"))
+            .map(|streamheader| {
+                streamheader
+                    .as_slice()
+                    .iter()
+                    .filter_map(|v| v.get::<gst::Buffer>())
+                    .collect::<Vec<_>>()
+            });
+
+        if let Some(headers) = headers {
+            gst_debug!(
+                self.cat,
+                obj: sink,
+                "Got {} stream headers from caps",
+                headers.len()
+            );
+            self.headers = headers;
+            self.caps_headers = true;
+        }
+
+        Ok(())
+    }
+
+    fn custom_event(
+        &mut self,
+        sink: &RsBaseSink,
+        structure: &gst::StructureRef,
+    ) -> Result<(), ErrorMessage> {
+        self.shared_settings.update(&mut self.settings);
+
+        if !self.settings.split_on_key_unit_event || structure.get_name() != "GstForceKeyUnit" {
+            return Ok(());
+        }
+
+        if let StreamingState::Started {
+            ref mut split_pending,
+            ..
+        } = self.streaming_state
+        {
+            gst_debug!(self.cat, obj: sink, "Starting new request with next buffer");
+            *split_pending = true;
+        }
+
+        Ok(())
+    }
+
+    fn render(&mut self, sink: &RsBaseSink, buffer: &gst::BufferRef) -> Result<(), FlowError> {
+        let cat = self.cat;
+
+        self.shared_settings.update(&mut self.settings);
+
+        gst_trace!(cat, obj: sink, "Rendering {:?}", buffer);
+
+        let is_header = buffer.get_flags().contains(gst::BufferFlags::HEADER);
+
+        // Headers from the caps are sent at the beginning of every request already
+        if is_header && self.caps_headers {
+            gst_trace!(cat, obj: sink, "Skipping header buffer");
+            return Ok(());
+        }
+
+        let map = match buffer.map_readable() {
+            None => {
+                return Err(FlowError::Error(error_msg!(
+                    gst::LibraryError::Failed,
+                    ["Failed to map buffer"]
+                )));
+            }
+            Some(map) => map,
+        };
+        let data = map.as_slice();
+
+        // Empty chunks would end the request body
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let (mut index, mut upload, mut retry, split_pending) = match self.streaming_state {
+            StreamingState::Started {
+                index,
+                ref mut upload,
+                retry,
+                ref mut split_pending,
+                ..
+            } => {
+                let pending = *split_pending;
+                *split_pending = false;
+                (index, upload.take(), retry, pending)
+            }
+            StreamingState::Stopped => {
+                return Err(FlowError::Error(
+                    error_msg!(gst::LibraryError::Failed, ["Not started yet"]),
+                ));
+            }
+        };
+
+        let split = match upload {
+            Some(ref upload) if !is_header => {
+                split_pending
+                    || (self.settings.max_size_bytes != 0
+                        && upload.size() + data.len() as u64 > self.settings.max_size_bytes)
+            }
+            _ => false,
+        };
+
+        let mut res = Ok(());
+        if split {
+            gst_debug!(cat, obj: sink, "Starting new request");
+            res = self.complete_upload(sink, upload.take().unwrap(), index, &mut retry);
+            index += 1;
+            retry = 0;
+        }
+
+        if res.is_ok() {
+            res = self.write_buffer(sink, &mut upload, index, data, &mut retry);
+        }
+
+        if res.is_ok() && is_header {
+            self.headers.push(buffer.to_owned());
+        }
+
+        if let StreamingState::Started {
+            index: ref mut i,
+            upload: ref mut u,
+            retry: ref mut r,
+            ..
+        } = self.streaming_state
+        {
+            *i = index;
+            *u = upload;
+            *r = retry;
+        }
+
+        res
+    }
+
+    fn eos(&mut self, sink: &RsBaseSink) -> Result<(), ErrorMessage> {
+        let upload = match self.streaming_state {
+            StreamingState::Started {
+                index,
+                ref mut upload,
+                retry,
+                ..
+            } => upload.take().map(|upload| (upload, index, retry)),
+            StreamingState::Stopped => None,
+        };
+
+        let (upload, index, mut retry) = match upload {
+            Some(upload) => upload,
+            None => return Ok(()),
+        };
+
+        match self.complete_upload(sink, upload, index, &mut retry) {
+            Ok(()) => Ok(()),
+            Err(FlowError::Error(msg)) | Err(FlowError::NotNegotiated(msg)) => Err(msg),
+            Err(err) => {
+                gst_debug!(self.cat, obj: sink, "Not finishing upload: {:?}", err);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::str;
+    use std::thread;
+
+    const CREATED: &str = "HTTP/1.1 201 Created\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+
+    fn fragment(uri: &str, index: u32) -> Option<String> {
+        fragment_uri(&Url::parse(uri).unwrap(), index).map(|uri| uri.into_string())
+    }
+
+    fn find(data: &[u8], pattern: &[u8]) -> Option<usize> {
+        data.windows(pattern.len()).position(|w| w == pattern)
+    }
+
+    enum Reply {
+        // Response sent right after the request headers
+        Early(&'static str),
+        // Response sent after the whole chunked body
+        Late(&'static str),
+        // No response, the client has to close the connection
+        Never,
+    }
+
+    // Minimal HTTP server that handles one request per connection, with the
+    // given replies in order. Returns the request heads and decoded bodies
+    fn serve(listener: TcpListener, replies: Vec<Reply>) -> Vec<(String, Vec<u8>)> {
+        let mut requests = Vec::new();
+
+        for reply in replies {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut data = Vec::new();
+            let mut buf = [0u8; 4096];
+
+            let header_end = loop {
+                let size = stream.read(&mut buf).unwrap();
+                assert!(size > 0);
+                data.extend_from_slice(&buf[..size]);
+                if let Some(idx) = find(&data, b"\r\n\r\n") {
+                    break idx + 4;
+                }
+            };
+            let head = String::from_utf8(data[..header_end].to_vec()).unwrap();
+            let mut body = data[header_end..].to_vec();
+
+            let response = match reply {
+                Reply::Early(response) => {
+                    stream.write_all(response.as_bytes()).unwrap();
+                    requests.push((head, body));
+                    continue;
+                }
+                Reply::Late(response) => response,
+                Reply::Never => {
+                    loop {
+                        match stream.read(&mut buf) {
+                            Ok(0) | Err(_) => break,
+                            Ok(size) => body.extend_from_slice(&buf[..size]),
+                        }
+                    }
+                    requests.push((head, body));
+                    continue;
+                }
+            };
+
+            if head.to_lowercase().contains("transfer-encoding: chunked") {
+                while !body.ends_with(b"0\r\n\r\n") {
+                    let size = stream.read(&mut buf).unwrap();
+                    assert!(size > 0);
+                    body.extend_from_slice(&buf[..size]);
+                }
+
+                let mut decoded = Vec::new();
+                {
+                    let mut rest = &body[..];
+                    loop {
+                        let line_end = find(rest, b"\r\n").unwrap();
+                        let len = usize::from_str_radix(
+                            str::from_utf8(&rest[..line_end]).unwrap(),
+                            16,
+                        ).unwrap();
+                        if len == 0 {
+                            break;
+                        }
+                        decoded.extend_from_slice(&rest[(line_end + 2)..(line_end + 2 + len)]);
+                        rest = &rest[(line_end + 2 + len + 2)..];
+                    }
+                }
+                body = decoded;
+            }
+
+            stream.write_all(response.as_bytes()).unwrap();
+            requests.push((head, body));
+        }
+
+        requests
+    }
+
+    // Creates a sink for the URI, with a type of its own for every test as
+    // types can only be registered once
+    fn create_sink(name: &str, uri: &str) -> (gst::Element, gst::Bus) {
+        gst::init().unwrap();
+
+        let type_ = sink_register_type(SinkInfo {
+            name: format!("rshttpsink-{}", name),
+            long_name: "HTTP/HTTPS Sink".into(),
+            description: "Uploads streams with HTTP/HTTPS PUT or POST requests".into(),
+            classification: "Sink/Network".into(),
+            author: "agent <agent@local>".into(),
+            rank: 0,
+            create_instance: HttpSink::new_boxed,
+            protocols: vec!["http".into(), "https".into()],
+            properties: properties(),
+        });
+
+        let sink = glib::Object::new(type_, &[])
+            .unwrap()
+            .downcast::<gst::Element>()
+            .unwrap();
+        sink.set_property("uri", &uri.to_value()).unwrap();
+        sink.set_property("sync", &false.to_value()).unwrap();
+        sink.set_property("async", &false.to_value()).unwrap();
+        sink.set_property("retry-delay", &0u64.to_value()).unwrap();
+
+        let bus = gst::Bus::new();
+        sink.set_bus(Some(&bus));
+
+        (sink, bus)
+    }
+
+    // Starts the sink and renders the buffers, followed by EOS if requested
+    fn render(sink: &gst::Element, buffers: &[&'static [u8]], eos: bool) {
+        assert_eq!(
+            sink.set_state(gst::State::Playing),
+            gst::StateChangeReturn::Success
+        );
+
+        let pad = sink.get_static_pad("sink").unwrap();
+        assert!(pad.send_event(gst::Event::new_stream_start("test").build()));
+        let mut segment = gst::Segment::default();
+        segment.init(gst::Format::Bytes);
+        assert!(pad.send_event(gst::Event::new_segment(&segment).build()));
+
+        for data in buffers {
+            let buffer = gst::Buffer::from_slice(*data).unwrap();
+            assert_eq!(pad.chain(buffer), gst::FlowReturn::Ok);
+        }
+
+        if eos {
+            assert!(pad.send_event(gst::Event::new_eos().build()));
+        }
+    }
+
+    // Stops the sink and returns the fragment-uploaded messages it posted
+    fn stop(sink: &gst::Element, bus: &gst::Bus) -> Vec<gst::Structure> {
+        assert_eq!(
+            sink.set_state(gst::State::Null),
+            gst::StateChangeReturn::Success
+        );
+
+        let mut fragments = Vec::new();
+        while let Some(msg) = bus.pop() {
+            match msg.view() {
+                gst::MessageView::Element(..) => {
+                    let s = msg.get_structure().unwrap();
+                    if s.get_name() == "fragment-uploaded" {
+                        fragments.push(s.to_owned());
+                    }
+                }
+                gst::MessageView::Error(ref err) => panic!("Error: {}", err.get_error()),
+                _ => (),
+            }
+        }
+
+        fragments
+    }
+
+    #[test]
+    fn test_fragment_uri() {
+        assert_eq!(
+            fragment("http://server/fragment-%05d.ts", 7),
+            Some(String::from("http://server/fragment-00007.ts"))
+        );
+        assert_eq!(
+            fragment("http://server/%d/data?key=%d", 12),
+            Some(String::from("http://server/12/data?key=%d"))
+        );
+        assert_eq!(
+            fragment("http://server/a%20b%0a-%d.ts", 3),
+            Some(String::from("http://server/a%20b%0a-3.ts"))
+        );
+        assert_eq!(fragment("http://server/%da.ts", 3), None);
+        assert_eq!(fragment("http://server/upload", 3), None);
+    }
+
+    #[test]
+    fn test_retry() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/upload", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            serve(
+                listener,
+                vec![
+                    Reply::Early(
+                        "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\
+                         Connection: close\r\n\r\n",
+                    ),
+                    Reply::Late(CREATED),
+                ],
+            )
+        });
+
+        let (sink, bus) = create_sink("retry", &uri);
+        render(&sink, &[b"Hello ", b"World"], true);
+        let fragments = stop(&sink, &bus);
+
+        let requests = server.join().unwrap();
+        assert!(requests[1].0.starts_with("PUT /upload HTTP/1.1\r\n"));
+        assert_eq!(requests[1].1, b"Hello World");
+
+        assert_eq!(fragments.len(), 1);
+        assert_eq!(fragments[0].get::<u32>("index"), Some(0));
+        assert_eq!(fragments[0].get::<u64>("size"), Some(11));
+        assert_eq!(fragments[0].get::<u32>("status-code"), Some(201));
+    }
+
+    #[test]
+    fn test_split() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/fragment-%d.ts", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            serve(
+                listener,
+                vec![Reply::Late(CREATED), Reply::Late(CREATED), Reply::Late(CREATED)],
+            )
+        });
+
+        let (sink, bus) = create_sink("split", &uri);
+        sink.set_property("max-size-bytes", &8u64.to_value()).unwrap();
+        render(&sink, &[b"Hello ", b"World ", b"again"], true);
+        let fragments = stop(&sink, &bus);
+
+        let requests = server.join().unwrap();
+        let expected: [&[u8]; 3] = [b"Hello ", b"World ", b"again"];
+        assert_eq!(requests.len(), 3);
+        for (index, &(ref head, ref body)) in requests.iter().enumerate() {
+            assert!(head.starts_with(&format!("PUT /fragment-{}.ts HTTP/1.1\r\n", index)));
+            assert_eq!(body.as_slice(), expected[index]);
+        }
+
+        assert_eq!(fragments.len(), 3);
+        for (index, fragment) in fragments.iter().enumerate() {
+            assert_eq!(fragment.get::<u32>("index"), Some(index as u32));
+        }
+    }
+
+    #[test]
+    fn test_auth_probe() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/upload", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            serve(
+                listener,
+                vec![
+                    Reply::Late(
+                        "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Basic realm=\"test\"\r\n\
+                         Content-Length: 0\r\nConnection: close\r\n\r\n",
+                    ),
+                    Reply::Late(CREATED),
+                ],
+            )
+        });
+
+        let (sink, bus) = create_sink("auth-probe", &uri);
+        sink.set_property("user-id", &"user".to_value()).unwrap();
+        sink.set_property("user-pw", &"secret".to_value()).unwrap();
+        render(&sink, &[b"Hello"], true);
+        let fragments = stop(&sink, &bus);
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].0.starts_with("HEAD /upload HTTP/1.1\r\n"));
+        assert!(!requests[0].0.to_lowercase().contains("authorization"));
+        assert!(requests[1].0.starts_with("PUT /upload HTTP/1.1\r\n"));
+        assert!(
+            requests[1]
+                .0
+                .to_lowercase()
+                .contains("authorization: basic dxnlcjpzzwnyzxq=")
+        );
+        assert_eq!(requests[1].1, b"Hello");
+        assert_eq!(fragments.len(), 1);
+    }
+
+    #[test]
+    fn test_stop_aborts() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/upload", listener.local_addr().unwrap());
+        let server = thread::spawn(move || serve(listener, vec![Reply::Never]));
+
+        let (sink, bus) = create_sink("stop-aborts", &uri);
+        render(&sink, &[b"Hello"], false);
+        // Give the request thread time to send what was rendered
+        thread::sleep(Duration::from_millis(200));
+        let fragments = stop(&sink, &bus);
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].1.starts_with(b"5\r\nHello\r\n"));
+        assert!(find(&requests[0].1, b"0\r\n\r\n").is_none());
+        assert!(fragments.is_empty());
+    }
+}
@@ -6,37 +6,345 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use std::u64;
-use std::io::Read;
-use url::Url;
-use reqwest::{Client, Response};
-use reqwest::header::{AcceptRanges, ByteRangeSpec, ContentLength, ContentRange, ContentRangeSpec,
-                      Range, RangeUnit};
+use std::{cmp, i32, str, u32, u64};
+use std::io::{self, ErrorKind, Read};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use url::{Position, Url};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use reqwest::header::{AcceptRanges, ByteRangeSpec, Connection, ContentEncoding, ContentLength,
+                      ContentRange, ContentRangeSpec, ContentType, Headers, Location, Range,
+                      RangeUnit};
 
 use gst_plugin::error::*;
+use gst_plugin::properties::*;
 use gst_plugin_simple::source::*;
-use gst_plugin_simple::UriValidator;
+use gst_plugin_simple::{Flushing, PropertyHandle, QueryHandle, SettingsImpl, SharedSettings, Unlock,
+                        UriValidator};
 
+use glib;
 use gst;
 use gst::prelude::*;
 
+use auth::{self, Challenge};
+use body::BodyReader;
+use request::{self, SendError};
+use cache::{self, BlockCache};
+use encoding;
+use tls;
+use proxy::ProxyConfig;
+use stats::Stats;
+use status;
+use common::{self, ClientSettings, RequestSettings};
+use icy::IcyReader;
+
+const DEFAULT_USER_AGENT: &str = "GStreamer rshttpsrc";
+const DEFAULT_IRADIO_MODE: bool = false;
+const DEFAULT_STRIP_ICY_METADATA: bool = true;
+const DEFAULT_CONNECT_TIMEOUT: u32 = 15;
+const DEFAULT_KEEP_ALIVE: bool = true;
+const DEFAULT_BLOCK_SIZE: u32 = 128 * 1024;
+const DEFAULT_CACHE_SIZE: u32 = 4 * 1024 * 1024;
+const DEFAULT_AUTOMATIC_REDIRECT: bool = true;
+const DEFAULT_STATS_INTERVAL: u64 = gst::SECOND;
+
+// Skipping this much data is cheaper than a new request
+const MAX_SKIP_SIZE: u64 = 256 * 1024;
+
+#[derive(Debug, Clone)]
+struct Settings {
+    request: RequestSettings,
+    client: ClientSettings,
+    iradio_mode: bool,
+    strip_icy_metadata: bool,
+    connect_timeout: u32,
+    keep_alive: bool,
+    block_size: u32,
+    cache_size: u32,
+    automatic_redirect: bool,
+    stats_interval: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            request: RequestSettings::new(DEFAULT_USER_AGENT),
+            client: Default::default(),
+            iradio_mode: DEFAULT_IRADIO_MODE,
+            strip_icy_metadata: DEFAULT_STRIP_ICY_METADATA,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            keep_alive: DEFAULT_KEEP_ALIVE,
+            block_size: DEFAULT_BLOCK_SIZE,
+            cache_size: DEFAULT_CACHE_SIZE,
+            automatic_redirect: DEFAULT_AUTOMATIC_REDIRECT,
+            stats_interval: DEFAULT_STATS_INTERVAL,
+        }
+    }
+}
+
+impl SettingsImpl for Settings {
+    fn set_property(&mut self, property: &Property, value: &glib::Value) -> bool {
+        if self.request.set_property(property, value) || self.client.set_property(property, value)
+        {
+            return true;
+        }
+
+        match *property {
+            Property::Boolean("iradio-mode", ..) => {
+                self.iradio_mode = value.get().unwrap();
+            }
+            Property::Boolean("strip-icy-metadata", ..) => {
+                self.strip_icy_metadata = value.get().unwrap();
+            }
+            Property::UInt("connect-timeout", ..) => {
+                self.connect_timeout = value.get().unwrap();
+            }
+            Property::Boolean("keep-alive", ..) => {
+                self.keep_alive = value.get().unwrap();
+            }
+            Property::UInt("block-size", ..) => {
+                self.block_size = value.get().unwrap();
+            }
+            Property::UInt("cache-size", ..) => {
+                self.cache_size = value.get().unwrap();
+            }
+            Property::Boolean("automatic-redirect", ..) => {
+                self.automatic_redirect = value.get().unwrap();
+            }
+            Property::UInt64("stats-interval", ..) => {
+                self.stats_interval = value.get().unwrap();
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn get_property(&self, property: &Property) -> Option<glib::Value> {
+        if let Some(value) = self
+            .request
+            .get_property(property)
+            .or_else(|| self.client.get_property(property))
+        {
+            return Some(value);
+        }
+
+        match *property {
+            Property::Boolean("iradio-mode", ..) => Some(self.iradio_mode.to_value()),
+            Property::Boolean("strip-icy-metadata", ..) => Some(self.strip_icy_metadata.to_value()),
+            Property::UInt("connect-timeout", ..) => Some(self.connect_timeout.to_value()),
+            Property::Boolean("keep-alive", ..) => Some(self.keep_alive.to_value()),
+            Property::UInt("block-size", ..) => Some(self.block_size.to_value()),
+            Property::UInt("cache-size", ..) => Some(self.cache_size.to_value()),
+            Property::Boolean("automatic-redirect", ..) => Some(self.automatic_redirect.to_value()),
+            Property::UInt64("stats-interval", ..) => Some(self.stats_interval.to_value()),
+            _ => None,
+        }
+    }
+}
+
+pub fn properties() -> Vec<Property<'static>> {
+    let mut properties = common::properties(
+        DEFAULT_USER_AGENT,
+        "Maximum number of reconnection attempts after the connection was lost (0=never)",
+        "Seconds to wait for data of the response body to arrive (0=no timeout)",
+    );
+    properties.extend(common::client_properties());
+    properties.extend(vec![
+        Property::Boolean(
+            "iradio-mode",
+            "Internet Radio Mode",
+            "Request ICY metadata from internet radio servers",
+            DEFAULT_IRADIO_MODE,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::Boolean(
+            "strip-icy-metadata",
+            "Strip ICY Metadata",
+            "Remove ICY metadata from the stream and send it as tags, \
+             instead of outputting application/x-icy",
+            DEFAULT_STRIP_ICY_METADATA,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::UInt(
+            "connect-timeout",
+            "Connect Timeout",
+            "Seconds to wait for connecting and for the response headers (0=no timeout)",
+            (0, 3600),
+            DEFAULT_CONNECT_TIMEOUT,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::Boolean(
+            "keep-alive",
+            "Keep Alive",
+            "Reuse the connection for further requests once a response was read \
+             completely, e.g. for range requests or after seeking close to the end",
+            DEFAULT_KEEP_ALIVE,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::Boolean(
+            "automatic-redirect",
+            "Automatic Redirect",
+            "Follow redirects, otherwise only post a redirect element message",
+            DEFAULT_AUTOMATIC_REDIRECT,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::UInt64(
+            "stats-interval",
+            "Statistics Interval",
+            "Interval in nanoseconds between http-stats element messages (0=disabled)",
+            (0, u64::MAX),
+            DEFAULT_STATS_INTERVAL,
+            PropertyMutability::ReadWrite,
+        ),
+        Property::Boxed(
+            "stats",
+            "Statistics",
+            "Bytes received, average and instant bitrate in bits per second, \
+             latency of the last request and number of requests",
+            gst::Structure::static_type(),
+            PropertyMutability::Readable,
+        ),
+        Property::String(
+            "content-type",
+            "Content Type",
+            "Content-Type of the response",
+            None,
+            PropertyMutability::Readable,
+        ),
+        Property::String(
+            "final-uri",
+            "Final URI",
+            "URI of the response after following redirects",
+            None,
+            PropertyMutability::Readable,
+        ),
+    ]);
+
+    properties
+}
+
+// The random access variant additionally has the block cache configuration
+pub fn pull_properties() -> Vec<Property<'static>> {
+    let mut properties = properties();
+
+    properties.push(Property::UInt(
+        "block-size",
+        "Block Size",
+        "Size in bytes of the blocks that are requested with range requests and cached",
+        (4096, u32::MAX),
+        DEFAULT_BLOCK_SIZE,
+        PropertyMutability::ReadWrite,
+    ));
+    properties.push(Property::UInt(
+        "cache-size",
+        "Cache Size",
+        "Maximum size in bytes of cached blocks",
+        (0, u32::MAX),
+        DEFAULT_CACHE_SIZE,
+        PropertyMutability::ReadWrite,
+    ));
+
+    properties
+}
+
 #[derive(Debug)]
 enum StreamingState {
     Stopped,
     Started {
         uri: Url,
-        response: Response,
+        body: BodyReader,
         seekable: bool,
         position: u64,
         size: Option<u64>,
         start: u64,
         stop: Option<u64>,
+        caps: Option<gst::Caps>,
+        tags: Option<gst::TagList>,
+        icy: Option<IcyReader>,
     },
 }
 
+// Values that change while streaming, which are read by properties without
+// waiting for the streaming thread, e.g. while it is blocked in a request
+#[derive(Debug, Default)]
+struct Info {
+    content_type: Option<String>,
+    final_uri: Option<String>,
+    size: Option<u64>,
+    stats: Stats,
+}
+
+#[derive(Debug, Clone, Default)]
+struct SharedInfo(Arc<Mutex<Info>>);
+
+impl SharedInfo {
+    fn lock(&self) -> MutexGuard<Info> {
+        self.0.lock().unwrap()
+    }
+}
+
+impl PropertyHandle for SharedInfo {
+    fn get_property(&self, property: &Property) -> Option<glib::Value> {
+        let info = self.lock();
+
+        match *property {
+            Property::Boxed("stats", ..) => {
+                Some(Some(info.stats.to_structure(Instant::now())).to_value())
+            }
+            Property::String("content-type", ..) => Some(info.content_type.to_value()),
+            Property::String("final-uri", ..) => Some(info.final_uri.to_value()),
+            _ => None,
+        }
+    }
+}
+
+impl QueryHandle for SharedInfo {
+    // Data is passed on as soon as it arrives, so only the download rate is
+    // of interest here
+    fn query(&self, query: &mut gst::QueryRef) -> bool {
+        use gst::QueryView;
+
+        match query.view_mut() {
+            QueryView::Buffering(ref mut q) => {
+                let avg_in = cmp::min(
+                    self.lock().stats.average_bitrate(Instant::now()) / 8,
+                    i32::MAX as u64,
+                ) as i32;
+
+                q.set_percent(false, 100);
+                q.set_stats(gst::BufferingMode::Stream, avg_in, -1, 0);
+                true
+            }
+            QueryView::Duration(ref mut q) => {
+                let (fmt, _) = q.get();
+                match self.lock().size {
+                    Some(size) if fmt == gst::Format::Bytes => {
+                        q.set(fmt, size as i64);
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct HttpSrc {
     streaming_state: StreamingState,
+    settings: Settings,
+    shared_settings: SharedSettings<Settings>,
+    flushing: Flushing,
+    auth: Option<Challenge>,
+    proxy: ProxyConfig,
+    redirect_uri: Option<Url>,
+    cache: Option<BlockCache>,
+    stats_posted: Option<Instant>,
+    pending_tags: Vec<gst::TagList>,
+    segment_sent: bool,
+    info: SharedInfo,
     cat: gst::DebugCategory,
     client: Client,
 }
@@ -45,6 +353,17 @@ impl HttpSrc {
     pub fn new(_src: &RsBaseSrc) -> HttpSrc {
         HttpSrc {
             streaming_state: StreamingState::Stopped,
+            settings: Default::default(),
+            shared_settings: SharedSettings::new(Default::default()),
+            flushing: Flushing::new(),
+            auth: None,
+            proxy: ProxyConfig::default(),
+            redirect_uri: None,
+            cache: None,
+            stats_posted: None,
+            pending_tags: Vec::new(),
+            segment_sent: false,
+            info: SharedInfo::default(),
             cat: gst::DebugCategory::new(
                 "rshttpsrc",
                 gst::DebugColorFlags::empty(),
@@ -58,16 +377,85 @@ impl HttpSrc {
         Box::new(HttpSrc::new(src))
     }
 
-    fn do_request(
-        &self,
+    // Serves reads at any offset from a block cache if the server supports
+    // range requests, which allows pull mode
+    pub fn new_pull(src: &RsBaseSrc) -> HttpSrc {
+        let mut httpsrc = HttpSrc::new(src);
+        httpsrc.cache = Some(BlockCache::new(
+            u64::from(DEFAULT_BLOCK_SIZE),
+            (DEFAULT_CACHE_SIZE / DEFAULT_BLOCK_SIZE) as usize,
+        ));
+        httpsrc
+    }
+
+    pub fn new_pull_boxed(src: &RsBaseSrc) -> Box<SourceImpl> {
+        Box::new(HttpSrc::new_pull(src))
+    }
+
+    // Headers sent with every request, including the ones after seeking
+    fn request_headers(&self, src: &RsBaseSrc) -> Headers {
+        let mut headers = Headers::new();
+
+        if !self.settings.keep_alive {
+            headers.set(Connection::close());
+        }
+
+        self.settings.client.set_headers(&mut headers);
+
+        if self.settings.iradio_mode {
+            headers.set_raw("Icy-MetaData", "1");
+        }
+
+        self.settings
+            .request
+            .set_headers(self.cat, &src.clone().upcast(), &mut headers);
+
+        headers
+    }
+
+    fn create_client(&self, src: &RsBaseSrc, config: ProxyConfig) -> Result<Client, ErrorMessage> {
+        // Both timeouts are enforced by us while waiting
+        let timeout = if self.settings.request.timeout != 0 && self.settings.connect_timeout != 0 {
+            Some(Duration::from_secs(u64::from(cmp::max(
+                self.settings.request.timeout,
+                self.settings.connect_timeout,
+            ))))
+        } else {
+            None
+        };
+
+        self.settings.client.create_client(
+            self.cat,
+            &src.clone().upcast(),
+            config,
+            self.settings.automatic_redirect,
+            timeout,
+        )
+    }
+
+    fn build_request(
+        &mut self,
         src: &RsBaseSrc,
-        uri: Url,
+        uri: &Url,
         start: u64,
         stop: Option<u64>,
-    ) -> Result<StreamingState, ErrorMessage> {
-        let cat = self.cat;
+        credentials: Option<&(String, String)>,
+    ) -> RequestBuilder {
         let mut req = self.client.get(uri.clone());
 
+        let mut headers = self.request_headers(src);
+        if let Some(authorization) = self.proxy.authorization_for_url(uri) {
+            headers.set_raw("Proxy-Authorization", authorization);
+        }
+        if let (Some(auth), Some(&(ref user, ref password))) = (self.auth.as_mut(), credentials) {
+            let target = &uri[Position::BeforePath..];
+            headers.set_raw(
+                "Authorization",
+                auth.authorization(user, password, "GET", target),
+            );
+        }
+        req.headers(headers);
+
         match (start != 0, stop) {
             (false, None) => (),
             (true, None) => {
@@ -78,33 +466,300 @@ impl HttpSrc {
             }
         }
 
-        gst_debug!(cat, obj: src, "Doing new request {:?}", req);
+        gst_debug!(self.cat, obj: src, "Doing new request {:?}", req);
 
-        let response = try!(req.send().or_else(|err| {
-            gst_error!(cat, obj: src, "Request failed: {:?}", err);
-            Err(error_msg!(
+        req
+    }
+
+    fn request_error(&self, src: &RsBaseSrc, uri: &Url, err: SendError) -> ErrorMessage {
+        gst_error!(self.cat, obj: src, "Request failed: {:?}", err);
+
+        let err = match err {
+            SendError::Request(err) => err,
+            SendError::TimedOut => {
+                return error_msg!(
+                    gst::ResourceError::Read,
+                    ("Timed out waiting for {}", uri),
+                    [
+                        "No response in time (connect-timeout is {}s, timeout is {}s)",
+                        self.settings.connect_timeout,
+                        self.settings.request.timeout
+                    ]
+                );
+            }
+            err => {
+                return error_msg!(
+                    gst::ResourceError::Read,
+                    ["Failed to fetch {}: {}", uri, err]
+                );
+            }
+        };
+
+        if err.is_redirect() {
+            error_msg!(
+                gst::ResourceError::Read,
+                ("Too many redirects for {}", uri),
+                [
+                    "{} (max-redirects is {})",
+                    err.to_string(),
+                    self.settings.client.max_redirects
+                ]
+            )
+        } else if tls::is_certificate_error(&err) {
+            error_msg!(
+                gst::ResourceError::OpenRead,
+                ("Failed to verify the server certificate of {}", uri),
+                [
+                    "{} (use ssl-ca-file for private CAs, or disable ssl-strict)",
+                    err.to_string()
+                ]
+            )
+        } else {
+            error_msg!(
                 gst::ResourceError::Read,
                 ["Failed to fetch {}: {}", uri, err.to_string()]
-            ))
-        }));
+            )
+        }
+    }
+
+    fn send_request(
+        &mut self,
+        src: &RsBaseSrc,
+        uri: &Url,
+        start: u64,
+        stop: Option<u64>,
+        credentials: Option<&(String, String)>,
+    ) -> Result<Response, ErrorMessage> {
+        let req = self.build_request(src, uri, start, stop, credentials);
+
+        let timeout = if self.settings.connect_timeout != 0 {
+            Some(Duration::from_secs(u64::from(self.settings.connect_timeout)))
+        } else {
+            None
+        };
+        let request_start = Instant::now();
+
+        let response = try!(
+            request::send(req, &self.flushing, timeout)
+                .map_err(|err| self.request_error(src, uri, err))
+        );
+
+        self.info.lock().stats.add_request(request_start.elapsed());
+
+        Ok(response)
+    }
+
+    // Credentials are only ever sent in the Authorization header, so they are
+    // removed from the URI that is requested
+    fn request_uri(&self, uri: &Url) -> (Url, Option<(String, String)>) {
+        let credentials = auth::credentials(
+            uri,
+            self.settings.request.user_id.as_ref().map(String::as_str),
+            self.settings.request.user_pw.as_ref().map(String::as_str),
+        );
+        let mut request_uri = uri.clone();
+        let _ = request_uri.set_username("");
+        let _ = request_uri.set_password(None);
+
+        (request_uri, credentials)
+    }
+
+    // Reads a range completely with a request on the pooled client, without
+    // touching the streaming state, redirect messages or tags
+    fn fetch_range(
+        &mut self,
+        src: &RsBaseSrc,
+        uri: &Url,
+        start: u64,
+        stop: u64,
+    ) -> Result<Vec<u8>, ErrorMessage> {
+        let cat = self.cat;
+        let (request_uri, credentials) = self.request_uri(uri);
+
+        // The whole range has to arrive before both timeouts passed
+        let timeout = match (self.settings.connect_timeout, self.settings.request.timeout) {
+            (0, _) | (_, 0) => None,
+            (connect_timeout, timeout) => Some(Duration::from_secs(
+                u64::from(connect_timeout) + u64::from(timeout),
+            )),
+        };
+
+        let mut authenticated = false;
+        loop {
+            let req =
+                self.build_request(src, &request_uri, start, Some(stop), credentials.as_ref());
+            let request_start = Instant::now();
+
+            let (response, data) = try!(
+                request::fetch(req, &self.flushing, timeout)
+                    .map_err(|err| self.request_error(src, &request_uri, err))
+            );
+
+            self.info.lock().stats.add_request(request_start.elapsed());
+
+            // A stale Digest nonce needs a new challenge, as in do_request()
+            if response.status() == StatusCode::Unauthorized && credentials.is_some()
+                && !authenticated
+            {
+                if let Some(challenge) = auth::response_challenge(&response) {
+                    gst_debug!(cat, obj: src, "Authenticating with {:?}", challenge);
+                    self.auth = Some(challenge);
+                    authenticated = true;
+                    continue;
+                }
+            }
+
+            if !response.status().is_success() {
+                gst_error!(cat, obj: src, "Request status failed: {:?}", response);
+                return Err(status::status_error(
+                    response.status(),
+                    &request_uri,
+                    credentials.is_some(),
+                    gst::ResourceError::Read,
+                ));
+            }
+
+            let range_start = match response.headers().get() {
+                Some(&ContentRange(ContentRangeSpec::Bytes {
+                    range: Some((range_start, _)),
+                    ..
+                })) => Some(range_start),
+                _ => None,
+            };
+
+            if response.status() != StatusCode::PartialContent || range_start != Some(start)
+                || data.len() as u64 != stop - start
+            {
+                gst_error!(cat, obj: src, "Unexpected range response: {:?}", response);
+                return Err(error_msg!(
+                    gst::ResourceError::Seek,
+                    [
+                        "Failed to fetch {}-{}: Got {} bytes at {:?} ({})",
+                        start,
+                        stop,
+                        data.len(),
+                        range_start,
+                        response.status()
+                    ]
+                ));
+            }
+
+            return Ok(data);
+        }
+    }
+
+    fn do_request(
+        &mut self,
+        src: &RsBaseSrc,
+        uri: Url,
+        start: u64,
+        stop: Option<u64>,
+    ) -> Result<StreamingState, ErrorMessage> {
+        let cat = self.cat;
+        let (request_uri, credentials) = self.request_uri(&uri);
+
+        let mut response = try!(self.send_request(
+            src,
+            &request_uri,
+            start,
+            stop,
+            credentials.as_ref()
+        ));
+
+        // Retry once with the new challenge, e.g. for the first request or if
+        // the Digest nonce is stale
+        if response.status() == StatusCode::Unauthorized && credentials.is_some() {
+            if let Some(challenge) = auth::response_challenge(&response) {
+                gst_debug!(cat, obj: src, "Authenticating with {:?}", challenge);
+                self.auth = Some(challenge);
+
+                response = try!(self.send_request(
+                    src,
+                    &request_uri,
+                    start,
+                    stop,
+                    credentials.as_ref()
+                ));
+            }
+        }
+
+        if response.status().is_redirection() {
+            let location = response
+                .headers()
+                .get()
+                .and_then(|location: &Location| request_uri.join(location).ok());
+
+            if let Some(location) = location {
+                gst_error!(cat, obj: src, "Not following redirect to {}", location);
+                self.post_redirect(src, &location);
+                return Err(error_msg!(
+                    gst::ResourceError::Read,
+                    ("Redirected to {}", location),
+                    [
+                        "HTTP status code {} for {} and automatic-redirect is disabled",
+                        response.status().as_u16(),
+                        request_uri
+                    ]
+                ));
+            }
+        }
 
         if !response.status().is_success() {
             gst_error!(cat, obj: src, "Request status failed: {:?}", response);
-            return Err(error_msg!(
+            return Err(status::status_error(
+                response.status(),
+                &request_uri,
+                credentials.is_some(),
                 gst::ResourceError::Read,
-                ["Failed to fetch {}: {}", uri, response.status()]
             ));
         }
 
-        let size = response
+        if *response.url() != request_uri
+            && self.redirect_uri.as_ref() != Some(response.url())
+        {
+            gst_debug!(cat, obj: src, "Redirected to {}", response.url());
+            self.redirect_uri = Some(response.url().clone());
+            self.post_redirect(src, response.url());
+        }
+
+        let encodings = response
             .headers()
             .get()
-            .map(|&ContentLength(cl)| cl + start);
+            .map(|&ContentEncoding(ref encodings)| encodings.clone())
+            .unwrap_or_default();
+        let compressed = encoding::is_compressed(&encodings);
+
+        // For range requests the Content-Length is only the requested part,
+        // and for compressed responses it's not the size of the content
+        let size = match response.headers().get() {
+            _ if compressed => None,
+            Some(&ContentRange(ContentRangeSpec::Bytes {
+                instance_length: Some(length),
+                ..
+            })) => Some(length),
+            _ => response
+                .headers()
+                .get()
+                .map(|&ContentLength(cl)| cl + start),
+        };
+
+        // Servers ignoring the Range header reply with the whole content
+        if start != 0 && response.status() != StatusCode::PartialContent {
+            gst_error!(cat, obj: src, "Range request not supported: {:?}", response);
+            return Err(error_msg!(
+                gst::ResourceError::Seek,
+                [
+                    "Failed to seek to {}: Server does not support range requests ({})",
+                    start,
+                    response.status()
+                ]
+            ));
+        }
 
         let accept_byte_ranges = if let Some(&AcceptRanges(ref ranges)) = response.headers().get() {
             ranges.iter().any(|u| *u == RangeUnit::Bytes)
         } else {
-            false
+            response.status() == StatusCode::PartialContent
         };
 
         let seekable = size.is_some() && accept_byte_ranges;
@@ -128,18 +783,412 @@ impl HttpSrc {
             ));
         }
 
+        let content_type = response
+            .headers()
+            .get()
+            .map(|&ContentType(ref mime)| mime.to_string());
+        let caps = response
+            .headers()
+            .get()
+            .and_then(|&ContentType(ref mime)| {
+                caps_from_content_type(&format!("{}/{}", mime.type_(), mime.subtype()))
+            });
+
+        let metadata_interval = response
+            .headers()
+            .get_raw("icy-metaint")
+            .and_then(|raw| raw.one())
+            .and_then(|value| str::from_utf8(value).ok())
+            .and_then(|value| value.trim().parse::<usize>().ok())
+            .and_then(|interval| if interval > 0 { Some(interval) } else { None });
+
+        // Metadata is either removed here, or left for a downstream icydemux
+        let (caps, icy) = match metadata_interval {
+            Some(interval) if self.settings.strip_icy_metadata => {
+                gst_debug!(cat, obj: src, "Stripping ICY metadata every {} bytes", interval);
+                (caps, Some(IcyReader::new(interval)))
+            }
+            Some(interval) => (
+                Some(gst::Caps::new_simple(
+                    "application/x-icy",
+                    &[("metadata-interval", &(interval as i32))],
+                )),
+                None,
+            ),
+            None => (caps, None),
+        };
+
         gst_debug!(cat, obj: src, "Request successful: {:?}", response);
 
+        let timeout = if self.settings.request.timeout != 0 {
+            Some(Duration::from_secs(u64::from(self.settings.request.timeout)))
+        } else {
+            None
+        };
+
+        let final_uri = response.url().clone();
+        let mut tags = gst::TagList::new();
+        tags.get_mut()
+            .unwrap()
+            .add::<gst::tags::Location>(&final_uri.as_str(), gst::TagMergeMode::Replace);
+
+        let reader = try!(encoding::decoder(Box::new(response), &encodings).or_else(|err| {
+            gst_error!(cat, obj: src, "Can't decode response: {}", err);
+            Err(error_msg!(
+                gst::ResourceError::Read,
+                ["Failed to fetch {}: {}", request_uri, err]
+            ))
+        }));
+
+        let body = try!(
+            BodyReader::new(reader, self.flushing.clone(), timeout).or_else(|err| {
+                gst_error!(cat, obj: src, "Failed to start reading thread: {:?}", err);
+                Err(error_msg!(
+                    gst::LibraryError::Failed,
+                    ["Failed to start reading thread: {}", err.to_string()]
+                ))
+            })
+        );
+
+        {
+            let mut info = self.info.lock();
+            info.content_type = content_type;
+            info.final_uri = Some(final_uri.to_string());
+            info.size = size;
+        }
+
         Ok(StreamingState::Started {
             uri: uri,
-            response: response,
-            seekable: seekable,
-            position: 0,
+            body: body,
+            seekable: seekable && metadata_interval.is_none(),
+            position: start,
             size: size,
             start: start,
             stop: stop,
+            caps: caps,
+            tags: Some(tags),
+            icy: icy,
         })
     }
+
+    // Posted at most once per stats-interval while data is received
+    fn post_stats(&mut self, src: &RsBaseSrc) {
+        if self.settings.stats_interval == 0 {
+            return;
+        }
+
+        let now = Instant::now();
+        if let Some(posted) = self.stats_posted {
+            let elapsed = now.duration_since(posted);
+            let elapsed = elapsed.as_secs() * gst::SECOND + u64::from(elapsed.subsec_nanos());
+            if elapsed < self.settings.stats_interval {
+                return;
+            }
+        }
+
+        self.stats_posted = Some(now);
+        let s = self.info.lock().stats.to_structure(now);
+        src.post_message(&gst::Message::new_element(s).src(Some(src)).build());
+    }
+
+    // Tag events must not be sent before the segment, which the base class
+    // only sends together with the first buffer
+    fn push_pending_tags(&mut self, src: &RsBaseSrc) {
+        if !self.segment_sent || self.pending_tags.is_empty() {
+            return;
+        }
+
+        let pad = src.get_static_pad("src").unwrap();
+        for tags in self.pending_tags.drain(..) {
+            gst_debug!(self.cat, obj: src, "Sending tags {:?}", tags);
+            pad.push_event(gst::Event::new_tag(tags).build());
+        }
+    }
+
+    fn post_redirect(&self, src: &RsBaseSrc, location: &Url) {
+        let s = gst::Structure::new("redirect", &[("new-location", &location.as_str())]);
+        src.post_message(&gst::Message::new_element(s).src(Some(src)).build());
+    }
+
+    // Re-requests everything from the current position, which fails if the
+    // server does not support range requests
+    fn reconnect(&mut self, src: &RsBaseSrc, retry: &mut u32) -> Result<(), FlowError> {
+        let cat = self.cat;
+
+        let (uri, position, stop, seekable) = match self.streaming_state {
+            StreamingState::Started {
+                ref uri,
+                position,
+                stop,
+                seekable,
+                ..
+            } => (uri.clone(), position, stop, seekable),
+            StreamingState::Stopped => {
+                return Err(FlowError::Error(
+                    error_msg!(gst::LibraryError::Failed, ["Not started yet"]),
+                ));
+            }
+        };
+
+        if position != 0 && !seekable {
+            gst_error!(cat, obj: src, "Can't resume at {} without range requests", position);
+            return Err(FlowError::Error(error_msg!(
+                gst::ResourceError::Read,
+                (
+                    "Connection lost and the server does not support resuming at {}",
+                    position
+                ),
+                ["Server does not support range requests"]
+            )));
+        }
+
+        loop {
+            *retry += 1;
+
+            gst_debug!(cat, obj: src, "Reconnecting at {}", position);
+            try!(self.wait_retry(src, *retry));
+
+            match self.do_request(src, uri.clone(), position, stop) {
+                Ok(state) => {
+                    self.streaming_state = state;
+                    return Ok(());
+                }
+                Err(_) if self.flushing.is_flushing() => return Err(FlowError::Flushing),
+                Err(msg) => {
+                    if *retry >= self.settings.request.retries {
+                        return Err(FlowError::Error(msg));
+                    }
+
+                    gst_warning!(cat, obj: src, "Reconnecting failed: {:?}", msg);
+                }
+            }
+        }
+    }
+
+    // Exponential backoff before the given retry, interrupted by flushing
+    fn wait_retry(&self, src: &RsBaseSrc, retry: u32) -> Result<(), FlowError> {
+        if !self.settings.request.wait_retry(
+            self.cat,
+            &src.clone().upcast(),
+            &self.flushing,
+            retry,
+        ) {
+            return Err(FlowError::Flushing);
+        }
+
+        Ok(())
+    }
+
+    // Reads and drops data up to the target offset on the current connection
+    fn skip(&mut self, src: &RsBaseSrc, target: u64) -> bool {
+        let cat = self.cat;
+
+        let (body, position) = match self.streaming_state {
+            StreamingState::Started {
+                ref mut body,
+                ref mut position,
+                icy: None,
+                ..
+            } => (body, position),
+            _ => return false,
+        };
+
+        let mut scratch = vec![0; 64 * 1024];
+        while *position < target {
+            let len = cmp::min(scratch.len() as u64, target - *position) as usize;
+            match body.read(&mut scratch[..len]) {
+                Ok(0) => return false,
+                Ok(size) => *position += size as u64,
+                Err(err) => {
+                    gst_debug!(cat, obj: src, "Failed to skip to {}: {:?}", target, err);
+                    return false;
+                }
+            }
+        }
+
+        gst_debug!(cat, obj: src, "Skipped to {}", target);
+
+        true
+    }
+
+    // Only connections of completely read responses are reused, so a small
+    // rest is read instead of closing the connection
+    fn drain(&mut self, src: &RsBaseSrc) {
+        let cat = self.cat;
+
+        if let StreamingState::Started {
+            ref mut body,
+            position,
+            stop,
+            size,
+            icy: None,
+            ..
+        } = self.streaming_state
+        {
+            let remaining = stop.or(size).map(|end| end.saturating_sub(position));
+            if remaining.map_or(false, |remaining| remaining <= MAX_SKIP_SIZE) {
+                match body.drain(MAX_SKIP_SIZE) {
+                    Ok(true) => gst_debug!(cat, obj: src, "Drained the rest of the response"),
+                    Ok(false) => (),
+                    Err(err) => gst_debug!(cat, obj: src, "Failed to drain: {:?}", err),
+                }
+            }
+        }
+    }
+
+    // Blocks that directly follow the current position are read from the
+    // current connection, everything else with a separate range request
+    fn fetch_blocks(
+        &mut self,
+        src: &RsBaseSrc,
+        first: u64,
+        last: u64,
+    ) -> Result<Vec<Vec<u8>>, FlowError> {
+        let cat = self.cat;
+        let block_size = self.cache.as_ref().unwrap().block_size();
+
+        let (uri, size) = match self.streaming_state {
+            StreamingState::Started {
+                ref uri,
+                size: Some(size),
+                ..
+            } => (uri.clone(), size),
+            _ => {
+                return Err(FlowError::Error(
+                    error_msg!(gst::LibraryError::Failed, ["Not started yet"]),
+                ));
+            }
+        };
+
+        let start = first * block_size;
+        let stop = cmp::min((last + 1) * block_size, size);
+
+        if let StreamingState::Started {
+            ref mut body,
+            ref mut position,
+            icy: None,
+            ..
+        } = self.streaming_state
+        {
+            if *position == start {
+                match cache::read_blocks(body, position, stop, block_size) {
+                    Ok(blocks) => return Ok(blocks),
+                    Err(_) if self.flushing.is_flushing() => return Err(FlowError::Flushing),
+                    Err(err) => {
+                        gst_debug!(cat, obj: src, "Failed to read at {}: {:?}", start, err);
+                    }
+                }
+            }
+        }
+
+        gst_debug!(cat, obj: src, "Requesting blocks {}-{}", first, last);
+
+        let mut retry = 0;
+        loop {
+            let res = self.fetch_range(src, &uri, start, stop).map(|data| {
+                data.chunks(block_size as usize)
+                    .map(|block| block.to_vec())
+                    .collect()
+            });
+
+            match res {
+                Ok(blocks) => return Ok(blocks),
+                Err(_) if self.flushing.is_flushing() => return Err(FlowError::Flushing),
+                Err(msg) => {
+                    if retry >= self.settings.request.retries {
+                        gst_error!(cat, obj: src, "Failed to fetch blocks: {:?}", msg);
+                        return Err(FlowError::Error(msg));
+                    }
+
+                    gst_warning!(cat, obj: src, "Failed to fetch blocks: {:?}", msg);
+                    retry += 1;
+                    try!(self.wait_retry(src, retry));
+                }
+            }
+        }
+    }
+
+    fn fill_from_cache(
+        &mut self,
+        src: &RsBaseSrc,
+        offset: u64,
+        length: u32,
+        buffer: &mut gst::BufferRef,
+    ) -> Result<(), FlowError> {
+        let size = match self.streaming_state {
+            StreamingState::Started { size: Some(size), .. } => size,
+            _ => {
+                return Err(FlowError::Error(
+                    error_msg!(gst::LibraryError::Failed, ["Not started yet"]),
+                ));
+            }
+        };
+
+        if offset >= size {
+            return Err(FlowError::Eos);
+        }
+
+        let end = cmp::min(offset + u64::from(length), size);
+        let block_size = self.cache.as_ref().unwrap().block_size();
+        let first = offset / block_size;
+        let last = (end - 1) / block_size;
+
+        {
+            let mut map = match buffer.map_writable() {
+                None => {
+                    return Err(FlowError::Error(error_msg!(
+                        gst::LibraryError::Failed,
+                        ["Failed to map buffer"]
+                    )));
+                }
+                Some(map) => map,
+            };
+            let data = map.as_mut_slice();
+
+            // Copies the part of a block that is inside the requested range
+            let copy_block = |data: &mut [u8], index: u64, block: &[u8]| {
+                let block_start = index * block_size;
+                let from = cmp::max(offset, block_start);
+                let to = cmp::min(end, block_start + block.len() as u64);
+                if from < to {
+                    data[((from - offset) as usize)..((to - offset) as usize)].copy_from_slice(
+                        &block[((from - block_start) as usize)..((to - block_start) as usize)],
+                    );
+                }
+            };
+
+            let mut index = first;
+            while index <= last {
+                if let Some(block) = self.cache.as_mut().unwrap().get(index) {
+                    copy_block(data, index, block);
+                    index += 1;
+                    continue;
+                }
+
+                // Missing blocks next to each other are fetched together
+                let mut run_end = index;
+                while run_end < last && !self.cache.as_ref().unwrap().contains(run_end + 1) {
+                    run_end += 1;
+                }
+
+                let blocks = try!(self.fetch_blocks(src, index, run_end));
+                let received = blocks.iter().map(|block| block.len() as u64).sum();
+                self.info.lock().stats.add_bytes(received, Instant::now());
+
+                let cache = self.cache.as_mut().unwrap();
+                for block in blocks {
+                    copy_block(data, index, &block);
+                    cache.insert(index, block);
+                    index += 1;
+                }
+            }
+        }
+
+        buffer.set_size((end - offset) as usize);
+        self.post_stats(src);
+
+        Ok(())
+    }
 }
 
 fn validate_uri(uri: &Url) -> Result<(), UriError> {
@@ -153,11 +1202,42 @@ fn validate_uri(uri: &Url) -> Result<(), UriError> {
     Ok(())
 }
 
+fn caps_from_content_type(content_type: &str) -> Option<gst::Caps> {
+    match content_type {
+        "video/x-flv" => Some(gst::Caps::new_simple("video/x-flv", &[])),
+        "video/mp2t" => Some(gst::Caps::new_simple(
+            "video/mpegts",
+            &[("systemstream", &true)],
+        )),
+        "application/ogg" | "audio/ogg" | "video/ogg" => {
+            Some(gst::Caps::new_simple("application/ogg", &[]))
+        }
+        "audio/webm" | "video/webm" => Some(gst::Caps::new_simple("video/webm", &[])),
+        "audio/flac" | "audio/x-flac" => Some(gst::Caps::new_simple("audio/x-flac", &[])),
+        "application/vnd.apple.mpegurl" | "application/x-mpegurl" => {
+            Some(gst::Caps::new_simple("application/x-hls", &[]))
+        }
+        _ => None,
+    }
+}
+
 impl SourceImpl for HttpSrc {
     fn uri_validator(&self) -> Box<UriValidator> {
         Box::new(validate_uri)
     }
 
+    fn unlock_handle(&self) -> Option<Box<Unlock>> {
+        Some(Box::new(self.flushing.clone()))
+    }
+
+    fn property_handle(&self) -> Option<Box<PropertyHandle>> {
+        Some(Box::new((self.shared_settings.clone(), self.info.clone())))
+    }
+
+    fn query_handle(&self) -> Option<Box<QueryHandle>> {
+        Some(Box::new(self.info.clone()))
+    }
+
     fn is_seekable(&self, _src: &RsBaseSrc) -> bool {
         match self.streaming_state {
             StreamingState::Started { seekable, .. } => seekable,
@@ -172,8 +1252,34 @@ impl SourceImpl for HttpSrc {
         }
     }
 
+    fn get_caps(&self, _src: &RsBaseSrc) -> Option<gst::Caps> {
+        match self.streaming_state {
+            StreamingState::Started { ref caps, .. } => caps.clone(),
+            _ => None,
+        }
+    }
+
     fn start(&mut self, src: &RsBaseSrc, uri: Url) -> Result<(), ErrorMessage> {
+        self.shared_settings.update(&mut self.settings);
         self.streaming_state = StreamingState::Stopped;
+        self.auth = None;
+        self.redirect_uri = None;
+        self.info.lock().stats = Stats::new();
+        self.stats_posted = None;
+        self.pending_tags.clear();
+        self.segment_sent = false;
+        if self.cache.is_some() {
+            self.cache = Some(BlockCache::new(
+                u64::from(self.settings.block_size),
+                (self.settings.cache_size / self.settings.block_size) as usize,
+            ));
+        }
+        self.proxy = try!(
+            self.settings
+                .client
+                .proxy_config(self.cat, &src.clone().upcast())
+        );
+        self.client = try!(self.create_client(src, self.proxy.clone()));
         self.streaming_state = try!(self.do_request(src, uri, 0, None));
 
         Ok(())
@@ -181,11 +1287,22 @@ impl SourceImpl for HttpSrc {
 
     fn stop(&mut self, _src: &RsBaseSrc) -> Result<(), ErrorMessage> {
         self.streaming_state = StreamingState::Stopped;
+        {
+            let mut info = self.info.lock();
+            info.content_type = None;
+            info.final_uri = None;
+            info.size = None;
+        }
+        if let Some(ref mut cache) = self.cache {
+            cache.clear();
+        }
 
         Ok(())
     }
 
     fn seek(&mut self, src: &RsBaseSrc, start: u64, stop: Option<u64>) -> Result<(), ErrorMessage> {
+        self.shared_settings.update(&mut self.settings);
+
         let (position, old_stop, uri) = match self.streaming_state {
             StreamingState::Started {
                 position,
@@ -202,7 +1319,18 @@ impl SourceImpl for HttpSrc {
             return Ok(());
         }
 
+        if old_stop == stop && start > position && start - position <= MAX_SKIP_SIZE
+            && self.skip(src, start)
+        {
+            return Ok(());
+        }
+
+        if self.settings.keep_alive {
+            self.drain(src);
+        }
+
         self.streaming_state = StreamingState::Stopped;
+        self.segment_sent = false;
         self.streaming_state = try!(self.do_request(src, uri, start, stop));
 
         Ok(())
@@ -212,61 +1340,118 @@ impl SourceImpl for HttpSrc {
         &mut self,
         src: &RsBaseSrc,
         offset: u64,
-        _: u32,
+        length: u32,
         buffer: &mut gst::BufferRef,
     ) -> Result<(), FlowError> {
         let cat = self.cat;
+        let mut retry = 0;
 
-        let (response, position) = match self.streaming_state {
-            StreamingState::Started {
-                ref mut response,
-                ref mut position,
-                ..
-            } => (response, position),
-            StreamingState::Stopped => {
-                return Err(FlowError::Error(
-                    error_msg!(gst::LibraryError::Failed, ["Not started yet"]),
-                ));
-            }
+        self.shared_settings.update(&mut self.settings);
+
+        let tags = match self.streaming_state {
+            StreamingState::Started { ref mut tags, .. } => tags.take(),
+            StreamingState::Stopped => None,
         };
+        self.pending_tags.extend(tags);
+        self.push_pending_tags(src);
 
-        if *position != offset {
-            return Err(FlowError::Error(error_msg!(
-                gst::ResourceError::Seek,
-                ["Got unexpected offset {}, expected {}", offset, position]
-            )));
+        if self.cache.is_some() && self.is_seekable(src) {
+            try!(self.fill_from_cache(src, offset, length, buffer));
+            self.segment_sent = true;
+            return Ok(());
         }
 
-        let size = {
-            let mut map = match buffer.map_writable() {
-                None => {
+        loop {
+            let res = {
+                let (body, position, end, icy) = match self.streaming_state {
+                    StreamingState::Started {
+                        ref mut body,
+                        ref mut position,
+                        stop,
+                        size,
+                        ref mut icy,
+                        ..
+                    } => (body, position, stop.or(size), icy),
+                    StreamingState::Stopped => {
+                        return Err(FlowError::Error(
+                            error_msg!(gst::LibraryError::Failed, ["Not started yet"]),
+                        ));
+                    }
+                };
+
+                if *position != offset {
                     return Err(FlowError::Error(error_msg!(
-                        gst::LibraryError::Failed,
-                        ["Failed to map buffer"]
+                        gst::ResourceError::Seek,
+                        ["Got unexpected offset {}, expected {}", offset, position]
                     )));
                 }
-                Some(map) => map,
-            };
 
-            let data = map.as_mut_slice();
+                let mut map = match buffer.map_writable() {
+                    None => {
+                        return Err(FlowError::Error(error_msg!(
+                            gst::LibraryError::Failed,
+                            ["Failed to map buffer"]
+                        )));
+                    }
+                    Some(map) => map,
+                };
 
-            try!(response.read(data).or_else(|err| {
-                gst_error!(cat, obj: src, "Failed to read: {:?}", err);
-                Err(FlowError::Error(error_msg!(
-                    gst::ResourceError::Read,
-                    ["Failed to read at {}: {}", offset, err.to_string()]
-                )))
-            }))
-        };
+                let data = map.as_mut_slice();
 
-        if size == 0 {
-            return Err(FlowError::Eos);
-        }
+                let res = match *icy {
+                    Some(ref mut icy) => icy.read(body, data),
+                    None => body.read(data).map(|size| (size, None)),
+                };
+
+                match res {
+                    Ok((0, _)) if end.map_or(false, |end| *position < end) => Err(io::Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "Connection closed before the end",
+                    )),
+                    Ok((size, metadata)) => {
+                        *position += size as u64;
+                        Ok((size, metadata))
+                    }
+                    Err(err) => Err(err),
+                }
+            };
 
-        *position += size as u64;
+            match res {
+                Ok((size, metadata)) => {
+                    if let Some(tags) = metadata.and_then(|metadata| metadata.to_tags()) {
+                        gst_debug!(cat, obj: src, "Got ICY metadata {:?}", tags);
+                        self.pending_tags.push(tags);
+                    }
+                    self.push_pending_tags(src);
 
-        buffer.set_size(size);
+                    if size == 0 {
+                        return Err(FlowError::Eos);
+                    }
 
-        Ok(())
+                    buffer.set_size(size);
+                    self.segment_sent = true;
+                    self.info.lock().stats.add_bytes(size as u64, Instant::now());
+                    self.post_stats(src);
+
+                    return Ok(());
+                }
+                Err(_) if self.flushing.is_flushing() => {
+                    gst_debug!(cat, obj: src, "Flushing");
+                    return Err(FlowError::Flushing);
+                }
+                Err(err) => {
+                    if retry >= self.settings.request.retries {
+                        gst_error!(cat, obj: src, "Failed to read: {:?}", err);
+                        return Err(FlowError::Error(error_msg!(
+                            gst::ResourceError::Read,
+                            ["Failed to read at {}: {}", offset, err.to_string()]
+                        )));
+                    }
+
+                    gst_warning!(cat, obj: src, "Failed to read at {}: {:?}", offset, err);
+                    try!(self.reconnect(src, &mut retry));
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,198 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::cmp;
+use std::io::{self, Read};
+
+use gst;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IcyMetadata {
+    pub title: Option<String>,
+    pub url: Option<String>,
+}
+
+impl IcyMetadata {
+    pub fn to_tags(&self) -> Option<gst::TagList> {
+        if self.title.is_none() && self.url.is_none() {
+            return None;
+        }
+
+        let mut tags = gst::TagList::new();
+        {
+            let tags = tags.get_mut().unwrap();
+            if let Some(ref title) = self.title {
+                tags.add::<gst::tags::Title>(&title.as_str(), gst::TagMergeMode::Replace);
+            }
+            if let Some(ref url) = self.url {
+                tags.add::<gst::tags::Homepage>(&url.as_str(), gst::TagMergeMode::Replace);
+            }
+        }
+
+        Some(tags)
+    }
+}
+
+// Metadata blocks are a length byte (in units of 16 bytes) followed by
+// "StreamTitle='...';StreamUrl='...';", padded with zeroes
+pub fn parse_metadata(data: &[u8]) -> IcyMetadata {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    let data = &data[..end];
+
+    // Not all servers use UTF-8, fall back to Latin-1 then
+    let s = match String::from_utf8(data.to_vec()) {
+        Ok(s) => s,
+        Err(_) => data.iter().map(|&b| b as char).collect(),
+    };
+
+    let mut metadata = IcyMetadata::default();
+    let mut rest = s.as_str();
+    while let Some(idx) = rest.find("='") {
+        let key = rest[..idx].trim();
+        let value_start = idx + 2;
+
+        // Values can contain quotes and semicolons themselves
+        let (value, next) = match rest[value_start..].find("';") {
+            Some(len) => (
+                &rest[value_start..(value_start + len)],
+                value_start + len + 2,
+            ),
+            None => (rest[value_start..].trim_right_matches('\''), rest.len()),
+        };
+
+        let value = if value.is_empty() {
+            None
+        } else {
+            Some(String::from(value))
+        };
+
+        match key {
+            "StreamTitle" => metadata.title = value,
+            "StreamUrl" => metadata.url = value,
+            _ => (),
+        }
+
+        rest = &rest[next..];
+    }
+
+    metadata
+}
+
+// Removes the metadata blocks that are inserted every interval bytes
+#[derive(Debug)]
+pub struct IcyReader {
+    interval: usize,
+    remaining: usize,
+}
+
+impl IcyReader {
+    pub fn new(interval: usize) -> IcyReader {
+        IcyReader {
+            interval: interval,
+            remaining: interval,
+        }
+    }
+
+    // Returns the metadata of a block that was skipped before the data, if any
+    pub fn read<R: Read>(
+        &mut self,
+        reader: &mut R,
+        data: &mut [u8],
+    ) -> io::Result<(usize, Option<IcyMetadata>)> {
+        let mut metadata = None;
+
+        if self.remaining == 0 {
+            let mut len = [0u8; 1];
+            if try!(reader.read(&mut len)) == 0 {
+                return Ok((0, None));
+            }
+
+            let mut block = vec![0; len[0] as usize * 16];
+            try!(reader.read_exact(&mut block));
+            if !block.is_empty() {
+                metadata = Some(parse_metadata(&block));
+            }
+
+            self.remaining = self.interval;
+        }
+
+        let len = cmp::min(data.len(), self.remaining);
+        let size = try!(reader.read(&mut data[..len]));
+        self.remaining -= size;
+
+        Ok((size, metadata))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_parse_metadata() {
+        assert_eq!(
+            parse_metadata(
+                b"StreamTitle='Artist - It's a Song';StreamUrl='http://example.com';\0\0"
+            ),
+            IcyMetadata {
+                title: Some(String::from("Artist - It's a Song")),
+                url: Some(String::from("http://example.com")),
+            }
+        );
+
+        assert_eq!(
+            parse_metadata(b"StreamTitle='';\0"),
+            IcyMetadata {
+                title: None,
+                url: None,
+            }
+        );
+
+        assert_eq!(
+            parse_metadata(b"StreamTitle='Caf\xe9';"),
+            IcyMetadata {
+                title: Some(String::from("Café")),
+                url: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_icy_reader() {
+        let mut stream = Vec::new();
+        stream.extend_from_slice(b"abcd");
+        stream.push(1);
+        let mut block = b"StreamTitle='x';".to_vec();
+        block.resize(16, 0);
+        stream.extend_from_slice(&block);
+        stream.extend_from_slice(b"efgh");
+        stream.push(0);
+        stream.extend_from_slice(b"ij");
+
+        let mut cursor = Cursor::new(stream);
+        let mut reader = IcyReader::new(4);
+        let mut data = [0u8; 16];
+        let mut output = Vec::new();
+        let mut titles = Vec::new();
+
+        loop {
+            let (size, metadata) = reader.read(&mut cursor, &mut data).unwrap();
+            if let Some(metadata) = metadata {
+                titles.push(metadata.title);
+            }
+            if size == 0 {
+                break;
+            }
+            output.extend_from_slice(&data[..size]);
+        }
+
+        assert_eq!(output, b"abcdefghij");
+        assert_eq!(titles, vec![Some(String::from("x"))]);
+    }
+}
@@ -11,15 +11,48 @@
 #[macro_use]
 extern crate gst_plugin;
 extern crate gst_plugin_simple;
+extern crate glib;
 #[macro_use]
 extern crate gstreamer as gst;
 extern crate reqwest;
+extern crate hyper;
+extern crate native_tls;
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "ios")))]
+extern crate openssl;
+extern crate md5;
+extern crate base64;
+extern crate flate2;
 extern crate url;
+extern crate aes;
+extern crate block_modes;
 
 use gst_plugin_simple::source::*;
+use gst_plugin_simple::sink::*;
 
+mod auth;
+mod body;
+mod cache;
+mod common;
+mod decrypt;
+mod encoding;
+mod hls;
+mod hlssrc;
+mod httpserversink;
+mod httpsink;
 mod httpsrc;
+mod icy;
+mod m3u8;
+mod proxy;
+mod request;
+mod server;
+mod stats;
+mod status;
+mod tls;
+mod upload;
 
+use hlssrc::HlsSrc;
+use httpserversink::HttpServerSink;
+use httpsink::HttpSink;
 use httpsrc::HttpSrc;
 
 fn plugin_init(plugin: &gst::Plugin) -> bool {
@@ -34,10 +67,73 @@ fn plugin_init(plugin: &gst::Plugin) -> bool {
             rank: 256 + 100,
             create_instance: HttpSrc::new_boxed,
             protocols: vec!["http".into(), "https".into()],
+            properties: httpsrc::properties(),
             push_only: true,
         },
     );
 
+    source_register(
+        plugin,
+        SourceInfo {
+            name: "rshttppullsrc".into(),
+            long_name: "HTTP/HTTPS Random Access Source".into(),
+            description: "Reads HTTP/HTTPS resources with range requests, also in pull mode".into(),
+            classification: "Source/File".into(),
+            author: "agent <agent@local>".into(),
+            rank: 0,
+            create_instance: HttpSrc::new_pull_boxed,
+            protocols: vec!["http".into(), "https".into()],
+            properties: httpsrc::pull_properties(),
+            push_only: false,
+        },
+    );
+
+    source_register(
+        plugin,
+        SourceInfo {
+            name: "rshlssrc".into(),
+            long_name: "HLS Source".into(),
+            description: "Reads HTTP Live Streaming streams".into(),
+            classification: "Source/Network".into(),
+            author: "agent <agent@local>".into(),
+            rank: 0,
+            create_instance: HlsSrc::new_boxed,
+            protocols: vec!["http".into(), "https".into()],
+            properties: hlssrc::properties(),
+            push_only: true,
+        },
+    );
+
+    sink_register(
+        plugin,
+        SinkInfo {
+            name: "rshttpsink".into(),
+            long_name: "HTTP/HTTPS Sink".into(),
+            description: "Uploads streams with HTTP/HTTPS PUT or POST requests".into(),
+            classification: "Sink/Network".into(),
+            author: "agent <agent@local>".into(),
+            rank: 0,
+            create_instance: HttpSink::new_boxed,
+            protocols: vec!["http".into(), "https".into()],
+            properties: httpsink::properties(),
+        },
+    );
+
+    sink_register(
+        plugin,
+        SinkInfo {
+            name: "rshttpserversink".into(),
+            long_name: "HTTP Server Sink".into(),
+            description: "Serves a live stream to HTTP clients".into(),
+            classification: "Sink/Network".into(),
+            author: "agent <agent@local>".into(),
+            rank: 0,
+            create_instance: HttpServerSink::new_boxed,
+            protocols: vec!["http".into()],
+            properties: httpserversink::PROPERTIES.to_vec(),
+        },
+    );
+
     true
 }
 
@@ -0,0 +1,665 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::HashMap;
+use std::time::Duration;
+use url::Url;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub uri: Url,
+    // In bits per second
+    pub bandwidth: u64,
+    pub resolution: Option<(u32, u32)>,
+    pub codecs: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MasterPlaylist {
+    pub variants: Vec<Variant>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ByteRange {
+    pub offset: u64,
+    pub length: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Key {
+    pub uri: Url,
+    // Either from the playlist or the media sequence number of the segment
+    pub iv: [u8; 16],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub uri: Url,
+    pub duration: Duration,
+    pub sequence: u64,
+    pub discontinuity: bool,
+    pub discontinuity_sequence: u64,
+    pub byte_range: Option<ByteRange>,
+    pub key: Option<Key>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaPlaylist {
+    pub target_duration: Duration,
+    pub media_sequence: u64,
+    pub discontinuity_sequence: u64,
+    pub segments: Vec<Segment>,
+    pub end_list: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Playlist {
+    Master(MasterPlaylist),
+    Media(MediaPlaylist),
+}
+
+impl MediaPlaylist {
+    pub fn segment(&self, sequence: u64) -> Option<&Segment> {
+        if sequence < self.media_sequence {
+            return None;
+        }
+
+        self.segments.get((sequence - self.media_sequence) as usize)
+    }
+
+    // Sequence number after the last segment
+    pub fn end_sequence(&self) -> u64 {
+        self.media_sequence + self.segments.len() as u64
+    }
+}
+
+// Attribute lists are comma separated KEY=VALUE pairs, where quoted string
+// values can contain commas
+fn parse_attributes(s: &str) -> Result<HashMap<String, String>, String> {
+    let mut attributes = HashMap::new();
+    let mut rest = s.trim();
+
+    while !rest.is_empty() {
+        let eq = try!(
+            rest.find('=')
+                .ok_or_else(|| format!("Invalid attribute list '{}'", s))
+        );
+        let name = rest[..eq].trim();
+        rest = &rest[(eq + 1)..];
+
+        let value = if rest.starts_with('"') {
+            let end = try!(
+                rest[1..]
+                    .find('"')
+                    .ok_or_else(|| format!("Unterminated quoted string in '{}'", s))
+            );
+            let value = &rest[1..(end + 1)];
+            rest = &rest[(end + 2)..];
+            value
+        } else {
+            let end = rest.find(',').unwrap_or_else(|| rest.len());
+            let value = rest[..end].trim();
+            rest = &rest[end..];
+            value
+        };
+
+        attributes.insert(name.into(), value.into());
+
+        rest = rest.trim_left();
+        if rest.starts_with(',') {
+            rest = rest[1..].trim_left();
+        } else if !rest.is_empty() {
+            return Err(format!("Invalid attribute list '{}'", s));
+        }
+    }
+
+    Ok(attributes)
+}
+
+fn parse_integer(s: &str) -> Result<u64, String> {
+    s.trim()
+        .parse::<u64>()
+        .map_err(|_| format!("Invalid integer '{}'", s))
+}
+
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let secs = try!(
+        s.trim()
+            .parse::<f64>()
+            .map_err(|_| format!("Invalid duration '{}'", s))
+    );
+    if secs < 0.0 || !secs.is_finite() {
+        return Err(format!("Invalid duration '{}'", s));
+    }
+
+    let nsecs = (secs * 1_000_000_000.0).round() as u64;
+    Ok(Duration::new(
+        nsecs / 1_000_000_000,
+        (nsecs % 1_000_000_000) as u32,
+    ))
+}
+
+fn parse_iv(s: &str) -> Result<[u8; 16], String> {
+    let hex = if s.starts_with("0x") || s.starts_with("0X") {
+        &s[2..]
+    } else {
+        return Err(format!("Invalid IV '{}'", s));
+    };
+
+    if hex.len() != 32 {
+        return Err(format!("Invalid IV '{}'", s));
+    }
+
+    let mut iv = [0u8; 16];
+    for (i, byte) in iv.iter_mut().enumerate() {
+        *byte = try!(
+            u8::from_str_radix(&hex[(2 * i)..(2 * i + 2)], 16)
+                .map_err(|_| format!("Invalid IV '{}'", s))
+        );
+    }
+
+    Ok(iv)
+}
+
+fn sequence_iv(sequence: u64) -> [u8; 16] {
+    let mut iv = [0u8; 16];
+    for (i, byte) in iv[8..].iter_mut().enumerate() {
+        *byte = (sequence >> (56 - 8 * i)) as u8;
+    }
+    iv
+}
+
+fn join(base: &Url, uri: &str) -> Result<Url, String> {
+    base.join(uri)
+        .map_err(|err| format!("Invalid URI '{}': {}", uri, err))
+}
+
+#[derive(Debug, Clone)]
+enum KeyMethod {
+    Aes128 { uri: Url, iv: Option<[u8; 16]> },
+}
+
+// Relative URIs are resolved against the base, which should be the URI of
+// the playlist after redirects
+pub fn parse(data: &str, base: &Url) -> Result<Playlist, String> {
+    let mut lines = data.trim_left_matches('\u{feff}')
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+
+    if lines.next() != Some("#EXTM3U") {
+        return Err("Not a M3U8 playlist".into());
+    }
+
+    let mut variants = Vec::new();
+    let mut stream_inf = None;
+
+    let mut target_duration = None;
+    let mut media_sequence = 0;
+    let mut discontinuity_sequence = 0;
+    let mut segments = Vec::new();
+    let mut end_list = false;
+    let mut is_media = false;
+
+    let mut duration = None;
+    let mut discontinuity = false;
+    let mut discontinuities = 0;
+    let mut byte_range = None;
+    let mut next_offset: Option<(Url, u64)> = None;
+    let mut key = None;
+
+    for line in lines {
+        let (tag, value) = match line.find(':') {
+            Some(idx) if line.starts_with('#') => (&line[..idx], &line[(idx + 1)..]),
+            _ => (line, ""),
+        };
+
+        match tag {
+            "#EXT-X-STREAM-INF" => {
+                let attributes = try!(parse_attributes(value));
+                stream_inf = Some(attributes);
+            }
+            "#EXT-X-TARGETDURATION" => {
+                is_media = true;
+                target_duration = Some(Duration::from_secs(try!(parse_integer(value))));
+            }
+            "#EXT-X-MEDIA-SEQUENCE" => {
+                is_media = true;
+                media_sequence = try!(parse_integer(value));
+            }
+            "#EXT-X-DISCONTINUITY-SEQUENCE" => {
+                is_media = true;
+                discontinuity_sequence = try!(parse_integer(value));
+            }
+            "#EXTINF" => {
+                is_media = true;
+                let value = value.split(',').next().unwrap();
+                duration = Some(try!(parse_duration(value)));
+            }
+            "#EXT-X-DISCONTINUITY" => {
+                discontinuity = true;
+                discontinuities += 1;
+            }
+            "#EXT-X-BYTERANGE" => {
+                let mut parts = value.splitn(2, '@');
+                let length = try!(parse_integer(parts.next().unwrap()));
+                if length == 0 {
+                    return Err(format!("Invalid byte range '{}'", value));
+                }
+                let offset = match parts.next() {
+                    Some(offset) => Some(try!(parse_integer(offset))),
+                    None => None,
+                };
+                byte_range = Some((length, offset));
+            }
+            "#EXT-X-KEY" => {
+                let attributes = try!(parse_attributes(value));
+                key = match attributes.get("METHOD").map(String::as_str) {
+                    Some("NONE") => None,
+                    Some("AES-128") => {
+                        let uri = try!(
+                            attributes
+                                .get("URI")
+                                .ok_or_else(|| String::from("AES-128 key without URI"))
+                        );
+                        let iv = match attributes.get("IV") {
+                            Some(iv) => Some(try!(parse_iv(iv))),
+                            None => None,
+                        };
+                        Some(KeyMethod::Aes128 {
+                            uri: try!(join(base, uri)),
+                            iv: iv,
+                        })
+                    }
+                    Some(method) => {
+                        return Err(format!("Unsupported encryption method '{}'", method));
+                    }
+                    None => return Err("Key without method".into()),
+                };
+            }
+            // Segments would only be decodable together with the media
+            // initialization section, e.g. for fragmented MP4
+            "#EXT-X-MAP" => {
+                return Err("Unsupported media initialization section (EXT-X-MAP)".into());
+            }
+            "#EXT-X-ENDLIST" => {
+                is_media = true;
+                end_list = true;
+            }
+            _ if tag.starts_with('#') => (),
+            uri => {
+                let uri = try!(join(base, uri));
+
+                if let Some(attributes) = stream_inf.take() {
+                    let bandwidth = try!(
+                        attributes
+                            .get("BANDWIDTH")
+                            .ok_or_else(|| format!("Variant {} without bandwidth", uri))
+                            .and_then(|bandwidth| parse_integer(bandwidth))
+                    );
+                    let resolution = attributes.get("RESOLUTION").and_then(|resolution| {
+                        let mut parts = resolution.splitn(2, 'x');
+                        match (parts.next(), parts.next()) {
+                            (Some(width), Some(height)) => {
+                                match (width.parse::<u32>(), height.parse::<u32>()) {
+                                    (Ok(width), Ok(height)) => Some((width, height)),
+                                    _ => None,
+                                }
+                            }
+                            _ => None,
+                        }
+                    });
+
+                    variants.push(Variant {
+                        uri: uri,
+                        bandwidth: bandwidth,
+                        resolution: resolution,
+                        codecs: attributes.get("CODECS").cloned(),
+                    });
+                    continue;
+                }
+
+                let duration = try!(
+                    duration
+                        .take()
+                        .ok_or_else(|| format!("Segment {} without duration", uri))
+                );
+                let sequence = media_sequence + segments.len() as u64;
+
+                // Without offset the range starts after the previous one of
+                // the same resource
+                let range = match byte_range.take() {
+                    Some((length, Some(offset))) => Some(ByteRange {
+                        offset: offset,
+                        length: length,
+                    }),
+                    Some((length, None)) => match next_offset {
+                        Some((ref previous, offset)) if *previous == uri => Some(ByteRange {
+                            offset: offset,
+                            length: length,
+                        }),
+                        _ => return Err(format!("Byte range of {} without offset", uri)),
+                    },
+                    None => None,
+                };
+                next_offset = range
+                    .as_ref()
+                    .map(|range| (uri.clone(), range.offset + range.length));
+
+                let segment_key = key.as_ref().map(|key| match *key {
+                    KeyMethod::Aes128 { ref uri, iv } => Key {
+                        uri: uri.clone(),
+                        iv: iv.unwrap_or_else(|| sequence_iv(sequence)),
+                    },
+                });
+
+                segments.push(Segment {
+                    uri: uri,
+                    duration: duration,
+                    sequence: sequence,
+                    discontinuity: discontinuity,
+                    discontinuity_sequence: discontinuity_sequence + discontinuities,
+                    byte_range: range,
+                    key: segment_key,
+                });
+                discontinuity = false;
+            }
+        }
+    }
+
+    if !variants.is_empty() {
+        if is_media {
+            return Err("Playlist contains variants and segments".into());
+        }
+
+        return Ok(Playlist::Master(MasterPlaylist { variants: variants }));
+    }
+
+    let target_duration = try!(target_duration.ok_or_else(|| {
+        String::from("Media playlist without target duration")
+    }));
+
+    Ok(Playlist::Media(MediaPlaylist {
+        target_duration: target_duration,
+        media_sequence: media_sequence,
+        discontinuity_sequence: discontinuity_sequence,
+        segments: segments,
+        end_list: end_list,
+    }))
+}
+
+// The variant with the highest bandwidth that is not above the limit, or the
+// one with the lowest bandwidth if all are above it. A limit of 0 means
+// unlimited
+pub fn select_variant(variants: &[Variant], max_bandwidth: u64) -> Option<usize> {
+    let below = variants
+        .iter()
+        .enumerate()
+        .filter(|&(_, variant)| max_bandwidth == 0 || variant.bandwidth <= max_bandwidth)
+        .max_by_key(|&(_, variant)| variant.bandwidth);
+
+    below
+        .or_else(|| {
+            variants
+                .iter()
+                .enumerate()
+                .min_by_key(|&(_, variant)| variant.bandwidth)
+        })
+        .map(|(idx, _)| idx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> Url {
+        Url::parse("http://example.com/live/index.m3u8").unwrap()
+    }
+
+    fn media(data: &str) -> MediaPlaylist {
+        match parse(data, &base()).unwrap() {
+            Playlist::Media(playlist) => playlist,
+            playlist => panic!("Unexpected playlist {:?}", playlist),
+        }
+    }
+
+    #[test]
+    fn test_attributes() {
+        let attributes =
+            parse_attributes("BANDWIDTH=1280000,CODECS=\"avc1.4d401f,mp4a.40.2\", NAME=x")
+                .unwrap();
+        assert_eq!(attributes["BANDWIDTH"], "1280000");
+        assert_eq!(attributes["CODECS"], "avc1.4d401f,mp4a.40.2");
+        assert_eq!(attributes["NAME"], "x");
+
+        assert!(parse_attributes("CODECS=\"avc1").is_err());
+        assert!(parse_attributes("BANDWIDTH").is_err());
+        assert!(parse_attributes("URI=\"a\"b").is_err());
+    }
+
+    #[test]
+    fn test_master_playlist() {
+        let playlist = parse(
+            "#EXTM3U\n\
+             #EXT-X-STREAM-INF:BANDWIDTH=1280000,RESOLUTION=640x360,CODECS=\"avc1,mp4a\"\n\
+             mid/index.m3u8\n\
+             #EXT-X-STREAM-INF:BANDWIDTH=320000\n\
+             http://cdn.example.com/low.m3u8\n\
+             #EXT-X-I-FRAME-STREAM-INF:BANDWIDTH=86000,URI=\"iframe.m3u8\"\n\
+             #EXT-X-STREAM-INF:BANDWIDTH=2560000,RESOLUTION=1280x720\n\
+             /high/index.m3u8\n",
+            &base(),
+        ).unwrap();
+
+        let variants = match playlist {
+            Playlist::Master(ref master) => &master.variants,
+            ref playlist => panic!("Unexpected playlist {:?}", playlist),
+        };
+
+        assert_eq!(variants.len(), 3);
+        assert_eq!(
+            variants[0],
+            Variant {
+                uri: Url::parse("http://example.com/live/mid/index.m3u8").unwrap(),
+                bandwidth: 1_280_000,
+                resolution: Some((640, 360)),
+                codecs: Some("avc1,mp4a".into()),
+            }
+        );
+        assert_eq!(variants[1].uri.as_str(), "http://cdn.example.com/low.m3u8");
+        assert_eq!(variants[1].resolution, None);
+        assert_eq!(variants[2].uri.as_str(), "http://example.com/high/index.m3u8");
+
+        assert_eq!(select_variant(variants, 0), Some(2));
+        assert_eq!(select_variant(variants, 2_000_000), Some(0));
+        assert_eq!(select_variant(variants, 1_280_000), Some(0));
+        assert_eq!(select_variant(variants, 100_000), Some(1));
+        assert_eq!(select_variant(&[], 0), None);
+    }
+
+    #[test]
+    fn test_media_playlist() {
+        let playlist = media(
+            "#EXTM3U\n\
+             #EXT-X-VERSION:3\n\
+             #EXT-X-TARGETDURATION:10\n\
+             #EXT-X-MEDIA-SEQUENCE:2680\n\
+             \n\
+             #EXTINF:9.009,\n\
+             segment2680.ts\n\
+             #EXTINF:9.5,title\n\
+             https://other.example.com/segment2681.ts\n\
+             #EXT-X-DISCONTINUITY\n\
+             #EXTINF:3,\n\
+             segment2682.ts\n",
+        );
+
+        assert_eq!(playlist.target_duration, Duration::from_secs(10));
+        assert_eq!(playlist.media_sequence, 2680);
+        assert_eq!(playlist.end_sequence(), 2683);
+        assert!(!playlist.end_list);
+        assert_eq!(playlist.segments.len(), 3);
+
+        let segment = &playlist.segments[0];
+        assert_eq!(segment.uri.as_str(), "http://example.com/live/segment2680.ts");
+        assert_eq!(segment.duration, Duration::from_millis(9009));
+        assert_eq!(segment.sequence, 2680);
+        assert!(!segment.discontinuity);
+        assert_eq!(segment.key, None);
+
+        assert_eq!(playlist.segments[1].duration, Duration::from_millis(9500));
+        assert_eq!(
+            playlist.segments[1].uri.as_str(),
+            "https://other.example.com/segment2681.ts"
+        );
+
+        let segment = playlist.segment(2682).unwrap();
+        assert!(segment.discontinuity);
+        assert_eq!(segment.discontinuity_sequence, 1);
+        assert_eq!(playlist.segment(2679), None);
+        assert_eq!(playlist.segment(2683), None);
+    }
+
+    #[test]
+    fn test_vod_playlist() {
+        let playlist = media(
+            "#EXTM3U\r\n\
+             #EXT-X-PLAYLIST-TYPE:VOD\r\n\
+             #EXT-X-TARGETDURATION:6\r\n\
+             #EXT-X-DISCONTINUITY-SEQUENCE:4\r\n\
+             #EXTINF:6.0,\r\n\
+             #EXT-X-BYTERANGE:1000@0\r\n\
+             media.ts\r\n\
+             #EXTINF:6.0,\r\n\
+             #EXT-X-BYTERANGE:500\r\n\
+             media.ts\r\n\
+             #EXT-X-DISCONTINUITY\r\n\
+             #EXTINF:2.0,\r\n\
+             #EXT-X-BYTERANGE:700@2000\r\n\
+             media.ts\r\n\
+             #EXT-X-ENDLIST\r\n",
+        );
+
+        assert!(playlist.end_list);
+        assert_eq!(playlist.media_sequence, 0);
+        assert_eq!(
+            playlist.segments[0].byte_range,
+            Some(ByteRange {
+                offset: 0,
+                length: 1000,
+            })
+        );
+        assert_eq!(
+            playlist.segments[1].byte_range,
+            Some(ByteRange {
+                offset: 1000,
+                length: 500,
+            })
+        );
+        assert_eq!(
+            playlist.segments[2].byte_range,
+            Some(ByteRange {
+                offset: 2000,
+                length: 700,
+            })
+        );
+        assert_eq!(playlist.segments[1].discontinuity_sequence, 4);
+        assert_eq!(playlist.segments[2].discontinuity_sequence, 5);
+    }
+
+    #[test]
+    fn test_keys() {
+        let playlist = media(
+            "#EXTM3U\n\
+             #EXT-X-TARGETDURATION:10\n\
+             #EXT-X-MEDIA-SEQUENCE:258\n\
+             #EXT-X-KEY:METHOD=AES-128,URI=\"https://keys.example.com/key1\"\n\
+             #EXTINF:10,\n\
+             a.ts\n\
+             #EXT-X-KEY:METHOD=AES-128,URI=\"key2\",IV=0x000102030405060708090A0B0C0D0E0F\n\
+             #EXTINF:10,\n\
+             b.ts\n\
+             #EXT-X-KEY:METHOD=NONE\n\
+             #EXTINF:10,\n\
+             c.ts\n",
+        );
+
+        assert_eq!(
+            playlist.segments[0].key,
+            Some(Key {
+                uri: Url::parse("https://keys.example.com/key1").unwrap(),
+                iv: [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 2],
+            })
+        );
+        assert_eq!(
+            playlist.segments[1].key,
+            Some(Key {
+                uri: Url::parse("http://example.com/live/key2").unwrap(),
+                iv: [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+            })
+        );
+        assert_eq!(playlist.segments[2].key, None);
+
+        assert!(
+            parse(
+                "#EXTM3U\n\
+                 #EXT-X-TARGETDURATION:10\n\
+                 #EXT-X-KEY:METHOD=SAMPLE-AES,URI=\"key\"\n",
+                &base()
+            ).is_err()
+        );
+        assert!(
+            parse(
+                "#EXTM3U\n\
+                 #EXT-X-TARGETDURATION:10\n\
+                 #EXT-X-KEY:METHOD=AES-128,URI=\"key\",IV=0x0102\n",
+                &base()
+            ).is_err()
+        );
+    }
+
+    #[test]
+    fn test_invalid_playlists() {
+        assert!(parse("", &base()).is_err());
+        assert!(parse("#EXTINF:10,\na.ts\n", &base()).is_err());
+        assert!(parse("#EXTM3U\n#EXTINF:10,\na.ts\n", &base()).is_err());
+        assert!(parse("#EXTM3U\n#EXT-X-TARGETDURATION:10\na.ts\n", &base()).is_err());
+        assert!(parse("#EXTM3U\n#EXT-X-TARGETDURATION:x\n", &base()).is_err());
+        assert!(
+            parse(
+                "#EXTM3U\n\
+                 #EXT-X-TARGETDURATION:10\n\
+                 #EXTINF:10,\n\
+                 #EXT-X-BYTERANGE:100\n\
+                 a.ts\n",
+                &base()
+            ).is_err()
+        );
+        assert!(
+            parse(
+                "#EXTM3U\n\
+                 #EXT-X-TARGETDURATION:10\n\
+                 #EXTINF:10,\n\
+                 #EXT-X-BYTERANGE:0@100\n\
+                 a.ts\n",
+                &base()
+            ).is_err()
+        );
+        assert!(parse("#EXTM3U\n#EXT-X-STREAM-INF:CODECS=\"a\"\nlow.m3u8\n", &base()).is_err());
+        assert!(
+            parse(
+                "#EXTM3U\n\
+                 #EXT-X-TARGETDURATION:10\n\
+                 #EXT-X-MAP:URI=\"init.mp4\"\n\
+                 #EXTINF:10,\n\
+                 a.m4s\n",
+                &base()
+            ).is_err()
+        );
+    }
+}
@@ -0,0 +1,308 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use reqwest::{self, RequestBuilder, Response};
+
+use gst_plugin_simple::Flushing;
+
+// Interval in which waiting for the response checks if it should be interrupted
+const WAIT_TIMEOUT_MS: u64 = 100;
+
+// Upper bound for request threads that are still running, including the ones
+// abandoned after flushing or timing out. Without a client timeout these only
+// finish once the server replies or closes the connection
+const MAX_REQUEST_THREADS: usize = 32;
+
+static REQUEST_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+// Counts a running request thread until it is dropped
+struct RequestThread;
+
+impl RequestThread {
+    fn acquire() -> Option<RequestThread> {
+        if REQUEST_THREADS.fetch_add(1, Ordering::SeqCst) >= MAX_REQUEST_THREADS {
+            REQUEST_THREADS.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+
+        Some(RequestThread)
+    }
+}
+
+impl Drop for RequestThread {
+    fn drop(&mut self) {
+        REQUEST_THREADS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug)]
+pub enum SendError {
+    Flushing,
+    TimedOut,
+    TooManyRequests,
+    Thread(io::Error),
+    Request(reqwest::Error),
+    Read(io::Error),
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SendError::Flushing => f.write_str("Flushing"),
+            SendError::TimedOut => f.write_str("Timed out waiting for the response"),
+            SendError::TooManyRequests => write!(
+                f,
+                "Too many unfinished requests ({}), the server is not responding",
+                MAX_REQUEST_THREADS
+            ),
+            SendError::Thread(ref err) => write!(f, "Failed to start request thread: {}", err),
+            SendError::Request(ref err) => write!(f, "{}", err),
+            SendError::Read(ref err) => write!(f, "Failed to read the response: {}", err),
+        }
+    }
+}
+
+// Sends the request in a separate thread, so that connecting and waiting for
+// the response headers can be interrupted by flushing and can time out. An
+// interrupted request is abandoned, its thread finishes once send() returns.
+// Fails without sending if too many earlier requests are still unfinished
+pub fn send(
+    request: RequestBuilder,
+    flushing: &Flushing,
+    timeout: Option<Duration>,
+) -> Result<Response, SendError> {
+    let mut request = request;
+    run(move || request.send().map_err(SendError::Request), flushing, timeout)
+}
+
+// Like send(), but also reads the whole body of a successful response in the
+// request thread. Only meant for small responses, e.g. a few blocks of a range
+pub fn fetch(
+    request: RequestBuilder,
+    flushing: &Flushing,
+    timeout: Option<Duration>,
+) -> Result<(Response, Vec<u8>), SendError> {
+    let mut request = request;
+    run(
+        move || {
+            let mut response = try!(request.send().map_err(SendError::Request));
+            let mut body = Vec::new();
+            if response.status().is_success() {
+                try!(response.read_to_end(&mut body).map_err(SendError::Read));
+            }
+
+            Ok((response, body))
+        },
+        flushing,
+        timeout,
+    )
+}
+
+fn run<T, F>(func: F, flushing: &Flushing, timeout: Option<Duration>) -> Result<T, SendError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, SendError> + Send + 'static,
+{
+    let receiver = try!(spawn(func));
+    wait(&receiver, flushing, timeout)
+}
+
+// Runs the function in a new request thread, which fails if too many earlier
+// requests are still unfinished. Its result is received with wait()
+pub fn spawn<T, F>(func: F) -> Result<Receiver<Result<T, SendError>>, SendError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, SendError> + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+
+    let guard = try!(RequestThread::acquire().ok_or(SendError::TooManyRequests));
+    try!(
+        thread::Builder::new()
+            .name("rshttp-request".into())
+            .spawn(move || {
+                let _guard = guard;
+                let _ = sender.send(func());
+            })
+            .map_err(SendError::Thread)
+    );
+
+    Ok(receiver)
+}
+
+// Waits for the result of a request thread. If this is interrupted by flushing
+// or times out, the thread is abandoned and finishes on its own
+pub fn wait<T>(
+    receiver: &Receiver<Result<T, SendError>>,
+    flushing: &Flushing,
+    timeout: Option<Duration>,
+) -> Result<T, SendError> {
+    let start = Instant::now();
+    loop {
+        if flushing.is_flushing() {
+            return Err(SendError::Flushing);
+        }
+
+        match receiver.recv_timeout(Duration::from_millis(WAIT_TIMEOUT_MS)) {
+            Ok(res) => return res,
+            Err(RecvTimeoutError::Timeout) => {
+                if timeout.map_or(false, |timeout| start.elapsed() >= timeout) {
+                    return Err(SendError::TimedOut);
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(SendError::Thread(io::Error::new(
+                    io::ErrorKind::Other,
+                    "Request thread panicked",
+                )));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::io::Write;
+    use reqwest::{Client, StatusCode};
+    use gst_plugin_simple::Unlock;
+
+    // Accepts a single connection, only replies once told what to and keeps
+    // the connection open until the sender is dropped
+    fn silent_server(listener: TcpListener, reply: mpsc::Receiver<&'static [u8]>) {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = [0u8; 4096];
+        let _ = stream.read(&mut buf);
+
+        if let Ok(data) = reply.recv() {
+            let _ = stream.write_all(data);
+            let _ = reply.recv();
+        }
+    }
+
+    #[test]
+    fn test_send() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/", listener.local_addr().unwrap());
+        let (reply, receiver) = mpsc::channel();
+        let server = thread::spawn(move || silent_server(listener, receiver));
+
+        reply
+            .send(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")
+            .unwrap();
+        let flushing = Flushing::new();
+        let response = send(Client::new().get(uri.as_str()), &flushing, None).unwrap();
+        assert_eq!(response.status(), StatusCode::NoContent);
+
+        drop(reply);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_fetch() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/", listener.local_addr().unwrap());
+        let (reply, receiver) = mpsc::channel();
+        let server = thread::spawn(move || silent_server(listener, receiver));
+
+        reply
+            .send(b"HTTP/1.1 206 Partial Content\r\nContent-Length: 5\r\n\r\nhello")
+            .unwrap();
+        let flushing = Flushing::new();
+        let (response, body) = fetch(Client::new().get(uri.as_str()), &flushing, None).unwrap();
+        assert_eq!(response.status(), StatusCode::PartialContent);
+        assert_eq!(body, b"hello");
+
+        drop(reply);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_fetch_unlock() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/", listener.local_addr().unwrap());
+        let (reply, receiver) = mpsc::channel();
+        let server = thread::spawn(move || silent_server(listener, receiver));
+
+        // The headers arrive, the body never does
+        reply
+            .send(b"HTTP/1.1 206 Partial Content\r\nContent-Length: 5\r\n\r\n")
+            .unwrap();
+        let flushing = Flushing::new();
+        let unlock = {
+            let flushing = flushing.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(300));
+                flushing.unlock();
+            })
+        };
+
+        match fetch(Client::new().get(uri.as_str()), &flushing, None) {
+            Err(SendError::Flushing) => (),
+            res => panic!("Unexpected result {:?}", res),
+        }
+
+        unlock.join().unwrap();
+        drop(reply);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_send_unlock() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/", listener.local_addr().unwrap());
+        let (reply, receiver) = mpsc::channel();
+        let server = thread::spawn(move || silent_server(listener, receiver));
+
+        let flushing = Flushing::new();
+        let unlock = {
+            let flushing = flushing.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(300));
+                flushing.unlock();
+            })
+        };
+
+        let start = Instant::now();
+        match send(Client::new().get(uri.as_str()), &flushing, None) {
+            Err(SendError::Flushing) => (),
+            res => panic!("Unexpected result {:?}", res),
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        unlock.join().unwrap();
+        drop(reply);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_send_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/", listener.local_addr().unwrap());
+        let (reply, receiver) = mpsc::channel();
+        let server = thread::spawn(move || silent_server(listener, receiver));
+
+        let flushing = Flushing::new();
+        let timeout = Some(Duration::from_millis(300));
+        match send(Client::new().get(uri.as_str()), &flushing, timeout) {
+            Err(SendError::TimedOut) => (),
+            res => panic!("Unexpected result {:?}", res),
+        }
+
+        drop(reply);
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,637 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// Interval in which the listening thread checks if it should stop
+const ACCEPT_POLL_MS: u64 = 100;
+
+// Time a client has to send its request
+const REQUEST_TIMEOUT_SECS: u64 = 10;
+
+const MAX_REQUEST_SIZE: usize = 8192;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlowClientPolicy {
+    // Drop data for the client and continue with the next keyframe
+    Drop,
+    Disconnect,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientEvent {
+    Added { id: u64, address: SocketAddr },
+    Removed {
+        id: u64,
+        address: SocketAddr,
+        reason: String,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Request<'a> {
+    method: &'a str,
+    path: &'a str,
+}
+
+// Only the request line is of interest, the request headers are ignored
+fn parse_request(head: &str) -> Option<Request> {
+    let line = head.lines().next().unwrap_or("");
+    let mut parts = line.split(' ').filter(|part| !part.is_empty());
+
+    let method = parts.next();
+    let target = parts.next();
+    let version = parts.next();
+
+    match (method, target, version, parts.next()) {
+        (Some(method), Some(target), Some(version), None) if version.starts_with("HTTP/1.") => {
+            let path = target.split('?').next().unwrap();
+            Some(Request {
+                method: method,
+                path: path,
+            })
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+struct Client {
+    stream: TcpStream,
+    // Only streaming clients get data, before that the request is read
+    streaming: bool,
+    synced: bool,
+    // The stream headers are only sent once, before the first keyframe
+    headers_sent: bool,
+    queue: VecDeque<Arc<Vec<u8>>>,
+    queued: usize,
+    closed: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    clients: HashMap<u64, Client>,
+    headers: Vec<Arc<Vec<u8>>>,
+    // Header buffers after data start a new sequence of headers
+    headers_followed: bool,
+    content_type: String,
+    next_id: u64,
+    threads: usize,
+    shutdown: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    cond: Condvar,
+    path: String,
+    max_queue_bytes: usize,
+    policy: SlowClientPolicy,
+    notify: Box<Fn(ClientEvent) + Send + Sync>,
+}
+
+// Serves the stream to all clients that request the path with GET. Every
+// client is handled by its own thread and gets the stream headers together
+// with the next keyframe, followed by the data after it
+pub struct Server {
+    shared: Arc<Shared>,
+    address: SocketAddr,
+}
+
+impl Server {
+    pub fn new<A: ToSocketAddrs>(
+        address: A,
+        path: &str,
+        max_queue_bytes: usize,
+        policy: SlowClientPolicy,
+        notify: Box<Fn(ClientEvent) + Send + Sync>,
+    ) -> io::Result<Server> {
+        let listener = try!(TcpListener::bind(address));
+        try!(listener.set_nonblocking(true));
+        let address = try!(listener.local_addr());
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                content_type: "application/octet-stream".into(),
+                threads: 1,
+                ..Default::default()
+            }),
+            cond: Condvar::new(),
+            path: path.into(),
+            max_queue_bytes: max_queue_bytes,
+            policy: policy,
+            notify: notify,
+        });
+
+        let shared_clone = shared.clone();
+        try!(
+            thread::Builder::new()
+                .name("rshttpserversink-accept".into())
+                .spawn(move || accept(&listener, &shared_clone))
+        );
+
+        Ok(Server {
+            shared: shared,
+            address: address,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn num_clients(&self) -> usize {
+        let state = self.shared.state.lock().unwrap();
+        state.clients.values().filter(|c| c.streaming).count()
+    }
+
+    // Replaces all headers, e.g. after a caps change. Only clients that didn't
+    // get any data yet get the new headers
+    pub fn set_headers(&self, headers: Vec<Vec<u8>>) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.headers = headers.into_iter().map(Arc::new).collect();
+        state.headers_followed = false;
+    }
+
+    // Adds a header buffer from the stream, which replaces the previous
+    // headers if data was sent since then. Clients that already got the
+    // headers get the new one right away
+    pub fn add_header(&self, header: Vec<u8>) {
+        let header = Arc::new(header);
+        let mut state = self.shared.state.lock().unwrap();
+
+        if state.headers_followed {
+            state.headers.clear();
+            state.headers_followed = false;
+        }
+
+        for client in state.clients.values_mut() {
+            if client.headers_sent && client.closed.is_none() {
+                client.queued += header.len();
+                client.queue.push_back(header.clone());
+            }
+        }
+        state.headers.push(header);
+
+        self.shared.cond.notify_all();
+    }
+
+    pub fn set_content_type(&self, content_type: &str) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.content_type = content_type.into();
+    }
+
+    pub fn send(&self, data: Vec<u8>, keyframe: bool) {
+        let data = Arc::new(data);
+        let mut guard = self.shared.state.lock().unwrap();
+        let state = &mut *guard;
+
+        state.headers_followed = true;
+
+        for client in state.clients.values_mut() {
+            if !client.streaming || client.closed.is_some() {
+                continue;
+            }
+
+            if !client.synced {
+                if !keyframe {
+                    continue;
+                }
+                client.synced = true;
+            }
+
+            if self.shared.max_queue_bytes != 0 && !client.queue.is_empty()
+                && client.queued + data.len() > self.shared.max_queue_bytes
+            {
+                match self.shared.policy {
+                    SlowClientPolicy::Drop => {
+                        client.synced = false;
+                    }
+                    SlowClientPolicy::Disconnect => {
+                        client.closed = Some("Client too slow".into());
+                        let _ = client.stream.shutdown(Shutdown::Both);
+                    }
+                }
+                continue;
+            }
+
+            if !client.headers_sent {
+                client.headers_sent = true;
+                for header in &state.headers {
+                    client.queued += header.len();
+                    client.queue.push_back(header.clone());
+                }
+            }
+
+            client.queued += data.len();
+            client.queue.push_back(data.clone());
+        }
+
+        self.shared.cond.notify_all();
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+
+        state.shutdown = true;
+        for client in state.clients.values() {
+            let _ = client.stream.shutdown(Shutdown::Both);
+        }
+        self.shared.cond.notify_all();
+
+        while state.threads > 0 {
+            state = self.shared.cond.wait(state).unwrap();
+        }
+    }
+}
+
+impl fmt::Debug for Server {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Server")
+            .field("address", &self.address)
+            .finish()
+    }
+}
+
+fn thread_finished(shared: &Shared) {
+    let mut state = shared.state.lock().unwrap();
+    state.threads -= 1;
+    shared.cond.notify_all();
+}
+
+fn accept(listener: &TcpListener, shared: &Arc<Shared>) {
+    loop {
+        if shared.state.lock().unwrap().shutdown {
+            break;
+        }
+
+        let (stream, address) = match listener.accept() {
+            Ok(res) => res,
+            Err(ref err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => {
+                thread::sleep(Duration::from_millis(ACCEPT_POLL_MS));
+                continue;
+            }
+        };
+
+        let stream_clone = match stream
+            .set_nonblocking(false)
+            .and_then(|_| stream.try_clone())
+        {
+            Ok(stream_clone) => stream_clone,
+            Err(_) => continue,
+        };
+
+        let id = {
+            let mut state = shared.state.lock().unwrap();
+            if state.shutdown {
+                break;
+            }
+
+            let id = state.next_id;
+            state.next_id += 1;
+            state.threads += 1;
+            state.clients.insert(
+                id,
+                Client {
+                    stream: stream_clone,
+                    streaming: false,
+                    synced: false,
+                    headers_sent: false,
+                    queue: VecDeque::new(),
+                    queued: 0,
+                    closed: None,
+                },
+            );
+            id
+        };
+
+        let shared_clone = shared.clone();
+        if thread::Builder::new()
+            .name("rshttpserversink-client".into())
+            .spawn(move || serve(stream, id, address, &shared_clone))
+            .is_err()
+        {
+            let mut state = shared.state.lock().unwrap();
+            state.clients.remove(&id);
+            state.threads -= 1;
+        }
+    }
+
+    thread_finished(shared);
+}
+
+fn read_request(stream: &mut TcpStream) -> io::Result<String> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+
+    try!(stream.set_read_timeout(Some(Duration::from_secs(REQUEST_TIMEOUT_SECS))));
+
+    while !head.ends_with(b"\r\n\r\n") && !head.ends_with(b"\n\n") {
+        if head.len() >= MAX_REQUEST_SIZE {
+            return Err(io::Error::new(ErrorKind::InvalidData, "Request too large"));
+        }
+
+        // Byte by byte to not read into anything sent after the request
+        let size = try!(stream.read(&mut buf[..1]));
+        if size == 0 {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "Connection closed during request",
+            ));
+        }
+        head.extend_from_slice(&buf[..size]);
+    }
+
+    String::from_utf8(head).map_err(|_| io::Error::new(ErrorKind::InvalidData, "Invalid request"))
+}
+
+// Returns if the client should get the stream
+fn respond(stream: &mut TcpStream, shared: &Shared) -> io::Result<bool> {
+    let head = try!(read_request(stream));
+
+    let (status, stream_data) = match parse_request(&head) {
+        None => ("400 Bad Request", false),
+        Some(ref request) if request.path != shared.path => ("404 Not Found", false),
+        Some(Request { method: "GET", .. }) => ("200 OK", true),
+        Some(Request { method: "HEAD", .. }) => ("200 OK", false),
+        Some(_) => ("405 Method Not Allowed", false),
+    };
+
+    let content_type = shared.state.lock().unwrap().content_type.clone();
+    let response = format!(
+        "HTTP/1.0 {}\r\n\
+         Content-Type: {}\r\n\
+         Cache-Control: no-cache\r\n\
+         Connection: close\r\n\
+         \r\n",
+        status,
+        content_type
+    );
+    try!(stream.write_all(response.as_bytes()));
+
+    Ok(stream_data)
+}
+
+fn serve(mut stream: TcpStream, id: u64, address: SocketAddr, shared: &Shared) {
+    let reason = match respond(&mut stream, shared) {
+        Ok(true) => Some(stream_to(&mut stream, id, address, shared)),
+        Ok(false) | Err(_) => None,
+    };
+
+    shared.state.lock().unwrap().clients.remove(&id);
+
+    if let Some(reason) = reason {
+        (shared.notify)(ClientEvent::Removed {
+            id: id,
+            address: address,
+            reason: reason,
+        });
+    }
+
+    thread_finished(shared);
+}
+
+// Returns the reason for the disconnect
+fn stream_to(stream: &mut TcpStream, id: u64, address: SocketAddr, shared: &Shared) -> String {
+    {
+        let mut state = shared.state.lock().unwrap();
+        state.clients.get_mut(&id).unwrap().streaming = true;
+    }
+
+    (shared.notify)(ClientEvent::Added {
+        id: id,
+        address: address,
+    });
+
+    loop {
+        let data = {
+            let mut state = shared.state.lock().unwrap();
+            loop {
+                if state.shutdown {
+                    return "Server stopped".into();
+                }
+
+                {
+                    let client = state.clients.get_mut(&id).unwrap();
+                    if let Some(ref reason) = client.closed {
+                        return reason.clone();
+                    }
+                    if let Some(data) = client.queue.pop_front() {
+                        client.queued -= data.len();
+                        break data;
+                    }
+                }
+
+                state = shared.cond.wait(state).unwrap();
+            }
+        };
+
+        if let Err(err) = stream.write_all(&data) {
+            return format!("Failed to send: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_parse_request() {
+        assert_eq!(
+            parse_request("GET /live?token=1 HTTP/1.1\r\nHost: localhost\r\n\r\n"),
+            Some(Request {
+                method: "GET",
+                path: "/live",
+            })
+        );
+        assert_eq!(
+            parse_request("HEAD / HTTP/1.0\r\n\r\n"),
+            Some(Request {
+                method: "HEAD",
+                path: "/",
+            })
+        );
+        assert_eq!(parse_request("GET /\r\n\r\n"), None);
+        assert_eq!(parse_request("GET / RTSP/1.0\r\n\r\n"), None);
+        assert_eq!(parse_request(""), None);
+    }
+
+    fn connect(server: &Server, path: &str) -> TcpStream {
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        stream
+    }
+
+    fn read_exact(stream: &mut TcpStream, len: usize) -> Vec<u8> {
+        let mut data = vec![0; len];
+        stream.read_exact(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_server() {
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let server = Server::new(
+            "127.0.0.1:0",
+            "/live",
+            0,
+            SlowClientPolicy::Drop,
+            Box::new(move |event| sender.lock().unwrap().send(event).unwrap()),
+        ).unwrap();
+        server.set_content_type("video/x-matroska");
+        server.set_headers(vec![b"HEAD".to_vec()]);
+
+        let mut stream = connect(&server, "/live");
+        match receiver.recv().unwrap() {
+            ClientEvent::Added { id, .. } => assert_eq!(id, 0),
+            event => panic!("Unexpected event {:?}", event),
+        }
+        assert_eq!(server.num_clients(), 1);
+
+        // Data before the first keyframe is not sent
+        server.send(b"delta".to_vec(), false);
+        server.send(b"KEY".to_vec(), true);
+        server.send(b"delta".to_vec(), false);
+
+        let expected = b"HTTP/1.0 200 OK\r\n\
+                         Content-Type: video/x-matroska\r\n\
+                         Cache-Control: no-cache\r\n\
+                         Connection: close\r\n\
+                         \r\n\
+                         HEADKEYdelta";
+        assert_eq!(&read_exact(&mut stream, expected.len())[..], &expected[..]);
+
+        let mut other = connect(&server, "/other");
+        let mut response = String::new();
+        other.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.0 404 Not Found\r\n"));
+
+        drop(stream);
+        server.send(vec![0; 1024 * 1024], true);
+        server.send(vec![0; 1024 * 1024], true);
+        match receiver.recv().unwrap() {
+            ClientEvent::Removed { id, .. } => assert_eq!(id, 0),
+            event => panic!("Unexpected event {:?}", event),
+        }
+        assert_eq!(server.num_clients(), 0);
+    }
+
+    #[test]
+    fn test_late_headers() {
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let server = Server::new(
+            "127.0.0.1:0",
+            "/",
+            0,
+            SlowClientPolicy::Drop,
+            Box::new(move |event| sender.lock().unwrap().send(event).unwrap()),
+        ).unwrap();
+
+        let mut stream = connect(&server, "/");
+        receiver.recv().unwrap();
+
+        // Headers that are only known after the client connected are still
+        // sent before the first keyframe, header buffers after that right away
+        server.set_headers(vec![b"HEAD".to_vec()]);
+        server.add_header(b"ER".to_vec());
+        server.send(b"KEY".to_vec(), true);
+        server.set_headers(vec![b"NEW".to_vec()]);
+        server.send(b"KEY".to_vec(), true);
+        server.add_header(b"MORE".to_vec());
+
+        let expected = b"HTTP/1.0 200 OK\r\n\
+                         Content-Type: application/octet-stream\r\n\
+                         Cache-Control: no-cache\r\n\
+                         Connection: close\r\n\
+                         \r\n\
+                         HEADERKEYKEYMORE";
+        assert_eq!(&read_exact(&mut stream, expected.len())[..], &expected[..]);
+    }
+
+    #[test]
+    fn test_header_sequences() {
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let server = Server::new(
+            "127.0.0.1:0",
+            "/",
+            0,
+            SlowClientPolicy::Drop,
+            Box::new(move |event| sender.lock().unwrap().send(event).unwrap()),
+        ).unwrap();
+
+        // Header buffers after data replace the earlier ones for new clients
+        server.add_header(b"OLD".to_vec());
+        server.add_header(b"ER".to_vec());
+        server.send(b"KEY".to_vec(), true);
+        server.add_header(b"NEW".to_vec());
+        server.add_header(b"ER".to_vec());
+        server.send(b"KEY".to_vec(), true);
+
+        let mut stream = connect(&server, "/");
+        receiver.recv().unwrap();
+        server.send(b"KEY".to_vec(), true);
+
+        // New caps replace them as well
+        server.set_headers(Vec::new());
+        let mut other = connect(&server, "/");
+        receiver.recv().unwrap();
+        server.send(b"KEY".to_vec(), true);
+
+        let response = b"HTTP/1.0 200 OK\r\n\
+                         Content-Type: application/octet-stream\r\n\
+                         Cache-Control: no-cache\r\n\
+                         Connection: close\r\n\
+                         \r\n";
+        let expected = [&response[..], b"NEWERKEYKEY"].concat();
+        assert_eq!(read_exact(&mut stream, expected.len()), expected);
+        let expected = [&response[..], b"KEY"].concat();
+        assert_eq!(read_exact(&mut other, expected.len()), expected);
+    }
+
+    #[test]
+    fn test_slow_client() {
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        let server = Server::new(
+            "127.0.0.1:0",
+            "/",
+            1024,
+            SlowClientPolicy::Disconnect,
+            Box::new(move |event| sender.lock().unwrap().send(event).unwrap()),
+        ).unwrap();
+
+        let _stream = connect(&server, "/");
+        receiver.recv().unwrap();
+
+        // The client never reads, so eventually its queue runs full
+        for _ in 0..(64 * 1024) {
+            server.send(vec![0; 512], true);
+            if server.num_clients() == 0 {
+                break;
+            }
+        }
+
+        match receiver.recv().unwrap() {
+            ClientEvent::Removed { reason, .. } => assert_eq!(reason, "Client too slow"),
+            event => panic!("Unexpected event {:?}", event),
+        }
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use gst;
+
+// Period over which the instant bitrate is measured
+const INSTANT_WINDOW_MS: u64 = 1000;
+
+#[derive(Debug, Default)]
+pub struct Stats {
+    first_data: Option<Instant>,
+    bytes_received: u64,
+    requests: u32,
+    latency: Option<Duration>,
+    samples: VecDeque<(Instant, u64)>,
+}
+
+fn secs(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_nanos()) / 1_000_000_000.0
+}
+
+fn bitrate(bytes: u64, duration: Duration) -> u64 {
+    let secs = secs(duration);
+    if secs > 0.0 {
+        (bytes as f64 * 8.0 / secs) as u64
+    } else {
+        0
+    }
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        Default::default()
+    }
+
+    // Latency is the time until the response headers were received
+    pub fn add_request(&mut self, latency: Duration) {
+        self.requests += 1;
+        self.latency = Some(latency);
+    }
+
+    pub fn add_bytes(&mut self, bytes: u64, now: Instant) {
+        if self.first_data.is_none() {
+            self.first_data = Some(now);
+        }
+
+        self.bytes_received += bytes;
+        self.samples.push_back((now, bytes));
+
+        let window = Duration::from_millis(INSTANT_WINDOW_MS);
+        while self.samples
+            .front()
+            .map_or(false, |&(time, _)| now.duration_since(time) > window)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    // In bits per second since the first data was received
+    pub fn average_bitrate(&self, now: Instant) -> u64 {
+        match self.first_data {
+            Some(first_data) => bitrate(self.bytes_received, now.duration_since(first_data)),
+            None => 0,
+        }
+    }
+
+    // In bits per second over the last second
+    pub fn instant_bitrate(&self, now: Instant) -> u64 {
+        let first_data = match self.first_data {
+            Some(first_data) => first_data,
+            None => return 0,
+        };
+
+        let window = Duration::from_millis(INSTANT_WINDOW_MS);
+        let elapsed = now.duration_since(first_data);
+        let bytes = self.samples
+            .iter()
+            .filter(|&&(time, _)| now.duration_since(time) <= window)
+            .map(|&(_, bytes)| bytes)
+            .sum();
+
+        bitrate(bytes, if elapsed < window { elapsed } else { window })
+    }
+
+    pub fn to_structure(&self, now: Instant) -> gst::Structure {
+        let latency = self.latency.map_or(gst::CLOCK_TIME_NONE, |latency| {
+            latency.as_secs() * gst::SECOND + u64::from(latency.subsec_nanos())
+        });
+
+        gst::Structure::new(
+            "http-stats",
+            &[
+                ("bytes-received", &self.bytes_received),
+                ("average-bitrate", &self.average_bitrate(now)),
+                ("instant-bitrate", &self.instant_bitrate(now)),
+                ("request-latency", &latency),
+                ("requests", &self.requests),
+            ],
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitrates() {
+        let start = Instant::now();
+        let mut stats = Stats::new();
+
+        assert_eq!(stats.average_bitrate(start), 0);
+        assert_eq!(stats.instant_bitrate(start), 0);
+
+        stats.add_bytes(1000, start);
+        stats.add_bytes(1000, start + Duration::from_millis(500));
+        assert_eq!(stats.bytes_received, 2000);
+        assert_eq!(stats.average_bitrate(start + Duration::from_secs(1)), 16_000);
+        assert_eq!(stats.instant_bitrate(start + Duration::from_millis(500)), 32_000);
+
+        // Only the last second counts for the instant bitrate
+        stats.add_bytes(500, start + Duration::from_secs(2));
+        assert_eq!(stats.average_bitrate(start + Duration::from_secs(2)), 10_000);
+        assert_eq!(stats.instant_bitrate(start + Duration::from_secs(2)), 4_000);
+        assert_eq!(stats.instant_bitrate(start + Duration::from_secs(4)), 0);
+    }
+}
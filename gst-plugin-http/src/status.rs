@@ -0,0 +1,109 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use reqwest::StatusCode;
+use url::Url;
+
+use gst_plugin::error::*;
+
+use gst;
+
+// Failed statuses that are reported with a more specific error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    NotFound,
+    AuthenticationFailed,
+    AuthenticationRequired,
+    ProxyAuthenticationRequired,
+    Forbidden,
+    Other,
+}
+
+fn classify(status: StatusCode, has_credentials: bool) -> Failure {
+    match status {
+        StatusCode::NotFound | StatusCode::Gone => Failure::NotFound,
+        StatusCode::Unauthorized if has_credentials => Failure::AuthenticationFailed,
+        StatusCode::Unauthorized => Failure::AuthenticationRequired,
+        StatusCode::ProxyAuthenticationRequired => Failure::ProxyAuthenticationRequired,
+        StatusCode::Forbidden => Failure::Forbidden,
+        _ => Failure::Other,
+    }
+}
+
+// The details always contain "HTTP status code <code>" so that applications
+// can distinguish them. Statuses without a more specific error use the
+// given one
+pub fn status_error(
+    status: StatusCode,
+    uri: &Url,
+    has_credentials: bool,
+    error: gst::ResourceError,
+) -> ErrorMessage {
+    let details = format!(
+        "HTTP status code {} ({}) for {}",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or("Unknown"),
+        uri
+    );
+
+    match classify(status, has_credentials) {
+        Failure::NotFound => error_msg!(
+            gst::ResourceError::NotFound,
+            ("Resource {} not found", uri),
+            ["{}", details]
+        ),
+        Failure::AuthenticationFailed => error_msg!(
+            gst::ResourceError::NotAuthorized,
+            ("Authentication failed for {}", uri),
+            ["{}", details]
+        ),
+        Failure::AuthenticationRequired => error_msg!(
+            gst::ResourceError::NotAuthorized,
+            ("Authentication required for {}", uri),
+            ["{}", details]
+        ),
+        Failure::ProxyAuthenticationRequired => error_msg!(
+            gst::ResourceError::NotAuthorized,
+            ("Proxy authentication required for {}", uri),
+            ["{}", details]
+        ),
+        Failure::Forbidden => error_msg!(
+            gst::ResourceError::NotAuthorized,
+            ("Access to {} is forbidden", uri),
+            ["{}", details]
+        ),
+        Failure::Other => error_msg!(error, ["{}", details]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify(StatusCode::NotFound, false), Failure::NotFound);
+        assert_eq!(classify(StatusCode::Gone, true), Failure::NotFound);
+        assert_eq!(
+            classify(StatusCode::Unauthorized, true),
+            Failure::AuthenticationFailed
+        );
+        assert_eq!(
+            classify(StatusCode::Unauthorized, false),
+            Failure::AuthenticationRequired
+        );
+        assert_eq!(
+            classify(StatusCode::ProxyAuthenticationRequired, false),
+            Failure::ProxyAuthenticationRequired
+        );
+        assert_eq!(classify(StatusCode::Forbidden, true), Failure::Forbidden);
+        assert_eq!(classify(StatusCode::Forbidden, false), Failure::Forbidden);
+        assert_eq!(classify(StatusCode::InternalServerError, true), Failure::Other);
+        assert_eq!(classify(StatusCode::BadRequest, false), Failure::Other);
+    }
+}
@@ -0,0 +1,175 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::str;
+
+use base64;
+use hyper;
+use native_tls;
+use reqwest::{self, Certificate, Pkcs12};
+
+const PEM_BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+const PEM_END: &str = "-----END CERTIFICATE-----";
+
+fn read_file(path: &str) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    try!(
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut data))
+            .map_err(|err| format!("Failed to read '{}': {}", path, err))
+    );
+
+    Ok(data)
+}
+
+// The file can contain a single DER certificate or any number of PEM ones
+pub fn load_certificates(path: &str) -> Result<Vec<Certificate>, String> {
+    let data = try!(read_file(path));
+
+    let ders = match str::from_utf8(&data) {
+        Ok(s) if s.contains(PEM_BEGIN) => try!(pem_certificates(s)),
+        _ => vec![data],
+    };
+
+    ders.iter()
+        .map(|der| {
+            Certificate::from_der(der)
+                .map_err(|err| format!("Invalid certificate in '{}': {}", path, err))
+        })
+        .collect()
+}
+
+// Client certificate and private key in a PKCS #12 archive
+pub fn load_identity(path: &str, password: &str) -> Result<Pkcs12, String> {
+    let data = try!(read_file(path));
+
+    Pkcs12::from_der(&data, password)
+        .map_err(|err| format!("Invalid client certificate '{}': {}", path, err))
+}
+
+// Returns the DER data of all certificates in the PEM data
+fn pem_certificates(s: &str) -> Result<Vec<Vec<u8>>, String> {
+    let mut certificates = Vec::new();
+    let mut rest = s;
+
+    while let Some(begin) = rest.find(PEM_BEGIN) {
+        rest = &rest[(begin + PEM_BEGIN.len())..];
+        let end = try!(
+            rest.find(PEM_END)
+                .ok_or_else(|| String::from("Unterminated PEM certificate"))
+        );
+
+        let encoded = rest[..end]
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>();
+        let der = try!(
+            base64::decode(&encoded).map_err(|err| format!("Invalid PEM certificate: {}", err))
+        );
+        certificates.push(der);
+
+        rest = &rest[(end + PEM_END.len())..];
+    }
+
+    Ok(certificates)
+}
+
+// Handshake errors are passed on by hyper as the native-tls error inside of
+// an io::Error
+pub fn is_certificate_error(err: &reqwest::Error) -> bool {
+    let io_err = match err.get_ref() {
+        Some(err) => match err.downcast_ref::<hyper::Error>() {
+            Some(&hyper::Error::Io(ref err)) => Some(err),
+            _ => err.downcast_ref::<io::Error>(),
+        },
+        None => None,
+    };
+
+    io_err
+        .and_then(|err| err.get_ref())
+        .and_then(|err| err.downcast_ref::<native_tls::Error>())
+        .map_or(false, is_tls_certificate_error)
+}
+
+// native-tls has no common error kinds, so this depends on the backend
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "ios")))]
+fn is_tls_certificate_error(err: &native_tls::Error) -> bool {
+    use native_tls::backend::openssl::ErrorExt;
+    use openssl::ssl;
+
+    match *err.openssl_error() {
+        ssl::Error::Ssl(ref stack) => stack
+            .errors()
+            .iter()
+            .any(|err| is_verify_failure(err.code() as u64)),
+        _ => false,
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "ios"))]
+fn is_tls_certificate_error(_err: &native_tls::Error) -> bool {
+    false
+}
+
+// SSL_R_CERTIFICATE_VERIFY_FAILED in the SSL library, from the packed error
+// code of OpenSSL
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "ios")))]
+fn is_verify_failure(code: u64) -> bool {
+    const ERR_LIB_SSL: u64 = 20;
+    const SSL_R_CERTIFICATE_VERIFY_FAILED: u64 = 134;
+
+    (code >> 24) & 0xff == ERR_LIB_SSL && code & 0xfff == SSL_R_CERTIFICATE_VERIFY_FAILED
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pem_certificates() {
+        let pem = "Subject: CN=Test CA\n\
+                   -----BEGIN CERTIFICATE-----\n\
+                   AAEC\n\
+                   AwQF\n\
+                   -----END CERTIFICATE-----\n\
+                   -----BEGIN CERTIFICATE-----\n\
+                   BgcI\n\
+                   -----END CERTIFICATE-----\n";
+
+        assert_eq!(
+            pem_certificates(pem),
+            Ok(vec![vec![0, 1, 2, 3, 4, 5], vec![6, 7, 8]])
+        );
+        assert!(pem_certificates("-----BEGIN CERTIFICATE-----\nAAEC\n").is_err());
+    }
+
+    #[test]
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "ios")))]
+    fn test_is_verify_failure() {
+        // error:14090086:SSL routines:ssl3_get_server_certificate:certificate verify failed
+        assert!(is_verify_failure(0x1409_0086));
+        // error:1416F086:SSL routines:tls_process_server_certificate:certificate verify failed
+        assert!(is_verify_failure(0x1416_F086));
+        // error:1408F10B:SSL routines:ssl3_get_record:wrong version number
+        assert!(!is_verify_failure(0x1408_F10B));
+        // error:0B080074:x509 certificate routines:X509_check_private_key:key values mismatch
+        assert!(!is_verify_failure(0x0B08_0074));
+    }
+
+    #[test]
+    fn test_is_certificate_error() {
+        // Nothing is listening on the discard port, which is no TLS error
+        let err = reqwest::Client::new()
+            .get("https://127.0.0.1:9/")
+            .send()
+            .unwrap_err();
+        assert!(!is_certificate_error(&err));
+    }
+}
@@ -0,0 +1,343 @@
+// Copyright (C) 2026 agent <agent@local>
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::cmp;
+use std::fmt;
+use std::io::{self, ErrorKind, Read};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::time::Duration;
+
+use reqwest::{Body, RequestBuilder, Response};
+
+use gst_plugin_simple::Flushing;
+
+use request::{self, SendError};
+
+const MAX_QUEUED_CHUNKS: usize = 16;
+
+// Interval in which waiting for the request checks if it should be interrupted
+const WAIT_TIMEOUT_MS: u64 = 100;
+
+// Request body that is fed from the streaming thread. It ends once the sender
+// is dropped, or fails if the upload was aborted so that the body is not
+// terminated and the server never sees a complete request
+struct ChannelReader {
+    receiver: Receiver<Vec<u8>>,
+    aborted: Arc<AtomicBool>,
+    pending: Vec<u8>,
+    offset: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, data: &mut [u8]) -> io::Result<usize> {
+        while self.offset == self.pending.len() {
+            match self.receiver.recv() {
+                Ok(chunk) => {
+                    self.pending = chunk;
+                    self.offset = 0;
+                }
+                Err(_) if self.aborted.load(Ordering::SeqCst) => {
+                    return Err(io::Error::new(ErrorKind::ConnectionAborted, "Upload aborted"));
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let size = cmp::min(data.len(), self.pending.len() - self.offset);
+        data[..size].copy_from_slice(&self.pending[self.offset..(self.offset + size)]);
+        self.offset += size;
+
+        Ok(size)
+    }
+}
+
+// A request with a chunked body that is sent from a separate request thread
+// while data is written to it. Up to max_replay bytes of the written data are
+// kept, so that a failed request can be repeated. Dropping an unfinished
+// upload aborts the request
+pub struct Upload {
+    sender: Option<SyncSender<Vec<u8>>>,
+    receiver: Receiver<Result<Response, SendError>>,
+    aborted: Arc<AtomicBool>,
+    replay: Option<Vec<u8>>,
+    max_replay: u64,
+    size: u64,
+}
+
+impl Upload {
+    pub fn new(request: RequestBuilder, max_replay: u64) -> Result<Upload, SendError> {
+        let (sender, body_receiver) = mpsc::sync_channel(MAX_QUEUED_CHUNKS);
+        let aborted = Arc::new(AtomicBool::new(false));
+
+        let mut request = request;
+        request.body(Body::new(ChannelReader {
+            receiver: body_receiver,
+            aborted: aborted.clone(),
+            pending: Vec::new(),
+            offset: 0,
+        }));
+
+        let receiver = try!(request::spawn(move || {
+            request.send().map_err(SendError::Request)
+        }));
+
+        Ok(Upload {
+            sender: Some(sender),
+            receiver: receiver,
+            aborted: aborted,
+            replay: Some(Vec::new()),
+            max_replay: max_replay,
+            size: 0,
+        })
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    // All data written so far, also what failed because the request finished
+    // already, unless that was more than max_replay bytes
+    pub fn take_replay(&mut self) -> Option<Vec<u8>> {
+        self.replay.take()
+    }
+
+    // Fails with ErrorKind::Other if flushing, and with ErrorKind::BrokenPipe
+    // if the request finished already, e.g. because the connection was lost
+    pub fn write(&mut self, data: Vec<u8>, flushing: &Flushing) -> io::Result<()> {
+        let len = data.len() as u64;
+
+        // Data is kept even if sending it fails because the request finished,
+        // as it has to be sent again with a repeated request then
+        let replay_len = self.replay.as_ref().map(Vec::len);
+        match replay_len {
+            Some(replay_len) if replay_len as u64 + len <= self.max_replay => {
+                self.replay.as_mut().unwrap().extend_from_slice(&data);
+            }
+            _ => self.replay = None,
+        }
+
+        let mut data = data;
+        loop {
+            match self.sender.as_ref().unwrap().try_send(data) {
+                Ok(()) => break,
+                Err(TrySendError::Full(d)) => {
+                    data = d;
+                    if flushing.wait_timeout(Duration::from_millis(WAIT_TIMEOUT_MS)) {
+                        // Not sent, so also not kept
+                        if let (Some(replay), Some(kept)) = (self.replay.as_mut(), replay_len) {
+                            replay.truncate(kept);
+                        }
+                        return Err(io::Error::new(ErrorKind::Other, "Flushing"));
+                    }
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    return Err(io::Error::new(
+                        ErrorKind::BrokenPipe,
+                        "Request finished before the end",
+                    ));
+                }
+            }
+        }
+
+        self.size += len;
+
+        Ok(())
+    }
+
+    // Ends the body and waits for the response. If this is interrupted by
+    // flushing or times out, the request is aborted
+    pub fn finish(
+        mut self,
+        flushing: &Flushing,
+        timeout: Option<Duration>,
+    ) -> Result<Response, SendError> {
+        self.sender = None;
+        request::wait(&self.receiver, flushing, timeout)
+    }
+}
+
+impl Drop for Upload {
+    fn drop(&mut self) {
+        // Only has an effect if the body did not end yet. The request thread
+        // fails on its own then and is not waited for
+        self.aborted.store(true, Ordering::SeqCst);
+        self.sender = None;
+    }
+}
+
+impl fmt::Debug for Upload {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Upload").field("size", &self.size).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::str;
+    use std::thread;
+    use reqwest::{Client, StatusCode};
+
+    fn find(data: &[u8], pattern: &[u8]) -> Option<usize> {
+        data.windows(pattern.len()).position(|w| w == pattern)
+    }
+
+    // Minimal HTTP server that receives a single chunked request
+    fn receive_request(listener: TcpListener) -> (String, Vec<u8>) {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut data = Vec::new();
+        let mut buf = [0u8; 4096];
+
+        let header_end = loop {
+            let size = stream.read(&mut buf).unwrap();
+            data.extend_from_slice(&buf[..size]);
+            if let Some(idx) = find(&data, b"\r\n\r\n") {
+                break idx + 4;
+            }
+        };
+        let head = String::from_utf8(data[..header_end].to_vec()).unwrap();
+
+        let mut body = data[header_end..].to_vec();
+        while !body.ends_with(b"0\r\n\r\n") {
+            let size = stream.read(&mut buf).unwrap();
+            assert!(size > 0);
+            body.extend_from_slice(&buf[..size]);
+        }
+
+        let mut decoded = Vec::new();
+        let mut rest = &body[..];
+        loop {
+            let line_end = find(rest, b"\r\n").unwrap();
+            let len = usize::from_str_radix(str::from_utf8(&rest[..line_end]).unwrap(), 16)
+                .unwrap();
+            if len == 0 {
+                break;
+            }
+            decoded.extend_from_slice(&rest[(line_end + 2)..(line_end + 2 + len)]);
+            rest = &rest[(line_end + 2 + len + 2)..];
+        }
+
+        stream
+            .write_all(b"HTTP/1.1 201 Created\r\nContent-Length: 0\r\n\r\n")
+            .unwrap();
+
+        (head, decoded)
+    }
+
+    // Receives the head and the raw body of a request until the client
+    // closes the connection
+    fn receive_aborted(listener: TcpListener) -> (String, Vec<u8>) {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut data = Vec::new();
+        let mut buf = [0u8; 4096];
+
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(size) => data.extend_from_slice(&buf[..size]),
+            }
+        }
+
+        let header_end = find(&data, b"\r\n\r\n").unwrap() + 4;
+        let head = String::from_utf8(data[..header_end].to_vec()).unwrap();
+
+        (head, data[header_end..].to_vec())
+    }
+
+    #[test]
+    fn test_upload() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/upload", listener.local_addr().unwrap());
+        let server = thread::spawn(move || receive_request(listener));
+
+        let flushing = Flushing::new();
+        let mut upload = Upload::new(Client::new().put(uri.as_str()), 1024).unwrap();
+        upload.write(b"Hello ".to_vec(), &flushing).unwrap();
+        upload.write(b"World".to_vec(), &flushing).unwrap();
+        assert_eq!(upload.size(), 11);
+
+        let response = upload.finish(&flushing, None).unwrap();
+        assert_eq!(response.status(), StatusCode::Created);
+
+        let (head, body) = server.join().unwrap();
+        assert!(head.starts_with("PUT /upload HTTP/1.1\r\n"));
+        assert!(head.to_lowercase().contains("transfer-encoding: chunked"));
+        assert_eq!(body, b"Hello World");
+    }
+
+    #[test]
+    fn test_abort() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/upload", listener.local_addr().unwrap());
+        let server = thread::spawn(move || receive_aborted(listener));
+
+        let flushing = Flushing::new();
+        let mut upload = Upload::new(Client::new().put(uri.as_str()), 1024).unwrap();
+        upload.write(b"Hello".to_vec(), &flushing).unwrap();
+        // Give the request thread time to send what was written
+        thread::sleep(Duration::from_millis(200));
+        drop(upload);
+
+        let (head, body) = server.join().unwrap();
+        assert!(head.starts_with("PUT /upload HTTP/1.1\r\n"));
+        assert!(body.starts_with(b"5\r\nHello\r\n"));
+        assert!(find(&body, b"0\r\n\r\n").is_none());
+    }
+
+    #[test]
+    fn test_finish_unlock() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/upload", listener.local_addr().unwrap());
+        // Accepts the connection but never responds
+        let server = thread::spawn(move || receive_aborted(listener));
+
+        let flushing = Flushing::new();
+        let upload = Upload::new(Client::new().put(uri.as_str()), 1024).unwrap();
+        assert!(match upload.finish(&flushing, Some(Duration::from_millis(300))) {
+            Err(SendError::TimedOut) => true,
+            _ => false,
+        });
+
+        let upload = Upload::new(Client::new().put(uri.as_str()), 1024).unwrap();
+        flushing.set_flushing(true);
+        assert!(match upload.finish(&flushing, None) {
+            Err(SendError::Flushing) => true,
+            _ => false,
+        });
+        drop(server);
+    }
+
+    #[test]
+    fn test_replay() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/upload", listener.local_addr().unwrap());
+        let server = thread::spawn(move || receive_request(listener));
+
+        let flushing = Flushing::new();
+        let mut upload = Upload::new(Client::new().put(uri.as_str()), 8).unwrap();
+        upload.write(b"Hello".to_vec(), &flushing).unwrap();
+        assert_eq!(upload.take_replay(), Some(b"Hello".to_vec()));
+        upload.finish(&flushing, None).unwrap();
+        server.join().unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/upload", listener.local_addr().unwrap());
+        let server = thread::spawn(move || receive_request(listener));
+
+        let mut upload = Upload::new(Client::new().put(uri.as_str()), 8).unwrap();
+        upload.write(b"Hello".to_vec(), &flushing).unwrap();
+        upload.write(b"World".to_vec(), &flushing).unwrap();
+        assert_eq!(upload.take_replay(), None);
+        upload.finish(&flushing, None).unwrap();
+        assert_eq!(server.join().unwrap().1, b"HelloWorld");
+    }
+}
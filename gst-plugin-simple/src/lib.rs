@@ -14,6 +14,14 @@ extern crate gstreamer as gst;
 extern crate gstreamer_base as gst_base;
 
 extern crate url;
+#[macro_use]
+extern crate lazy_static;
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use gst_plugin::properties::Property;
 
 pub mod source;
 pub mod sink;
@@ -24,3 +32,136 @@ pub type UriValidator = Fn(&url::Url)
                             + Send
                             + Sync
                             + 'static;
+
+// Called from another thread than the streaming thread to interrupt any
+// blocking operations, e.g. on flushing seeks or state changes
+pub trait Unlock: Send + Sync + 'static {
+    fn unlock(&self);
+    fn unlock_stop(&self);
+}
+
+// Properties that are set and read without locking the implementation, so
+// that this does not wait while it is blocked, e.g. waiting for the network
+pub trait PropertyHandle: Send + Sync + 'static {
+    // Returns false for properties that are not handled here
+    fn set_property(&self, _property: &Property, _value: &glib::Value) -> bool {
+        false
+    }
+
+    // Returns None for properties that are not handled here
+    fn get_property(&self, property: &Property) -> Option<glib::Value>;
+}
+
+impl<A: PropertyHandle, B: PropertyHandle> PropertyHandle for (A, B) {
+    fn set_property(&self, property: &Property, value: &glib::Value) -> bool {
+        self.0.set_property(property, value) || self.1.set_property(property, value)
+    }
+
+    fn get_property(&self, property: &Property) -> Option<glib::Value> {
+        self.0
+            .get_property(property)
+            .or_else(|| self.1.get_property(property))
+    }
+}
+
+// Queries that are answered without locking the implementation
+pub trait QueryHandle: Send + Sync + 'static {
+    // Returns true if the query was handled
+    fn query(&self, query: &mut gst::QueryRef) -> bool;
+}
+
+pub trait SettingsImpl: Clone + Send + 'static {
+    // Returns false for properties that are not part of the settings
+    fn set_property(&mut self, property: &Property, value: &glib::Value) -> bool;
+    fn get_property(&self, property: &Property) -> Option<glib::Value>;
+}
+
+// Settings that are changed by the property handle, while the implementation
+// works on its own copy that it updates before using it
+#[derive(Debug, Default)]
+pub struct SharedSettings<S>(Arc<(Mutex<S>, AtomicBool)>);
+
+impl<S> Clone for SharedSettings<S> {
+    fn clone(&self) -> Self {
+        SharedSettings(self.0.clone())
+    }
+}
+
+impl<S: SettingsImpl> SharedSettings<S> {
+    pub fn new(settings: S) -> Self {
+        SharedSettings(Arc::new((Mutex::new(settings), AtomicBool::new(false))))
+    }
+
+    pub fn get(&self) -> S {
+        (self.0).0.lock().unwrap().clone()
+    }
+
+    // Replaces the copy if properties were set since the last update
+    pub fn update(&self, settings: &mut S) {
+        let &(ref lock, ref changed) = &*self.0;
+        if changed.swap(false, Ordering::SeqCst) {
+            *settings = lock.lock().unwrap().clone();
+        }
+    }
+}
+
+impl<S: SettingsImpl> PropertyHandle for SharedSettings<S> {
+    fn set_property(&self, property: &Property, value: &glib::Value) -> bool {
+        let &(ref lock, ref changed) = &*self.0;
+        let mut settings = lock.lock().unwrap();
+
+        if !settings.set_property(property, value) {
+            return false;
+        }
+        changed.store(true, Ordering::SeqCst);
+
+        true
+    }
+
+    fn get_property(&self, property: &Property) -> Option<glib::Value> {
+        (self.0).0.lock().unwrap().get_property(property)
+    }
+}
+
+// Simple flushing flag that can be waited on with a timeout and is set
+// whenever unlock is called
+#[derive(Debug, Clone, Default)]
+pub struct Flushing(Arc<(Mutex<bool>, Condvar)>);
+
+impl Flushing {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn is_flushing(&self) -> bool {
+        *(self.0).0.lock().unwrap()
+    }
+
+    pub fn set_flushing(&self, flushing: bool) {
+        let &(ref lock, ref cond) = &*self.0;
+        *lock.lock().unwrap() = flushing;
+        cond.notify_all();
+    }
+
+    // Returns true if flushing, otherwise waits at most until the timeout
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let &(ref lock, ref cond) = &*self.0;
+        let flushing = lock.lock().unwrap();
+        if *flushing {
+            return true;
+        }
+
+        let (flushing, _) = cond.wait_timeout(flushing, timeout).unwrap();
+        *flushing
+    }
+}
+
+impl Unlock for Flushing {
+    fn unlock(&self) {
+        self.set_flushing(true);
+    }
+
+    fn unlock_stop(&self) {
+        self.set_flushing(false);
+    }
+}
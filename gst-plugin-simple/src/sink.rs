@@ -26,20 +26,92 @@ use gst_plugin::error::*;
 pub use gst_plugin::base_sink::RsBaseSink;
 
 use UriValidator;
+use Unlock;
+use PropertyHandle;
+
+lazy_static! {
+    static ref CAT: gst::DebugCategory = {
+        gst::DebugCategory::new(
+            "rssink",
+            gst::DebugColorFlags::empty(),
+            "Rust sink base class",
+        )
+    };
+}
 
 pub trait SinkImpl: Send + 'static {
     fn uri_validator(&self) -> Box<UriValidator>;
 
+    fn unlock_handle(&self) -> Option<Box<Unlock>> {
+        None
+    }
+
+    fn property_handle(&self) -> Option<Box<PropertyHandle>> {
+        None
+    }
+
     fn start(&mut self, sink: &RsBaseSink, uri: Url) -> Result<(), ErrorMessage>;
     fn stop(&mut self, sink: &RsBaseSink) -> Result<(), ErrorMessage>;
     fn render(&mut self, sink: &RsBaseSink, buffer: &gst::BufferRef) -> Result<(), FlowError>;
+
+    fn set_caps(&mut self, _sink: &RsBaseSink, _caps: &gst::CapsRef) -> Result<(), ErrorMessage> {
+        Ok(())
+    }
+
+    fn is_seekable(&self, _sink: &RsBaseSink) -> bool {
+        false
+    }
+
+    // Current write position in bytes
+    fn get_position(&self, _sink: &RsBaseSink) -> Option<u64> {
+        None
+    }
+
+    // Only called if is_seekable() returned true, whenever a BYTES segment
+    // starts somewhere else than the current position
+    fn seek(&mut self, _sink: &RsBaseSink, offset: u64) -> Result<(), ErrorMessage> {
+        Err(error_msg!(
+            gst::ResourceError::Seek,
+            ["Seeking to {} not supported", offset]
+        ))
+    }
+
+    fn segment(&mut self, _sink: &RsBaseSink, _segment: &gst::Segment) -> Result<(), ErrorMessage> {
+        Ok(())
+    }
+
+    fn eos(&mut self, _sink: &RsBaseSink) -> Result<(), ErrorMessage> {
+        Ok(())
+    }
+
+    // Serialized custom downstream events, e.g. GstForceKeyUnit
+    fn custom_event(
+        &mut self,
+        _sink: &RsBaseSink,
+        _structure: &gst::StructureRef,
+    ) -> Result<(), ErrorMessage> {
+        Ok(())
+    }
+
+    // Only called for properties that are not handled by the property handle
+    fn set_property(&mut self, sink: &RsBaseSink, property: &Property, _value: &glib::Value) {
+        gst_warning!(CAT, obj: sink, "Unhandled property {:?}", property);
+    }
+
+    fn get_property(&self, sink: &RsBaseSink, property: &Property) -> Result<glib::Value, ()> {
+        gst_warning!(CAT, obj: sink, "Unhandled property {:?}", property);
+        Err(())
+    }
 }
 
 struct Sink {
     cat: gst::DebugCategory,
     uri: Mutex<(Option<Url>, bool)>,
     uri_validator: Box<UriValidator>,
+    unlock_handle: Option<Box<Unlock>>,
+    property_handle: Option<Box<PropertyHandle>>,
     imp: Mutex<Box<SinkImpl>>,
+    properties: Vec<Property<'static>>,
 }
 
 static PROPERTIES: [Property; 1] = [
@@ -57,14 +129,13 @@ impl Sink {
         let sink_impl = (sink_info.create_instance)(sink);
 
         Self {
-            cat: gst::DebugCategory::new(
-                "rssink",
-                gst::DebugColorFlags::empty(),
-                "Rust sink base class",
-            ),
+            cat: CAT.clone(),
             uri: Mutex::new((None, false)),
             uri_validator: sink_impl.uri_validator(),
+            unlock_handle: sink_impl.unlock_handle(),
+            property_handle: sink_impl.property_handle(),
             imp: Mutex::new(sink_impl),
+            properties: sink_info.properties.clone(),
         }
     }
 
@@ -85,7 +156,9 @@ impl Sink {
         );
         klass.add_pad_template(pad_template);
 
-        klass.install_properties(&PROPERTIES);
+        let mut properties = PROPERTIES.to_vec();
+        properties.extend_from_slice(&sink_info.properties);
+        klass.install_properties(&properties);
     }
 
     fn init(element: &RsBaseSink, sink_info: &SinkInfo) -> Box<BaseSinkImpl<RsBaseSink>> {
@@ -137,22 +210,54 @@ impl Sink {
 
 impl ObjectImpl<RsBaseSink> for Sink {
     fn set_property(&self, obj: &glib::Object, id: u32, value: &glib::Value) {
-        let prop = &PROPERTIES[id as usize];
-
-        match *prop {
-            Property::String("uri", ..) => {
-                self.set_uri(obj, value.get()).unwrap();
+        if let Some(prop) = PROPERTIES.get(id as usize) {
+            match *prop {
+                Property::String("uri", ..) => {
+                    self.set_uri(obj, value.get()).unwrap();
+                }
+                _ => {
+                    gst_warning!(self.cat, obj: obj, "Unhandled property {:?}", prop);
+                }
             }
-            _ => unimplemented!(),
+        } else {
+            let sink = obj.clone().dynamic_cast::<RsBaseSink>().unwrap();
+            let prop = &self.properties[id as usize - PROPERTIES.len()];
+
+            if self.property_handle
+                .as_ref()
+                .map(|handle| handle.set_property(prop, value))
+                .unwrap_or(false)
+            {
+                return;
+            }
+
+            let sink_impl = &mut self.imp.lock().unwrap();
+            sink_impl.set_property(&sink, prop, value);
         }
     }
 
     fn get_property(&self, obj: &glib::Object, id: u32) -> Result<glib::Value, ()> {
-        let prop = &PROPERTIES[id as usize];
+        if let Some(prop) = PROPERTIES.get(id as usize) {
+            match *prop {
+                Property::String("uri", ..) => Ok(self.get_uri(obj).to_value()),
+                _ => {
+                    gst_warning!(self.cat, obj: obj, "Unhandled property {:?}", prop);
+                    Err(())
+                }
+            }
+        } else {
+            let sink = obj.clone().dynamic_cast::<RsBaseSink>().unwrap();
+            let prop = &self.properties[id as usize - PROPERTIES.len()];
+
+            if let Some(value) = self.property_handle
+                .as_ref()
+                .and_then(|handle| handle.get_property(prop))
+            {
+                return Ok(value);
+            }
 
-        match *prop {
-            Property::String("uri", ..) => Ok(self.get_uri(obj).to_value()),
-            _ => unimplemented!(),
+            let sink_impl = &self.imp.lock().unwrap();
+            sink_impl.get_property(&sink, prop)
         }
     }
 }
@@ -231,6 +336,125 @@ impl BaseSinkImpl<RsBaseSink> for Sink {
             }
         }
     }
+
+    fn unlock(&self, sink: &RsBaseSink) -> bool {
+        gst_debug!(self.cat, obj: sink, "Unlocking");
+
+        if let Some(ref unlock_handle) = self.unlock_handle {
+            unlock_handle.unlock();
+        }
+
+        true
+    }
+
+    fn unlock_stop(&self, sink: &RsBaseSink) -> bool {
+        gst_debug!(self.cat, obj: sink, "Stopping unlocking");
+
+        if let Some(ref unlock_handle) = self.unlock_handle {
+            unlock_handle.unlock_stop();
+        }
+
+        true
+    }
+
+    fn set_caps(&self, sink: &RsBaseSink, caps: &gst::CapsRef) -> bool {
+        let sink_impl = &mut self.imp.lock().unwrap();
+
+        gst_debug!(self.cat, obj: sink, "Setting caps {:?}", caps);
+
+        match sink_impl.set_caps(sink, caps) {
+            Ok(..) => true,
+            Err(ref msg) => {
+                gst_error!(self.cat, obj: sink, "Failed to set caps: {:?}", msg);
+                msg.post(sink);
+                false
+            }
+        }
+    }
+
+    fn event(&self, sink: &RsBaseSink, event: gst::Event) -> bool {
+        use gst::EventView;
+
+        let res = match event.view() {
+            EventView::Segment(ref e) => {
+                let segment = e.get_segment();
+                let sink_impl = &mut self.imp.lock().unwrap();
+
+                gst_debug!(self.cat, obj: sink, "Got segment {:?}", segment);
+
+                sink_impl.segment(sink, segment).and_then(|_| {
+                    if segment.get_format() != gst::Format::Bytes || !sink_impl.is_seekable(sink) {
+                        return Ok(());
+                    }
+
+                    let start = segment.get_start();
+                    if sink_impl.get_position(sink) == Some(start) {
+                        return Ok(());
+                    }
+
+                    gst_debug!(self.cat, obj: sink, "Seeking to {}", start);
+                    sink_impl.seek(sink, start)
+                })
+            }
+            EventView::Eos(..) => {
+                let sink_impl = &mut self.imp.lock().unwrap();
+
+                gst_debug!(self.cat, obj: sink, "End of stream");
+                sink_impl.eos(sink)
+            }
+            EventView::CustomDownstream(..) => match event.get_structure() {
+                Some(structure) => {
+                    let sink_impl = &mut self.imp.lock().unwrap();
+
+                    gst_debug!(self.cat, obj: sink, "Got custom event {:?}", structure);
+                    sink_impl.custom_event(sink, structure)
+                }
+                None => Ok(()),
+            },
+            _ => Ok(()),
+        };
+
+        if let Err(ref msg) = res {
+            gst_error!(self.cat, obj: sink, "Failed to handle event: {:?}", msg);
+            msg.post(sink);
+            return false;
+        }
+
+        BaseSink::parent_event(sink, event)
+    }
+
+    fn query(&self, sink: &RsBaseSink, query: &mut gst::QueryRef) -> bool {
+        use gst::QueryView;
+
+        match query.view_mut() {
+            QueryView::Position(ref mut q) => {
+                let (fmt, _) = q.get();
+                if fmt == gst::Format::Bytes {
+                    let sink_impl = &self.imp.lock().unwrap();
+
+                    match sink_impl.get_position(sink) {
+                        None => return false,
+                        Some(position) => {
+                            q.set(fmt, position as i64);
+                            return true;
+                        }
+                    }
+                }
+            }
+            QueryView::Seeking(ref mut q) => {
+                let (fmt, _, _, _) = q.get();
+                if fmt == gst::Format::Bytes {
+                    let sink_impl = &self.imp.lock().unwrap();
+
+                    q.set(sink_impl.is_seekable(sink), 0, -1);
+                    return true;
+                }
+            }
+            _ => (),
+        }
+
+        BaseSink::parent_query(sink, query)
+    }
 }
 
 impl URIHandlerImpl for Sink {
@@ -252,6 +476,7 @@ pub struct SinkInfo {
     pub rank: u32,
     pub create_instance: fn(&RsBaseSink) -> Box<SinkImpl>,
     pub protocols: Vec<String>,
+    pub properties: Vec<Property<'static>>,
 }
 
 struct SinkStatic {
@@ -295,11 +520,17 @@ pub fn sink_register(plugin: &gst::Plugin, sink_info: SinkInfo) {
     let name = sink_info.name.clone();
     let rank = sink_info.rank;
 
+    let type_ = sink_register_type(sink_info);
+    gst::Element::register(plugin, &name, rank, type_);
+}
+
+// Registers only the type, e.g. to create elements without a plugin in tests.
+// This can be done only once per name
+pub fn sink_register_type(sink_info: SinkInfo) -> glib::Type {
     let sink_static = SinkStatic {
-        name: format!("Sink-{}", name),
+        name: format!("Sink-{}", sink_info.name),
         sink_info: sink_info,
     };
 
-    let type_ = register_type(sink_static);
-    gst::Element::register(plugin, &name, rank, type_);
+    register_type(sink_static)
 }
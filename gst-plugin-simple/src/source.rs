@@ -28,13 +28,45 @@ use gst_plugin::error::*;
 pub use gst_plugin::base_src::RsBaseSrc;
 
 use UriValidator;
+use Unlock;
+use PropertyHandle;
+use QueryHandle;
+
+lazy_static! {
+    static ref CAT: gst::DebugCategory = {
+        gst::DebugCategory::new(
+            "rssource",
+            gst::DebugColorFlags::empty(),
+            "Rust source base class",
+        )
+    };
+}
 
 pub trait SourceImpl: Send + 'static {
     fn uri_validator(&self) -> Box<UriValidator>;
 
+    fn unlock_handle(&self) -> Option<Box<Unlock>> {
+        None
+    }
+
+    fn property_handle(&self) -> Option<Box<PropertyHandle>> {
+        None
+    }
+
+    // Allows answering queries before the base class, without locking the
+    // implementation
+    fn query_handle(&self) -> Option<Box<QueryHandle>> {
+        None
+    }
+
     fn is_seekable(&self, src: &RsBaseSrc) -> bool;
     fn get_size(&self, src: &RsBaseSrc) -> Option<u64>;
 
+    // Queried once after a successful start, before caps are negotiated
+    fn get_caps(&self, _src: &RsBaseSrc) -> Option<gst::Caps> {
+        None
+    }
+
     fn start(&mut self, src: &RsBaseSrc, uri: Url) -> Result<(), ErrorMessage>;
     fn stop(&mut self, src: &RsBaseSrc) -> Result<(), ErrorMessage>;
     fn fill(
@@ -45,13 +77,39 @@ pub trait SourceImpl: Send + 'static {
         buffer: &mut gst::BufferRef,
     ) -> Result<(), FlowError>;
     fn seek(&mut self, src: &RsBaseSrc, start: u64, stop: Option<u64>) -> Result<(), ErrorMessage>;
+
+    // Allows to provide buffers without copying into a newly allocated one.
+    // Returning None falls back to fill()
+    fn create(
+        &mut self,
+        _src: &RsBaseSrc,
+        _offset: u64,
+        _length: u32,
+    ) -> Option<Result<gst::Buffer, FlowError>> {
+        None
+    }
+
+    // Only called for properties that are not handled by the property handle
+    fn set_property(&mut self, src: &RsBaseSrc, property: &Property, _value: &glib::Value) {
+        gst_warning!(CAT, obj: src, "Unhandled property {:?}", property);
+    }
+
+    fn get_property(&self, src: &RsBaseSrc, property: &Property) -> Result<glib::Value, ()> {
+        gst_warning!(CAT, obj: src, "Unhandled property {:?}", property);
+        Err(())
+    }
 }
 
 struct Source {
     cat: gst::DebugCategory,
     uri: Mutex<(Option<Url>, bool)>,
     uri_validator: Box<UriValidator>,
+    unlock_handle: Option<Box<Unlock>>,
+    property_handle: Option<Box<PropertyHandle>>,
+    query_handle: Option<Box<QueryHandle>>,
     imp: Mutex<Box<SourceImpl>>,
+    caps: Mutex<Option<gst::Caps>>,
+    properties: Vec<Property<'static>>,
     push_only: bool,
 }
 
@@ -70,14 +128,15 @@ impl Source {
         let source_impl = (source_info.create_instance)(source);
 
         Self {
-            cat: gst::DebugCategory::new(
-                "rssource",
-                gst::DebugColorFlags::empty(),
-                "Rust source base class",
-            ),
+            cat: CAT.clone(),
             uri: Mutex::new((None, false)),
             uri_validator: source_impl.uri_validator(),
+            unlock_handle: source_impl.unlock_handle(),
+            property_handle: source_impl.property_handle(),
+            query_handle: source_impl.query_handle(),
             imp: Mutex::new(source_impl),
+            caps: Mutex::new(None),
+            properties: source_info.properties.clone(),
             push_only: source_info.push_only,
         }
     }
@@ -99,7 +158,9 @@ impl Source {
         );
         klass.add_pad_template(pad_template);
 
-        klass.install_properties(&PROPERTIES);
+        let mut properties = PROPERTIES.to_vec();
+        properties.extend_from_slice(&source_info.properties);
+        klass.install_properties(&properties);
     }
 
     fn init(element: &RsBaseSrc, source_info: &SourceInfo) -> Box<BaseSrcImpl<RsBaseSrc>> {
@@ -151,22 +212,54 @@ impl Source {
 
 impl ObjectImpl<RsBaseSrc> for Source {
     fn set_property(&self, obj: &glib::Object, id: u32, value: &glib::Value) {
-        let prop = &PROPERTIES[id as usize];
-
-        match *prop {
-            Property::String("uri", ..) => {
-                self.set_uri(obj, value.get()).unwrap();
+        if let Some(prop) = PROPERTIES.get(id as usize) {
+            match *prop {
+                Property::String("uri", ..) => {
+                    self.set_uri(obj, value.get()).unwrap();
+                }
+                _ => {
+                    gst_warning!(self.cat, obj: obj, "Unhandled property {:?}", prop);
+                }
+            }
+        } else {
+            let src = obj.clone().dynamic_cast::<RsBaseSrc>().unwrap();
+            let prop = &self.properties[id as usize - PROPERTIES.len()];
+
+            if self.property_handle
+                .as_ref()
+                .map(|handle| handle.set_property(prop, value))
+                .unwrap_or(false)
+            {
+                return;
             }
-            _ => unimplemented!(),
+
+            let source_impl = &mut self.imp.lock().unwrap();
+            source_impl.set_property(&src, prop, value);
         }
     }
 
     fn get_property(&self, obj: &glib::Object, id: u32) -> Result<glib::Value, ()> {
-        let prop = &PROPERTIES[id as usize];
+        if let Some(prop) = PROPERTIES.get(id as usize) {
+            match *prop {
+                Property::String("uri", ..) => Ok(self.get_uri(obj).to_value()),
+                _ => {
+                    gst_warning!(self.cat, obj: obj, "Unhandled property {:?}", prop);
+                    Err(())
+                }
+            }
+        } else {
+            let src = obj.clone().dynamic_cast::<RsBaseSrc>().unwrap();
+            let prop = &self.properties[id as usize - PROPERTIES.len()];
+
+            if let Some(value) = self.property_handle
+                .as_ref()
+                .and_then(|handle| handle.get_property(prop))
+            {
+                return Ok(value);
+            }
 
-        match *prop {
-            Property::String("uri", ..) => Ok(self.get_uri(obj).to_value()),
-            _ => unimplemented!(),
+            let source_impl = &self.imp.lock().unwrap();
+            source_impl.get_property(&src, prop)
         }
     }
 }
@@ -194,6 +287,11 @@ impl BaseSrcImpl<RsBaseSrc> for Source {
         match source_impl.start(src, uri) {
             Ok(..) => {
                 gst_trace!(self.cat, obj: src, "Started successfully");
+
+                let caps = source_impl.get_caps(src);
+                gst_debug!(self.cat, obj: src, "Source provided caps {:?}", caps);
+                *self.caps.lock().unwrap() = caps;
+
                 true
             }
             Err(ref msg) => {
@@ -215,6 +313,7 @@ impl BaseSrcImpl<RsBaseSrc> for Source {
             Ok(..) => {
                 gst_trace!(self.cat, obj: src, "Stopped successfully");
                 self.uri.lock().unwrap().1 = false;
+                *self.caps.lock().unwrap() = None;
                 true
             }
             Err(ref msg) => {
@@ -238,6 +337,14 @@ impl BaseSrcImpl<RsBaseSrc> for Source {
             _ => (),
         }
 
+        if self.query_handle
+            .as_ref()
+            .map(|handle| handle.query(query))
+            .unwrap_or(false)
+        {
+            return true;
+        }
+
         BaseSrc::parent_query(src, query)
     }
 
@@ -274,6 +381,44 @@ impl BaseSrcImpl<RsBaseSrc> for Source {
         }
     }
 
+    fn create(
+        &self,
+        src: &RsBaseSrc,
+        offset: u64,
+        length: u32,
+    ) -> Result<gst::Buffer, gst::FlowReturn> {
+        // Don't keep the implementation locked if we fall back to fill()
+        let res = {
+            let source_impl = &mut self.imp.lock().unwrap();
+            source_impl.create(src, offset, length)
+        };
+
+        match res {
+            None => BaseSrc::parent_create(src, offset, length),
+            Some(Ok(buffer)) => {
+                gst_trace!(
+                    self.cat,
+                    obj: src,
+                    "Created buffer {:?} with offset {} and length {}",
+                    buffer,
+                    offset,
+                    length
+                );
+                Ok(buffer)
+            }
+            Some(Err(flow_error)) => {
+                gst_error!(self.cat, obj: src, "Failed to create: {:?}", flow_error);
+                match flow_error {
+                    FlowError::NotNegotiated(ref msg) | FlowError::Error(ref msg) => {
+                        msg.post(src);
+                    }
+                    _ => (),
+                }
+                Err(flow_error.to_native())
+            }
+        }
+    }
+
     fn do_seek(&self, src: &RsBaseSrc, segment: &mut gst::Segment) -> bool {
         let source_impl = &mut self.imp.lock().unwrap();
 
@@ -295,6 +440,38 @@ impl BaseSrcImpl<RsBaseSrc> for Source {
         }
     }
 
+    fn unlock(&self, src: &RsBaseSrc) -> bool {
+        gst_debug!(self.cat, obj: src, "Unlocking");
+
+        if let Some(ref unlock_handle) = self.unlock_handle {
+            unlock_handle.unlock();
+        }
+
+        true
+    }
+
+    fn unlock_stop(&self, src: &RsBaseSrc) -> bool {
+        gst_debug!(self.cat, obj: src, "Stopping unlocking");
+
+        if let Some(ref unlock_handle) = self.unlock_handle {
+            unlock_handle.unlock_stop();
+        }
+
+        true
+    }
+
+    fn get_caps(&self, src: &RsBaseSrc, filter: Option<&gst::CapsRef>) -> Option<gst::Caps> {
+        let caps = match *self.caps.lock().unwrap() {
+            Some(ref caps) => caps.clone(),
+            None => return BaseSrc::parent_get_caps(src, filter),
+        };
+
+        match filter {
+            Some(filter) => Some(filter.intersect_with_mode(&caps, gst::CapsIntersectMode::First)),
+            None => Some(caps),
+        }
+    }
+
     fn is_seekable(&self, src: &RsBaseSrc) -> bool {
         let source_impl = &self.imp.lock().unwrap();
         source_impl.is_seekable(src)
@@ -325,6 +502,7 @@ pub struct SourceInfo {
     pub rank: u32,
     pub create_instance: fn(&RsBaseSrc) -> Box<SourceImpl>,
     pub protocols: Vec<String>,
+    pub properties: Vec<Property<'static>>,
     pub push_only: bool,
 }
 
@@ -369,11 +547,17 @@ pub fn source_register(plugin: &gst::Plugin, source_info: SourceInfo) {
     let name = source_info.name.clone();
     let rank = source_info.rank;
 
+    let type_ = source_register_type(source_info);
+    gst::Element::register(plugin, &name, rank, type_);
+}
+
+// Registers only the type, e.g. to create elements without a plugin in tests.
+// This can be done only once per name
+pub fn source_register_type(source_info: SourceInfo) -> glib::Type {
     let source_static = SourceStatic {
-        name: format!("Source-{}", name),
+        name: format!("Source-{}", source_info.name),
         source_info: source_info,
     };
 
-    let type_ = register_type(source_static);
-    gst::Element::register(plugin, &name, rank, type_);
+    register_type(source_static)
 }
@@ -57,7 +57,7 @@ macro_rules! error_msg(
     }};
 );
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ErrorMessage {
     error_domain: glib_ffi::GQuark,
     error_code: i32,